@@ -0,0 +1,49 @@
+//! Feeds arbitrary audio (NaN, infinities and denormals included) through the compressor with arbitrary settings, and
+//! checks that the gain stays finite and within what the settings allow, and that audio still comes out as audio.
+//!
+//! Run with `cargo fuzz run process` from the repository's root.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use funih::offline::{self, OfflineCompressor};
+use libfuzzer_sys::fuzz_target;
+
+const SAMPLE_RATES: [f32; 5] = [22050.0, 44100.0, 48000.0, 96000.0, 192000.0];
+/// Anything up to +60 dBFS still counts as audio, which has to come out finite.
+const MAX_AUDIO: f32 = 1000.0;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    sample_rate: u8,
+    /// One for every parameter, spread out over its whole range like a host's normalized values.
+    params: Vec<u16>,
+    frames: Vec<[f32; 2]>,
+}
+
+fuzz_target!(|input: Input| {
+    let sample_rate = SAMPLE_RATES[input.sample_rate as usize % SAMPLE_RATES.len()];
+    let settings = offline::settings_from_normalized(
+        input
+            .params
+            .iter()
+            .map(|value| *value as f32 / u16::MAX as f32),
+    );
+    let mut compressor = OfflineCompressor::new(&settings, sample_rate).unwrap();
+    let max_gain = compressor.max_gain();
+
+    for frame in input.frames {
+        let output = compressor.process(frame);
+        let gain = compressor.gain();
+        assert!(
+            gain.is_finite() && gain >= 0.0 && gain <= max_gain * 1.0001,
+            "gain {gain} (at most {max_gain}) after {frame:?}"
+        );
+        if frame.iter().all(|sample| sample.abs() <= MAX_AUDIO) {
+            assert!(
+                output.iter().all(|sample| sample.is_finite()),
+                "{output:?} from {frame:?}"
+            );
+        }
+    }
+});
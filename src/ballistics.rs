@@ -0,0 +1,145 @@
+use crate::smoothing::one_pole_coefficient;
+
+/// Roughly how long the initial transient lasts, in seconds. The transient bleed fades out over this time.
+const TRANSIENT_TIME: f32 = 0.005;
+/// The longest the LF hold waits for a zero crossing, in seconds. Half a cycle of 10 Hz, anything lower than that
+/// isn't a bass note anymore (and DC or silence never crosses zero at all).
+const MAX_CYCLE_HOLD: f32 = 0.05;
+
+// TODO:
+// pump shapes (linear, exponential, scooped) for how the gain recovers across a tempo synced interval, evaluated
+// against the transport position every block. there's no tempo synced ducking mode yet, so nothing to shape. once
+// there is, the shape replaces the release stage here instead of running on top of it
+
+/// Per-sample coefficients for the `GainEnvelope`. Recalculated every block from the parameters.
+#[derive(Clone, Copy)]
+pub struct BallisticsCoefficients {
+    attack: f32,
+    fast_release: f32,
+    slow_release: f32,
+    /// How much of the slow release stage ends up in the output, `0.0..=1.0`.
+    blend: f32,
+    /// How much of the gain reduction gets held back at the start of an attack, `0.0..=1.0`.
+    bleed: f32,
+    bleed_decay: f32,
+    /// `MAX_CYCLE_HOLD` in samples.
+    max_cycle_hold: u32,
+}
+
+impl BallisticsCoefficients {
+    /// All times are in seconds, see `GainParams::attack_time`, `release_time` and `slow_release_time`.
+    pub fn new(
+        attack_time: f32,
+        release_time: f32,
+        slow_release_time: f32,
+        blend: f32,
+        bleed: f32,
+        sample_rate: f32,
+    ) -> Self {
+        Self {
+            attack: one_pole_coefficient(attack_time, sample_rate),
+            fast_release: one_pole_coefficient(release_time, sample_rate),
+            slow_release: one_pole_coefficient(slow_release_time, sample_rate),
+            blend: blend.clamp(0.0, 1.0),
+            bleed: bleed.clamp(0.0, 1.0),
+            bleed_decay: 1.0 - one_pole_coefficient(TRANSIENT_TIME, sample_rate),
+            max_cycle_hold: (MAX_CYCLE_HOLD * sample_rate) as u32,
+        }
+    }
+}
+
+/// Applies attack and release to the gain computer's output.
+///
+/// There are two release integrators running in parallel that share the same attack: a fast one that recovers
+/// quickly after transients, and a slow one that follows the average level. Blending in the slow one is the classic
+/// trick for bus compression that doesn't pump, since short peaks no longer pull the whole mix down and back up.
+///
+/// On top of that, the transient bleed holds back part of the gain reduction right as an attack starts, and lets it
+/// engage over the next few milliseconds. That lets some of the transient through without touching the attack time.
+///
+/// With the LF hold, the release only moves at zero crossings and catches up all at once. The gain then stays constant
+/// over every half cycle, instead of a fast release modulating the waveform of bass notes (which is heard as
+/// distortion).
+pub struct GainEnvelope {
+    fast: f32,
+    slow: f32,
+    /// The release blend from the last call to `next()`.
+    blend: f32,
+    attacking: bool,
+    /// The gain right before the current attack started.
+    onset_gain: f32,
+    /// How much of the way back up to `onset_gain` the output currently gets lifted. Decays to zero after an onset.
+    lift: f32,
+    /// How many samples the release has been held for, waiting for a zero crossing.
+    held: u32,
+}
+
+impl Default for GainEnvelope {
+    fn default() -> Self {
+        Self {
+            fast: 1.0,
+            slow: 1.0,
+            blend: 0.0,
+            attacking: false,
+            onset_gain: 1.0,
+            lift: 0.0,
+            held: 0,
+        }
+    }
+}
+
+impl GainEnvelope {
+    /// Advances the envelope by a single sample towards `target` (a linear gain factor), and returns the gain to apply.
+    ///
+    /// `zero_crossing` is `None` without the LF hold, or whether the audio crossed zero on this sample with it.
+    pub fn next(
+        &mut self,
+        target: f32,
+        coefficients: &BallisticsCoefficients,
+        zero_crossing: Option<bool>,
+    ) -> f32 {
+        // moving away from unity gain is the attack phase. for compression that means the gain going down, for
+        // upward expansion it means going up
+        let is_attack = |state: f32| (target - 1.0).abs() > (state - 1.0).abs();
+        let attacking = is_attack(self.fast);
+        if attacking && !self.attacking {
+            self.onset_gain = self.output();
+            self.lift = coefficients.bleed;
+        }
+        self.attacking = attacking;
+
+        // how many samples worth of release to apply, zero while waiting for a zero crossing
+        let release_steps = match zero_crossing {
+            Some(false) if !attacking && self.held < coefficients.max_cycle_hold => {
+                self.held += 1;
+                0
+            }
+            _ => std::mem::take(&mut self.held) + 1,
+        };
+        let step = |state: &mut f32, release: f32| {
+            let coefficient = if is_attack(*state) {
+                coefficients.attack
+            } else {
+                1.0 - (1.0 - release).powi(release_steps as i32)
+            };
+            *state += (target - *state) * coefficient;
+        };
+        step(&mut self.fast, coefficients.fast_release);
+        step(&mut self.slow, coefficients.slow_release);
+        self.blend = coefficients.blend;
+
+        let gain = self.output();
+        let gain = gain + (self.onset_gain - gain) * self.lift;
+        self.lift *= coefficients.bleed_decay;
+        gain
+    }
+
+    /// The blend of both release stages, without any transient bleed.
+    fn output(&self) -> f32 {
+        self.fast + (self.slow - self.fast) * self.blend
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,146 @@
+use std::{collections::HashMap, env, fs, process::ExitCode};
+
+use funih::offline::{self, OfflineCompressor};
+
+const USAGE: &str = "\
+Runs a WAV file through funih's compressor and writes the result as a 32-bit float WAV.
+
+Usage: funih-render <input.wav> <output.wav> [options]
+
+Options:
+  --factory-preset <name>  Starts from one of the factory presets
+  --preset <file.json>     Starts from the settings in a JSON file, like {\"threshold\": -18, \"ratio\": 4}
+  --<parameter id> <value> Sets a single parameter, like --threshold -18. These win over the presets
+
+Values are plain parameter values in the parameter's own unit (seconds, dB, linear gain), enums and on/off parameters
+are the index of their option. Everything else stays at its factory default.";
+
+/// Everything that was passed on the command line.
+struct Args {
+    input: String,
+    output: String,
+    values: HashMap<String, f32>,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match render(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns `None` if all that was asked for is the usage.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut files = Vec::new();
+    let mut preset_values = HashMap::new();
+    let mut flag_values = HashMap::new();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        }
+        let Some(name) = arg.strip_prefix("--") else {
+            files.push(arg);
+            continue;
+        };
+        let value = args
+            .next()
+            .ok_or_else(|| format!("--{name} needs a value"))?;
+        match name {
+            "factory-preset" => {
+                let values = offline::factory_preset(&value).ok_or_else(|| {
+                    let names: Vec<_> = offline::factory_preset_names().collect();
+                    format!(
+                        "There's no factory preset called '{value}', pick one of: {}",
+                        names.join(", ")
+                    )
+                })?;
+                preset_values.extend(values);
+            }
+            "preset" => {
+                let contents = fs::read_to_string(&value)
+                    .map_err(|err| format!("Could not read '{value}': {err}"))?;
+                let values: HashMap<String, f32> = serde_json::from_str(&contents)
+                    .map_err(|err| format!("'{value}' isn't a valid preset: {err}"))?;
+                preset_values.extend(values);
+            }
+            id => {
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("--{id} needs a number, got '{value}'"))?;
+                flag_values.insert(id.to_string(), value);
+            }
+        }
+    }
+
+    let [input, output] = <[String; 2]>::try_from(files)
+        .map_err(|_| String::from("Expected an input and an output file"))?;
+    preset_values.extend(flag_values);
+
+    Ok(Some(Args {
+        input,
+        output,
+        values: preset_values,
+    }))
+}
+
+fn render(args: &Args) -> Result<(), String> {
+    let mut reader = hound::WavReader::open(&args.input)
+        .map_err(|err| format!("Could not open '{}': {err}", args.input))?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+    if !(1..=2).contains(&channels) {
+        return Err(format!(
+            "'{}' has {channels} channels, only mono and stereo files are supported",
+            args.input
+        ));
+    }
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>(),
+        hound::SampleFormat::Int => {
+            let full_scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / full_scale))
+                .collect::<Result<Vec<_>, _>>()
+        }
+    }
+    .map_err(|err| format!("Could not read '{}': {err}", args.input))?;
+
+    let mut compressor = OfflineCompressor::new(&args.values, spec.sample_rate as f32)
+        .map_err(|id| format!("There's no parameter called '{id}'"))?;
+
+    let output_spec = hound::WavSpec {
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+        ..spec
+    };
+    let mut writer = hound::WavWriter::create(&args.output, output_spec)
+        .map_err(|err| format!("Could not create '{}': {err}", args.output))?;
+    for frame in samples.chunks_exact(channels) {
+        // mono gets processed as dual mono, like in the plugin
+        let output = compressor.process([frame[0], frame[channels - 1]]);
+        for sample in &output[..channels] {
+            writer
+                .write_sample(*sample)
+                .map_err(|err| format!("Could not write '{}': {err}", args.output))?;
+        }
+    }
+    writer
+        .finalize()
+        .map_err(|err| format!("Could not write '{}': {err}", args.output))
+}
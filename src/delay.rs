@@ -0,0 +1,40 @@
+/// A stereo delay line with a fixed maximum length, allocated up front so it can be used on the audio thread.
+#[derive(Default)]
+pub struct DelayLine {
+    buffer: Vec<[f32; 2]>,
+    write_index: usize,
+}
+
+impl DelayLine {
+    /// Allocates room for delays of up to `max_delay` samples. Don't call this from the audio thread.
+    pub fn new(max_delay: usize) -> Self {
+        Self {
+            buffer: vec![[0.0; 2]; max_delay + 1],
+            write_index: 0,
+        }
+    }
+
+    /// The longest delay this line can do, in samples.
+    pub fn max_delay(&self) -> usize {
+        self.buffer.len().saturating_sub(1)
+    }
+
+    /// Pushes `frame` in and returns the frame from `delay` samples ago. `delay` gets clamped to `max_delay()`.
+    pub fn process(&mut self, frame: [f32; 2], delay: usize) -> [f32; 2] {
+        if self.buffer.is_empty() {
+            return frame;
+        }
+
+        let len = self.buffer.len();
+        self.buffer[self.write_index] = frame;
+        let read_index = (self.write_index + len - delay.min(len - 1)) % len;
+        self.write_index = (self.write_index + 1) % len;
+
+        self.buffer[read_index]
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill([0.0; 2]);
+        self.write_index = 0;
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use fundsp::hacker::{shared, Shared};
+
+/// The longest capture the buffer gets allocated for.
+pub const MAX_CAPTURE_SECONDS: f32 = 30.0;
+
+/// Records the delta signal (dry minus wet), i.e. exactly what the compressor removed, so it can be saved as a WAV
+/// file and listened to or analyzed.
+///
+/// The buffer is allocated in `allocate()` (from `initialize()`), so the audio thread only ever copies into it.
+/// The audio thread never waits on the lock: if the editor happens to be saving, that block just doesn't get recorded.
+pub struct DeltaCapture {
+    /// Interleaved stereo samples.
+    buffer: Mutex<Vec<f32>>,
+    /// How many frames have been recorded so far.
+    position: AtomicUsize,
+    /// How many frames the current capture should contain.
+    length: AtomicUsize,
+    sample_rate: Shared,
+}
+
+impl Default for DeltaCapture {
+    fn default() -> Self {
+        Self {
+            buffer: Mutex::new(Vec::new()),
+            position: AtomicUsize::new(0),
+            length: AtomicUsize::new(0),
+            sample_rate: shared(44100.0),
+        }
+    }
+}
+
+impl DeltaCapture {
+    /// (Re)allocates the buffer for the given sample rate. This throws away whatever was captured before.
+    pub fn allocate(&self, sample_rate: f32) {
+        let frames = (MAX_CAPTURE_SECONDS * sample_rate) as usize;
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.clear();
+        buffer.resize(frames * 2, 0.0);
+
+        self.sample_rate.set(sample_rate);
+        self.length.store(0, Ordering::Release);
+        self.position.store(0, Ordering::Release);
+    }
+
+    /// Starts a new capture of `seconds` seconds, throwing away the previous one.
+    pub fn start(&self, seconds: f32) {
+        let max_frames = self.buffer.lock().unwrap().len() / 2;
+        let frames = ((seconds * self.sample_rate.value()) as usize).min(max_frames);
+
+        // stop first, so the audio thread doesn't write with a mismatched position and length
+        self.length.store(0, Ordering::Release);
+        self.position.store(0, Ordering::Release);
+        self.length.store(frames, Ordering::Release);
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.position.load(Ordering::Acquire) < self.length.load(Ordering::Acquire)
+    }
+
+    pub fn captured_frames(&self) -> usize {
+        self.position.load(Ordering::Acquire)
+    }
+
+    /// How far along the current capture is, from `0.0` to `1.0`.
+    pub fn progress(&self) -> f32 {
+        let length = self.length.load(Ordering::Acquire);
+        if length == 0 {
+            0.0
+        } else {
+            self.position.load(Ordering::Acquire) as f32 / length as f32
+        }
+    }
+
+    /// Called from the audio thread with a block of delta frames.
+    pub fn record(&self, frames: &[[f32; 2]]) {
+        let length = self.length.load(Ordering::Acquire);
+        let position = self.position.load(Ordering::Acquire);
+        if position >= length {
+            return;
+        }
+        let Ok(mut buffer) = self.buffer.try_lock() else {
+            return;
+        };
+
+        let count = frames.len().min(length - position);
+        let destination = &mut buffer[position * 2..(position + count) * 2];
+        for (destination, frame) in destination.chunks_exact_mut(2).zip(frames) {
+            destination.copy_from_slice(frame);
+        }
+        self.position.store(position + count, Ordering::Release);
+    }
+
+    /// Writes everything captured so far to a 32-bit float WAV file.
+    pub fn save_wav(&self, path: &str) -> Result<(), hound::Error> {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: self.sample_rate.value() as u32,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let frames = self.captured_frames();
+        let buffer = self.buffer.lock().unwrap();
+
+        let mut writer = hound::WavWriter::create(path, spec)?;
+        for sample in &buffer[..frames * 2] {
+            writer.write_sample(*sample)?;
+        }
+        writer.finalize()
+    }
+}
@@ -0,0 +1,244 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use nih_plug::prelude::Enum;
+
+use crate::{smoothing::one_pole_coefficient, LevelDetection};
+
+/// The averaging time of the RMS detector, in seconds.
+pub const RMS_WINDOW: f32 = 0.1;
+/// The detector only listens to this range, in Hz. Rumble and DC below it, and ultrasonic content above it, would
+/// otherwise trigger gain reduction on something nobody can hear.
+const BAND_LIMIT_LOW: f32 = 10.0;
+const BAND_LIMIT_HIGH: f32 = 20_000.0;
+/// The loudest sample the detector takes, +60 dBFS. Way past anything that's still audio, but it keeps the squares in
+/// the RMS detector from overflowing when a broken input sends something like `f32::MAX`, and the peak detector
+/// forgets about it again in a fraction of a second.
+const MAX_INPUT: f32 = 1000.0;
+
+/// Coefficients for the two allpass chains of the Hilbert transformer (Olli Niemitalo's design).
+/// The outputs of the two chains are ~90 degrees apart over most of the audible range.
+const HILBERT_COEFFICIENTS: [[f32; 4]; 2] = [
+    [0.6923878, 0.9360654322959, 0.9882295226860, 0.9987488452737],
+    [
+        0.4021921162426,
+        0.8561710882420,
+        0.9722909545651,
+        0.9952884791278,
+    ],
+];
+
+/// Which pair of channels the detector weights get applied to, see `GainParams::detector_weight_a`.
+#[derive(Clone, Copy, PartialEq, Enum)]
+pub enum DetectorChannels {
+    #[name = "Left/Right"]
+    LeftRight,
+    #[name = "Mid/Side"]
+    MidSide,
+}
+
+impl DetectorChannels {
+    /// Splits a frame into the two channels and scales them by `weights`. The result still goes through the linked
+    /// detector as a stereo frame, so at full weights Left/Right leaves the detection exactly as it was.
+    ///
+    /// Mid/Side is scaled by 1/sqrt(2) so the RMS reading comes out the same as Left/Right at full weights. The peak
+    /// reading of a centered source reads up to 3 dB higher though.
+    pub fn weight(&self, [left, right]: [f32; 2], weights: [f32; 2]) -> [f32; 2] {
+        let channels = match self {
+            DetectorChannels::LeftRight => [left, right],
+            DetectorChannels::MidSide => [
+                (left + right) * FRAC_1_SQRT_2,
+                (left - right) * FRAC_1_SQRT_2,
+            ],
+        };
+        [channels[0] * weights[0], channels[1] * weights[1]]
+    }
+}
+
+/// Per-sample coefficients for the `Detector`. These only need recalculating when the times or the sample rate change.
+#[derive(Clone, Copy)]
+pub struct DetectorCoefficients {
+    rms: f32,
+    peak_decay: f32,
+    rise: f32,
+    fall: f32,
+    /// The band limiting filters' `g / (1 + g)` coefficients.
+    high_pass: f32,
+    low_pass: f32,
+}
+
+impl DetectorCoefficients {
+    /// `rise_time` and `fall_time` are in seconds, see `GainParams::detector_rise_time`. So is `peak_decay`, see
+    /// `GainParams::peak_decay`.
+    pub fn new(rise_time: f32, fall_time: f32, peak_decay: f32, sample_rate: f32) -> Self {
+        Self {
+            rms: one_pole_coefficient(RMS_WINDOW, sample_rate),
+            peak_decay: 1.0 - one_pole_coefficient(peak_decay, sample_rate),
+            rise: one_pole_coefficient(rise_time, sample_rate),
+            fall: one_pole_coefficient(fall_time, sample_rate),
+            high_pass: filter_coefficient(BAND_LIMIT_LOW, sample_rate),
+            // at lower sample rates 20 kHz is too close to (or past) Nyquist
+            low_pass: filter_coefficient(BAND_LIMIT_HIGH.min(sample_rate * 0.45), sample_rate),
+        }
+    }
+}
+
+/// Measures the level of a (linked) stereo signal, both as RMS and as peak.
+///
+/// The raw RMS and peak readings are then smoothed by a separate rise/fall stage. This is independent from the
+/// compressor's attack and release: slowing down the fall makes the detector "hold on" to sustained material,
+/// while slowing down the rise makes it ignore short bursts.
+///
+/// There's also an analytic signal (Hilbert) envelope, which tracks the amplitude directly without any rectification
+/// ripple. That makes it a good fit for very fast attacks, where RMS is too slow and peak is too jittery.
+#[derive(Default)]
+pub struct Detector {
+    mean_square: f32,
+    peak: f32,
+    hilbert: [HilbertTransformer; 2],
+    band_limit: [BandLimit; 2],
+    rms_level: f32,
+    peak_level: f32,
+    hilbert_level: f32,
+}
+
+impl Detector {
+    pub fn process(&mut self, frame: [f32; 2], coefficients: &DetectorCoefficients) -> Readings {
+        // a single NaN would stick in the filters and the followers for good, and the gain computer turns a NaN level
+        // into the full `max_boost`. so NaNs count as silence and infinities as very loud
+        let frame = frame.map(|sample| {
+            if sample.is_nan() {
+                0.0
+            } else {
+                sample.clamp(-MAX_INPUT, MAX_INPUT)
+            }
+        });
+        let frame =
+            [0, 1].map(|channel| self.band_limit[channel].process(frame[channel], coefficients));
+
+        // both channels contribute to the same reading, so the stereo image doesn't shift when only one side is loud
+        // TODO:
+        // unlinked (dual-mono) mode with a detector and gain per channel. that's also when per-channel output trims
+        // would make sense, to rebalance channels whose different gain reduction shifted the stereo image
+        let square = (frame[0] * frame[0] + frame[1] * frame[1]) / 2.0;
+        let abs = frame[0].abs().max(frame[1].abs());
+
+        self.mean_square += (square - self.mean_square) * coefficients.rms;
+        self.peak = abs.max(self.peak * coefficients.peak_decay);
+
+        let envelope = self.hilbert[0]
+            .envelope(frame[0])
+            .max(self.hilbert[1].envelope(frame[1]));
+
+        self.rms_level = follow(self.rms_level, self.mean_square.sqrt(), coefficients);
+        self.peak_level = follow(self.peak_level, self.peak, coefficients);
+        self.hilbert_level = follow(self.hilbert_level, envelope, coefficients);
+
+        Readings {
+            rms: self.rms_level,
+            peak: self.peak_level,
+            hilbert: self.hilbert_level,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Everything the `Detector` read on a single sample, as linear levels.
+#[derive(Clone, Copy, Default)]
+pub struct Readings {
+    pub rms: f32,
+    pub peak: f32,
+    pub hilbert: f32,
+}
+
+impl Readings {
+    /// The reading for the given type of level detection.
+    pub fn level(&self, level_detection: LevelDetection, peak_blend: f32) -> f32 {
+        match level_detection {
+            // mostly RMS with a little bit of peak sensitivity mixed in, see `GainParams::peak_blend`
+            LevelDetection::Rms => self.rms + (self.peak - self.rms) * peak_blend,
+            LevelDetection::Peak => self.peak,
+            LevelDetection::Hilbert => self.hilbert,
+        }
+    }
+}
+
+fn follow(current: f32, target: f32, coefficients: &DetectorCoefficients) -> f32 {
+    let coefficient = if target > current {
+        coefficients.rise
+    } else {
+        coefficients.fall
+    };
+    current + (target - current) * coefficient
+}
+
+/// The cutoff of a (TPT) one-pole filter, prewarped.
+pub fn filter_coefficient(cutoff: f32, sample_rate: f32) -> f32 {
+    let g = (std::f32::consts::PI * cutoff / sample_rate).tan();
+    g / (1.0 + g)
+}
+
+/// A one-pole high pass followed by a one-pole low pass, see `BAND_LIMIT_LOW` and `BAND_LIMIT_HIGH`.
+/// Gentle on purpose, this only needs to keep the extremes out of the detector.
+#[derive(Default, Clone, Copy)]
+struct BandLimit {
+    high_pass: f32,
+    low_pass: f32,
+}
+
+impl BandLimit {
+    fn process(&mut self, input: f32, coefficients: &DetectorCoefficients) -> f32 {
+        // the high pass is the input minus a low pass at the lower cutoff
+        let v = (input - self.high_pass) * coefficients.high_pass;
+        let below = v + self.high_pass;
+        self.high_pass = below + v;
+        let input = input - below;
+
+        let v = (input - self.low_pass) * coefficients.low_pass;
+        let output = v + self.low_pass;
+        self.low_pass = output + v;
+        output
+    }
+}
+
+/// A single second order allpass section: `y[n] = a² * (x[n] + y[n - 2]) - x[n - 2]`.
+#[derive(Default, Clone, Copy)]
+struct Allpass {
+    x: [f32; 2],
+    y: [f32; 2],
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32, coefficient: f32) -> f32 {
+        let output = coefficient * coefficient * (input + self.y[1]) - self.x[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+#[derive(Default)]
+struct HilbertTransformer {
+    chains: [[Allpass; 4]; 2],
+    /// The second chain's output needs to be delayed by one sample to line up with the first.
+    delayed: f32,
+}
+
+impl HilbertTransformer {
+    /// Returns the magnitude of the analytic signal, i.e. the instantaneous amplitude.
+    fn envelope(&mut self, input: f32) -> f32 {
+        let [real, imaginary] = [0, 1].map(|chain| {
+            self.chains[chain]
+                .iter_mut()
+                .zip(HILBERT_COEFFICIENTS[chain])
+                .fold(input, |signal, (allpass, coefficient)| {
+                    allpass.process(signal, coefficient)
+                })
+        });
+        let imaginary = std::mem::replace(&mut self.delayed, imaginary);
+
+        (real * real + imaginary * imaginary).sqrt()
+    }
+}
@@ -0,0 +1,1443 @@
+use std::{
+    any::Any,
+    sync::{atomic::Ordering, Arc},
+};
+
+use nih_plug::prelude::*;
+use nih_plug_egui::{
+    create_egui_editor,
+    egui::{self, Color32, Rect, Sense, Vec2},
+    resizable_window::ResizableWindow,
+    widgets, EguiState,
+};
+
+use crate::{
+    delta_capture::{DeltaCapture, MAX_CAPTURE_SECONDS},
+    event_log::{EventKind, EventLog},
+    gain_staging::GainStaging,
+    headroom::InputPad,
+    link,
+    meter_bus::{self, MeterBus},
+    midi_learn::{CcMapping, MidiLearn},
+    osc::{OscInputSettings, OscOutputSettings},
+    param_writer::ParamWriter,
+    params::{GainParams, BLOCK_SIZES},
+    presets,
+    safe_mode::SafeMode,
+    scenes::Scene,
+    session_stats::SessionStats,
+    smoothing::one_pole_coefficient,
+    test_tone::{TestTone, ToneKind},
+    user_defaults::UserDefaults,
+    LevelDetection,
+};
+use knob::Knob;
+use serde::{Deserialize, Serialize};
+use theme::{Theme, ThemeMode};
+use undo::{RecordingContext, UndoHistory};
+
+mod help;
+mod knob;
+pub mod theme;
+mod undo;
+
+/// The size the editor opens at the first time it's shown.
+/// After that, whatever size the user dragged the window to is persisted in `GainParams::editor_state`.
+pub const DEFAULT_WIDTH: u32 = 560;
+pub const DEFAULT_HEIGHT: u32 = 380;
+/// The smallest the window can be resized to before the controls start overlapping.
+const MIN_SIZE: Vec2 = Vec2::new(320.0, 260.0);
+/// Below this width the meters get stacked on top of the controls instead of sitting beside them.
+const WIDE_LAYOUT_WIDTH: f32 = 520.0;
+/// Roughly how much horizontal space a single knob needs. Used to figure out how many columns fit.
+const CONTROL_WIDTH: f32 = 96.0;
+const METER_WIDTH: f32 = 150.0;
+const METER_HEIGHT: f32 = 12.0;
+/// The bottom of the level meters, in dB.
+const METER_FLOOR_DB: f32 = -60.0;
+/// The most gain reduction the GR meter can show, in dB.
+const GR_METER_RANGE_DB: f32 = 30.0;
+const HISTORY_HEIGHT: f32 = 40.0;
+const VERTICAL_METER_WIDTH: f32 = 36.0;
+const VERTICAL_METER_HEIGHT: f32 = 120.0;
+
+pub fn default_state() -> Arc<EguiState> {
+    EguiState::from_size(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+}
+
+/// State that only lives as long as the editor window.
+struct EditorState {
+    /// The theme that was last applied to the egui context, so we only touch the visuals when it actually changes.
+    applied_theme: Option<Theme>,
+    /// When enabled, every control shows its description underneath it instead of only in a tooltip.
+    show_help: bool,
+    /// How many seconds of delta signal to capture.
+    capture_seconds: f32,
+    /// Where the captured delta signal gets saved to.
+    capture_path: String,
+    /// The result of the last attempt at saving the capture.
+    capture_status: Option<String>,
+    /// Where the event log gets exported to.
+    event_log_path: String,
+    /// The result of the last attempt at exporting the event log.
+    event_log_status: Option<String>,
+    /// The result of the last attempt at saving or clearing the user defaults.
+    user_defaults_status: Option<String>,
+    /// The section the routing diagram asked to jump to, opened (and scrolled to) on the next frame.
+    jump_to: Option<Section>,
+    /// The gain reduction the GR meter currently shows **in decibels**, see `GainParams::gr_display_smoothing`.
+    displayed_gr_db: f32,
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        Self {
+            applied_theme: None,
+            show_help: false,
+            capture_seconds: 10.0,
+            capture_path: std::env::temp_dir()
+                .join("funih-delta.wav")
+                .to_string_lossy()
+                .into_owned(),
+            capture_status: None,
+            event_log_path: std::env::temp_dir()
+                .join("funih-events.txt")
+                .to_string_lossy()
+                .into_owned(),
+            event_log_status: None,
+            user_defaults_status: None,
+            jump_to: None,
+            displayed_gr_db: 0.0,
+        }
+    }
+}
+
+/// How the meters are laid out. Persisted per instance in `GainParams::meter_layout`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum MeterLayout {
+    /// Small horizontal bars, for keeping lots of windows open at once.
+    Compact,
+    /// Vertical bars and the GR history.
+    #[default]
+    Expanded,
+}
+
+/// The parts of the editor the routing diagram can jump to.
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Sidechain,
+    Advanced,
+}
+
+/// Everything the editor needs from the plugin.
+pub struct EditorData {
+    pub params: Arc<GainParams>,
+    pub meters: Arc<MeterBus>,
+    pub param_writer: ParamWriter,
+    pub midi_learn: Arc<MidiLearn>,
+    pub gain_staging: Arc<GainStaging>,
+    pub delta_capture: Arc<DeltaCapture>,
+    pub test_tone: Arc<TestTone>,
+    pub event_log: Arc<EventLog>,
+    pub safe_mode: Arc<SafeMode>,
+}
+
+/// Looks up a parameter's ID from the parameter itself, for MIDI learn.
+fn param_id<'a>(param_ids: &'a [(ParamPtr, String)], param: &impl Param) -> Option<&'a str> {
+    let ptr = param.as_ptr();
+    param_ids
+        .iter()
+        .find(|(other, _)| *other == ptr)
+        .map(|(_, id)| id.as_str())
+}
+
+// NOTE:
+// egui works in logical points, and nih_plug_egui takes care of applying the host's (or the system's) scale factor,
+// so nothing in here should ever deal with physical pixels directly.
+pub fn create(data: EditorData) -> Option<Box<dyn Editor>> {
+    let egui_state = data.params.editor_state.clone();
+    let history = Arc::new(UndoHistory::default());
+    let editor_history = history.clone();
+    let param_writer = data.param_writer.clone();
+    let param_ids: Vec<(ParamPtr, String)> = data
+        .params
+        .param_map()
+        .into_iter()
+        .map(|(id, ptr, _group)| (ptr, id))
+        .collect();
+
+    let editor = create_egui_editor(
+        egui_state.clone(),
+        EditorState::default(),
+        |_, _| {},
+        move |egui_ctx, setter, state| {
+            let params = &data.params;
+            let history = editor_history.as_ref();
+            // changes from other threads (and undo/redo) get applied here, on the GUI thread like hosts expect. they
+            // aren't edits made in the editor, so they stay out of the undo history
+            history.without_recording(|| data.param_writer.drain(setter));
+            let theme = *params.theme.read().unwrap();
+            if state.applied_theme != Some(theme) {
+                egui_ctx.set_visuals(theme.visuals());
+                state.applied_theme = Some(theme);
+            }
+
+            ResizableWindow::new("funih-editor")
+                .min_size(MIN_SIZE)
+                .show(egui_ctx, egui_state.as_ref(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.heading("funih");
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            theme_ui(ui, params);
+                            meter_layout_ui(ui, params);
+                            material_menu(ui, &data, &param_ids, setter, history);
+                            undo_ui(ui, &data, history);
+                            ui.toggle_value(&mut state.show_help, "?")
+                                .on_hover_text("Show a description under every control");
+                        });
+                    });
+                    midi_learn_status_ui(ui, &data);
+                    safe_mode_ui(ui, &data.safe_mode);
+                    missed_changes_ui(ui, &data.param_writer);
+                    ui.separator();
+
+                    let jump_to = state.jump_to.take();
+                    egui::CollapsingHeader::new("Routing").show(ui, |ui| {
+                        routing_ui(ui, &data, state);
+                    });
+
+                    egui::CollapsingHeader::new("Presets").show(ui, |ui| {
+                        presets_ui(ui, &data, &param_ids, setter, history);
+                        user_defaults_ui(ui, params, state);
+                    });
+                    egui::CollapsingHeader::new("Scenes").show(ui, |ui| {
+                        scenes_ui(ui, params, setter);
+                    });
+                    egui::CollapsingHeader::new("Gain Staging").show(ui, |ui| {
+                        gain_staging_ui(ui, &data, setter);
+                    });
+                    egui::CollapsingHeader::new("Test Tone").show(ui, |ui| {
+                        test_tone_ui(ui, &data.test_tone);
+                    });
+                    egui::CollapsingHeader::new("Delta Capture").show(ui, |ui| {
+                        delta_capture_ui(ui, &data, state);
+                    });
+                    egui::CollapsingHeader::new("OSC").show(ui, |ui| {
+                        osc_output_ui(ui, params);
+                        osc_input_ui(ui, params);
+                    });
+                    egui::CollapsingHeader::new("Session Statistics").show(ui, |ui| {
+                        session_stats_ui(ui, params);
+                    });
+                    egui::CollapsingHeader::new("Event Log").show(ui, |ui| {
+                        event_log_ui(ui, &data.event_log, state);
+                    });
+
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        if ui.available_width() >= WIDE_LAYOUT_WIDTH {
+                            ui.horizontal_top(|ui| {
+                                ui.vertical(|ui| meters_ui(ui, &data, state));
+                                ui.separator();
+                                ui.vertical(|ui| {
+                                    controls_ui(
+                                        ui,
+                                        &data,
+                                        &param_ids,
+                                        setter,
+                                        state.show_help,
+                                        jump_to,
+                                    )
+                                });
+                            });
+                        } else {
+                            meters_ui(ui, &data, state);
+                            ui.separator();
+                            controls_ui(ui, &data, &param_ids, setter, state.show_help, jump_to);
+                        }
+                    });
+                });
+        },
+    );
+
+    editor.map(|inner| {
+        Box::new(RecordingEditor {
+            inner,
+            history,
+            param_writer,
+        }) as Box<dyn Editor>
+    })
+}
+
+/// Gives the editor a `RecordingContext` instead of the host's `GuiContext`, so everything changed from the GUI ends
+/// up in the undo history. Everything else is passed straight through. The host's context also goes to the
+/// `ParamWriter`, which keeps using it once the editor is closed.
+struct RecordingEditor {
+    inner: Box<dyn Editor>,
+    history: Arc<UndoHistory>,
+    param_writer: ParamWriter,
+}
+
+impl Editor for RecordingEditor {
+    fn spawn(
+        &self,
+        parent: ParentWindowHandle,
+        context: Arc<dyn GuiContext>,
+    ) -> Box<dyn Any + Send> {
+        self.param_writer.attach(context.clone());
+        self.inner.spawn(
+            parent,
+            Arc::new(RecordingContext {
+                inner: context,
+                history: self.history.clone(),
+            }),
+        )
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.inner.size()
+    }
+
+    fn set_scale_factor(&self, factor: f32) -> bool {
+        self.inner.set_scale_factor(factor)
+    }
+
+    fn param_value_changed(&self, id: &str, normalized_value: f32) {
+        self.inner.param_value_changed(id, normalized_value)
+    }
+
+    fn param_modulation_changed(&self, id: &str, modulation_offset: f32) {
+        self.inner.param_modulation_changed(id, modulation_offset)
+    }
+
+    fn param_values_changed(&self) {
+        self.inner.param_values_changed()
+    }
+}
+
+fn theme_ui(ui: &mut egui::Ui, params: &GainParams) {
+    let mut theme = *params.theme.read().unwrap();
+
+    ui.color_edit_button_srgb(&mut theme.accent)
+        .on_hover_text("Accent color");
+    ui.selectable_value(&mut theme.mode, ThemeMode::Light, "Light");
+    ui.selectable_value(&mut theme.mode, ThemeMode::Dark, "Dark");
+
+    if theme != *params.theme.read().unwrap() {
+        *params.theme.write().unwrap() = theme;
+    }
+}
+
+fn undo_ui(ui: &mut egui::Ui, data: &EditorData, history: &UndoHistory) {
+    // the buttons are laid out right to left
+    if ui
+        .add_enabled(history.can_redo(), egui::Button::new("Redo"))
+        .clicked()
+    {
+        history.redo(&data.param_writer);
+    }
+    if ui
+        .add_enabled(history.can_undo(), egui::Button::new("Undo"))
+        .clicked()
+    {
+        history.undo(&data.param_writer);
+    }
+}
+
+fn meter_layout_ui(ui: &mut egui::Ui, params: &GainParams) {
+    let mut layout = *params.meter_layout.read().unwrap();
+    ui.selectable_value(&mut layout, MeterLayout::Expanded, "Expanded")
+        .on_hover_text("Vertical meters and the gain reduction history");
+    ui.selectable_value(&mut layout, MeterLayout::Compact, "Compact")
+        .on_hover_text("Small horizontal meters");
+
+    if layout != *params.meter_layout.read().unwrap() {
+        *params.meter_layout.write().unwrap() = layout;
+    }
+}
+
+/// One-click ballistics for common material, see `presets::MATERIALS`.
+fn material_menu(
+    ui: &mut egui::Ui,
+    data: &EditorData,
+    param_ids: &[(ParamPtr, String)],
+    setter: &ParamSetter,
+    history: &UndoHistory,
+) {
+    ui.menu_button("Material", |ui| {
+        for material in presets::MATERIALS {
+            if ui.button(material.name).clicked() {
+                history.group(|| {
+                    presets::apply_with_setter(material, &data.params, param_ids, setter)
+                });
+                data.event_log
+                    .record(EventKind::Preset, format!("Material '{}'", material.name));
+                ui.close_menu();
+            }
+        }
+    })
+    .response
+    .on_hover_text("Sets attack, release, knee and the detector up for a type of material");
+}
+
+/// Loads factory presets, and lists every parameter that's been changed since with a button to revert it.
+fn presets_ui(
+    ui: &mut egui::Ui,
+    data: &EditorData,
+    param_ids: &[(ParamPtr, String)],
+    setter: &ParamSetter,
+    history: &UndoHistory,
+) {
+    let loaded = data.params.loaded_preset.read().unwrap().clone();
+
+    egui::ComboBox::from_id_source("factory-presets")
+        .selected_text(loaded.as_deref().unwrap_or("No preset loaded"))
+        .show_ui(ui, |ui| {
+            for preset in presets::FACTORY_PRESETS {
+                if ui
+                    .selectable_label(loaded.as_deref() == Some(preset.name), preset.name)
+                    .clicked()
+                {
+                    history.group(|| {
+                        presets::apply_with_setter(preset, &data.params, param_ids, setter)
+                    });
+                    *data.params.loaded_preset.write().unwrap() = Some(preset.name.to_string());
+                    data.event_log.record(
+                        EventKind::Preset,
+                        format!("Factory preset '{}'", preset.name),
+                    );
+                }
+            }
+        });
+
+    let Some(preset) = loaded.as_deref().and_then(presets::find) else {
+        return;
+    };
+    let differences = presets::differences(preset, param_ids);
+    if differences.is_empty() {
+        ui.label("Matches the preset");
+        return;
+    }
+
+    egui::Grid::new("preset-differences")
+        .striped(true)
+        .show(ui, |ui| {
+            for difference in differences {
+                ui.label(&difference.name);
+                ui.label(format!(
+                    "{} → {}",
+                    difference.preset_value, difference.current_value
+                ));
+                if ui
+                    .small_button("Revert")
+                    .on_hover_text("Set this back to the preset's value")
+                    .clicked()
+                {
+                    presets::set_with_setter(
+                        difference.param,
+                        difference.preset_normalized,
+                        setter,
+                    );
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// Stores the current settings in a scene slot, for the scene parameter to switch between.
+fn scenes_ui(ui: &mut egui::Ui, params: &GainParams, setter: &ParamSetter) {
+    ui.add(widgets::ParamSlider::for_param(&params.scene, setter))
+        .on_hover_text(help::SCENE);
+
+    let mut scenes = params.scenes.read().unwrap().clone();
+    egui::Grid::new("scenes").show(ui, |ui| {
+        for (index, scene) in scenes.iter_mut().enumerate() {
+            ui.label(format!("Scene {}", index + 1));
+            ui.weak(if scene.is_some() { "Stored" } else { "Empty" });
+            if ui
+                .small_button("Store")
+                .on_hover_text("Store the current settings in this scene")
+                .clicked()
+            {
+                *scene = Some(Scene::capture(params));
+            }
+            if ui
+                .add_enabled(scene.is_some(), egui::Button::new("Clear").small())
+                .clicked()
+            {
+                *scene = None;
+            }
+            ui.end_row();
+        }
+    });
+
+    if scenes != *params.scenes.read().unwrap() {
+        *params.scenes.write().unwrap() = scenes;
+    }
+}
+
+/// Saves the current settings as the starting point for new instances, see `UserDefaults`.
+fn user_defaults_ui(ui: &mut egui::Ui, params: &GainParams, state: &mut EditorState) {
+    ui.horizontal(|ui| {
+        if ui
+            .button("Save as Default")
+            .on_hover_text("New instances start out with the current settings")
+            .clicked()
+        {
+            state.user_defaults_status = Some(match UserDefaults::save(params) {
+                Ok(()) => "Saved, new instances will start out like this".to_string(),
+                Err(err) => format!("Could not save the defaults: {err}"),
+            });
+        }
+        if ui
+            .button("Clear Default")
+            .on_hover_text("New instances start out with the factory settings again")
+            .clicked()
+        {
+            state.user_defaults_status = Some(match UserDefaults::clear() {
+                Ok(()) => "Cleared, new instances will use the factory settings".to_string(),
+                Err(err) => format!("Could not clear the defaults: {err}"),
+            });
+        }
+    });
+    if let Some(status) = &state.user_defaults_status {
+        ui.label(status);
+    }
+}
+
+fn gain_staging_ui(ui: &mut egui::Ui, data: &EditorData, setter: &ParamSetter) {
+    let params = &data.params;
+    let staging = &data.gain_staging;
+
+    let mut analyzing = staging.analyzing.load(Ordering::Relaxed);
+    if ui
+        .toggle_value(&mut analyzing, "Analyze input")
+        .on_hover_text("Play some representative material while this is enabled")
+        .changed()
+    {
+        staging.analyzing.store(analyzing, Ordering::Relaxed);
+    }
+
+    let measured = staging.measured_lufs.value();
+    if !measured.is_finite() {
+        ui.label("Nothing measured yet");
+        return;
+    }
+
+    let Some(suggested_db) = staging.suggested_input_gain_db(params.threshold.value()) else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Input: {measured:.1} LUFS, suggested input gain: {suggested_db:+.1} dB"
+        ));
+        if ui.button("Apply").clicked() {
+            setter.begin_set_parameter(&params.input_gain);
+            setter.set_parameter(&params.input_gain, util::db_to_gain(suggested_db));
+            setter.end_set_parameter(&params.input_gain);
+        }
+    });
+
+    let output_gain_db = util::gain_to_db(params.output_gain.value());
+    let Some(matched_db) = staging.matched_output_gain_db(output_gain_db) else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Output: {:.1} LUFS",
+            staging.output_lufs.value()
+        ));
+        if ui
+            .button("Match Input Loudness")
+            .on_hover_text(format!(
+                "Sets the output gain to {matched_db:+.1} dB, so the compressed output is as loud as the input"
+            ))
+            .clicked()
+        {
+            setter.begin_set_parameter(&params.output_gain);
+            setter.set_parameter(&params.output_gain, util::db_to_gain(matched_db));
+            setter.end_set_parameter(&params.output_gain);
+            // the output measurement was made with the old output gain, so it's stale now. stopping the analysis
+            // keeps the audio thread from publishing it again, and analyzing again starts over from scratch
+            staging.analyzing.store(false, Ordering::Relaxed);
+            staging.output_lufs.set(f32::NAN);
+        }
+    });
+}
+
+fn test_tone_ui(ui: &mut egui::Ui, tone: &TestTone) {
+    let mut enabled = tone.enabled.load(Ordering::Relaxed);
+    if ui
+        .toggle_value(&mut enabled, "Generate")
+        .on_hover_text(
+            "Feeds a test signal into the compressor instead of (or on top of) the input",
+        )
+        .changed()
+    {
+        tone.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    let mut kind = tone.kind();
+    ui.horizontal(|ui| {
+        for option in ToneKind::ALL {
+            ui.selectable_value(&mut kind, option, option.name());
+        }
+    });
+    if kind != tone.kind() {
+        tone.set_kind(kind);
+    }
+
+    let mut level_db = tone.level_db.value();
+    if ui
+        .add(
+            egui::Slider::new(&mut level_db, -60.0..=0.0)
+                .text("Level")
+                .suffix(" dBFS"),
+        )
+        .changed()
+    {
+        tone.level_db.set(level_db);
+    }
+    if kind != ToneKind::PinkNoise {
+        let mut frequency = tone.frequency.value();
+        if ui
+            .add(
+                egui::Slider::new(&mut frequency, 20.0..=20_000.0)
+                    .logarithmic(true)
+                    .text("Frequency")
+                    .suffix(" Hz"),
+            )
+            .changed()
+        {
+            tone.frequency.set(frequency);
+        }
+    }
+
+    let mut replace_input = tone.replace_input.load(Ordering::Relaxed);
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut replace_input, true, "Replace input");
+        ui.radio_value(&mut replace_input, false, "Add to input");
+    });
+    tone.replace_input.store(replace_input, Ordering::Relaxed);
+}
+
+fn delta_capture_ui(ui: &mut egui::Ui, data: &EditorData, state: &mut EditorState) {
+    let capture = &data.delta_capture;
+
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::DragValue::new(&mut state.capture_seconds)
+                .clamp_range(1.0..=MAX_CAPTURE_SECONDS)
+                .suffix(" s"),
+        );
+        if ui
+            .button("Capture")
+            .on_hover_text(
+                "Records what the compressor removes (dry minus wet) while audio is playing",
+            )
+            .clicked()
+        {
+            capture.start(state.capture_seconds);
+            state.capture_status = None;
+        }
+    });
+    ui.add(egui::ProgressBar::new(capture.progress()).show_percentage());
+
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut state.capture_path).desired_width(200.0));
+        let can_save = !capture.is_capturing() && capture.captured_frames() > 0;
+        if ui
+            .add_enabled(can_save, egui::Button::new("Save WAV"))
+            .clicked()
+        {
+            state.capture_status = Some(match capture.save_wav(&state.capture_path) {
+                Ok(()) => format!("Saved to {}", state.capture_path),
+                Err(err) => format!("Could not save the capture: {err}"),
+            });
+        }
+    });
+    if let Some(status) = &state.capture_status {
+        ui.label(status);
+    }
+}
+
+fn session_stats_ui(ui: &mut egui::Ui, params: &GainParams) {
+    let stats = *params.session_stats.read().unwrap();
+    ui.label(format!(
+        "Compressing for {} of {}",
+        format_duration(stats.compressing_seconds),
+        format_duration(stats.processed_seconds)
+    ))
+    .on_hover_text("Only audio that isn't silent counts");
+    match stats.average_gain_reduction_db() {
+        Some(average) => ui.label(format!(
+            "Average GR: {average:.1} dB, max GR: {:.1} dB",
+            stats.max_gain_reduction_db
+        )),
+        None => ui.label("Average GR: -"),
+    };
+    match stats.level_delta_db() {
+        Some(delta) => ui.label(format!("Output vs. input: {delta:+.1} dB")),
+        None => ui.label("Output vs. input: -"),
+    }
+    .on_hover_text("The RMS level difference, measured after the input gain");
+
+    if ui
+        .button("Reset")
+        .on_hover_text("Starts the statistics over, for example after finishing a mix")
+        .clicked()
+    {
+        *params.session_stats.write().unwrap() = SessionStats::default();
+    }
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn event_log_ui(ui: &mut egui::Ui, log: &EventLog, state: &mut EditorState) {
+    let mut enabled = log.is_enabled();
+    if ui
+        .checkbox(&mut enabled, "Record events")
+        .on_hover_text(
+            "Keeps a timestamped list of parameter changes, presets, mode switches and host setup changes. \
+            Attach the exported file to bug reports.",
+        )
+        .changed()
+    {
+        log.set_enabled(enabled);
+    }
+
+    ui.horizontal(|ui| {
+        ui.label(format!("{} entries", log.entry_count()));
+        if ui.small_button("Clear").clicked() {
+            log.clear();
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut state.event_log_path).desired_width(200.0));
+        if ui
+            .add_enabled(log.entry_count() > 0, egui::Button::new("Export"))
+            .clicked()
+        {
+            state.event_log_status = Some(match log.save(&state.event_log_path) {
+                Ok(()) => format!("Saved to {}", state.event_log_path),
+                Err(err) => format!("Could not save the event log: {err}"),
+            });
+        }
+    });
+    if let Some(status) = &state.event_log_status {
+        ui.label(status);
+    }
+}
+
+fn osc_output_ui(ui: &mut egui::Ui, params: &GainParams) {
+    let mut settings: OscOutputSettings = params.osc_output.read().unwrap().clone();
+
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut settings.enabled, "Send meters");
+        ui.add(egui::TextEdit::singleline(&mut settings.host).desired_width(110.0));
+        ui.label(":");
+        ui.add(egui::DragValue::new(&mut settings.port));
+        ui.checkbox(&mut settings.allow_remote, "Allow other computers")
+            .on_hover_text("Lets the meters go to another machine, otherwise they can only be sent to this one");
+    });
+
+    if settings != *params.osc_output.read().unwrap() {
+        *params.osc_output.write().unwrap() = settings;
+    }
+}
+
+fn osc_input_ui(ui: &mut egui::Ui, params: &GainParams) {
+    let mut settings: OscInputSettings = params.osc_input.read().unwrap().clone();
+
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut settings.enabled, "Listen for control on port");
+        ui.add(egui::DragValue::new(&mut settings.port));
+        ui.checkbox(&mut settings.allow_remote, "Allow other computers")
+            .on_hover_text(
+                "Accepts messages from anywhere on the network. Anyone who can reach the port can change every \
+                parameter, so only turn this on for networks you trust",
+            );
+    })
+    .response
+    .on_hover_text(format!(
+        "Send /funih/param/<id> with a normalized value (0 to 1) to change a parameter. {}",
+        help::EDITOR_CLOSED
+    ));
+
+    if settings != *params.osc_input.read().unwrap() {
+        *params.osc_input.write().unwrap() = settings;
+    }
+}
+
+// TODO:
+// THD readout (2nd/3rd harmonic levels for a reference tone) once there's a saturation/drive stage to measure.
+// the analysis should run as a background `Task`, not in the editor or on the audio thread.
+fn meters_ui(ui: &mut egui::Ui, data: &EditorData, state: &mut EditorState) {
+    let meters = &data.meters;
+    let rms_db = util::gain_to_db(meters.input_rms_max());
+    let peak_db = util::gain_to_db(meters.input_peak_max());
+    // the compressor outputs a gain factor <= 1.0, so flip it to get a positive amount of reduction
+    let gr_db = -util::gain_to_db(meters.gain_reduction.value());
+    // only the readout gets smoothed, the audio still follows the raw envelope
+    let smoothing = *data.params.gr_display_smoothing.read().unwrap();
+    let frame_time = ui.input(|input| input.stable_dt);
+    state.displayed_gr_db +=
+        (gr_db - state.displayed_gr_db) * one_pole_coefficient(smoothing, frame_time.recip());
+    let gr_db = state.displayed_gr_db;
+    // the readings are frozen while the input is silent, so dim them to make that obvious
+    let silent = meters.silent.load(Ordering::Relaxed);
+    let fill = |color: Color32| {
+        if silent {
+            color.linear_multiply(0.35)
+        } else {
+            color
+        }
+    };
+
+    let rms_fill = fill(Color32::from_rgb(90, 200, 120));
+    let peak_fill = fill(Color32::from_rgb(230, 190, 70));
+    let gr_fill = fill(Color32::from_rgb(220, 90, 70));
+    let gr_fraction = (gr_db / GR_METER_RANGE_DB).clamp(0.0, 1.0);
+    let gr_menu = |ui: &mut egui::Ui| {
+        let mut smoothing = smoothing;
+        ui.add(
+            egui::Slider::new(&mut smoothing, 0.0..=1.0)
+                .text("Display smoothing")
+                .suffix(" s"),
+        );
+        if smoothing != *data.params.gr_display_smoothing.read().unwrap() {
+            *data.params.gr_display_smoothing.write().unwrap() = smoothing;
+        }
+    };
+    let gr_hover = "Right-click to change how smoothed the readout is";
+
+    match *data.params.meter_layout.read().unwrap() {
+        MeterLayout::Compact => {
+            level_meter(ui, "RMS", rms_db, rms_fill);
+            level_meter(ui, "Peak", peak_db, peak_fill);
+            ui.label(format!("GR: {gr_db:.1} dB"))
+                .on_hover_text(gr_hover)
+                .context_menu(gr_menu);
+            meter_bar(ui, gr_fraction, gr_fill);
+        }
+        MeterLayout::Expanded => {
+            ui.horizontal(|ui| {
+                vertical_meter(ui, "RMS", rms_db, level_fraction(rms_db), rms_fill);
+                vertical_meter(ui, "Peak", peak_db, level_fraction(peak_db), peak_fill);
+                // the GR meter fills from the top, since it shows the level going down
+                vertical_meter(ui, "GR", gr_db, -gr_fraction, gr_fill)
+                    .on_hover_text(gr_hover)
+                    .context_menu(gr_menu);
+            });
+            history_plot(ui, &meters.gr_history.snapshot(), gr_fill);
+        }
+    }
+    if silent {
+        ui.weak("Silence, meters paused");
+    }
+    if meters.hot_input.load(Ordering::Relaxed) {
+        let text = match data.params.input_pad.value() {
+            InputPad::Off => "Input above 0 dBFS".to_string(),
+            _ => format!("Input above 0 dBFS, padded ({})", data.params.input_pad),
+        };
+        ui.colored_label(ui.visuals().warn_fg_color, text)
+            .on_hover_text(help::INPUT_PAD);
+    }
+    ui.horizontal(|ui| {
+        let max_gr_db = -util::gain_to_db(meters.gr_max_hold.value());
+        let position = meter_bus::format_position(meters.gr_max_hold_time.value());
+        match position {
+            Some(position) => ui.label(format!("Max GR: {max_gr_db:.1} dB at {position}")),
+            None => ui.label(format!("Max GR: {max_gr_db:.1} dB")),
+        }
+        .on_hover_text(
+            "The most gain reduction since the last reset, and where in the song it happened",
+        );
+        if ui.small_button("Reset").clicked() {
+            meters.reset_gr_max_hold.store(true, Ordering::Relaxed);
+        }
+    });
+
+    if let Some(report) = *data.params.loudness_report.read().unwrap() {
+        ui.add_space(4.0);
+        ui.label(format!(
+            "Last offline render: {:.1} LUFS, {:.1} dBTP",
+            report.integrated_lufs, report.true_peak_dbtp
+        ));
+    }
+}
+
+fn level_meter(ui: &mut egui::Ui, label: &str, level_db: f32, fill: Color32) {
+    let level_db = level_db.max(METER_FLOOR_DB);
+    ui.label(format!("{label}: {level_db:.1} dB"));
+    meter_bar(ui, level_fraction(level_db), fill);
+}
+
+/// How far up the meter a level goes, with `METER_FLOOR_DB` at the bottom and 0 dB at the top.
+fn level_fraction(level_db: f32) -> f32 {
+    1.0 - level_db.max(METER_FLOOR_DB) / METER_FLOOR_DB
+}
+
+/// A labelled vertical bar for the expanded layout. A negative `fraction` fills down from the top instead.
+fn vertical_meter(
+    ui: &mut egui::Ui,
+    label: &str,
+    value_db: f32,
+    fraction: f32,
+    fill: Color32,
+) -> egui::Response {
+    ui.vertical(|ui| {
+        ui.set_width(VERTICAL_METER_WIDTH);
+        ui.small(format!("{:.1}", value_db.max(METER_FLOOR_DB)));
+        let (rect, _) = ui.allocate_exact_size(
+            Vec2::new(VERTICAL_METER_WIDTH, VERTICAL_METER_HEIGHT),
+            Sense::hover(),
+        );
+
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        let height = rect.height() * fraction.abs().clamp(0.0, 1.0);
+        let filled = if fraction < 0.0 {
+            Rect::from_min_size(rect.min, Vec2::new(rect.width(), height))
+        } else {
+            Rect::from_min_max(egui::pos2(rect.left(), rect.bottom() - height), rect.max)
+        };
+        painter.rect_filled(filled, 2.0, fill);
+        ui.small(label);
+    })
+    .response
+}
+
+/// Draws a horizontal bar filled up to `fraction` (`0.0` to `1.0`).
+fn meter_bar(ui: &mut egui::Ui, fraction: f32, fill: Color32) {
+    let width = ui.available_width().min(METER_WIDTH);
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(width, METER_HEIGHT), Sense::hover());
+
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let filled = Rect::from_min_size(
+        rect.min,
+        Vec2::new(rect.width() * fraction.clamp(0.0, 1.0), rect.height()),
+    );
+    painter.rect_filled(filled, 2.0, fill);
+}
+
+/// Plots the gain reduction over the last few seconds, newest on the right. Reduction goes down from the top.
+fn history_plot(ui: &mut egui::Ui, gains: &[f32], stroke: Color32) {
+    let width = ui.available_width().min(METER_WIDTH);
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(width, HISTORY_HEIGHT), Sense::hover());
+
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let step = rect.width() / (gains.len().max(2) - 1) as f32;
+    let points = gains
+        .iter()
+        .enumerate()
+        .map(|(index, gain)| {
+            let gr_db = -util::gain_to_db(*gain);
+            let fraction = (gr_db / GR_METER_RANGE_DB).clamp(0.0, 1.0);
+            egui::pos2(
+                rect.left() + index as f32 * step,
+                rect.top() + fraction * rect.height(),
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, stroke)));
+}
+
+/// Shows where the audio and the detector signal currently go, reflecting the current settings.
+/// Blocks with settings of their own can be clicked to jump to them.
+// TODO:
+// M/S, multiband splits and the other topologies get their own blocks here once they exist
+fn routing_ui(ui: &mut egui::Ui, data: &EditorData, state: &mut EditorState) {
+    let params = data.params.as_ref();
+    let mut block = |ui: &mut egui::Ui, label: String, section: Option<Section>| match section {
+        Some(section) => {
+            if ui.button(label).clicked() {
+                state.jump_to = Some(section);
+            }
+        }
+        None => {
+            ui.label(label);
+        }
+    };
+    let arrow = |ui: &mut egui::Ui| ui.weak("→");
+
+    ui.horizontal_wrapped(|ui| {
+        ui.strong("Detector:");
+        if params.sidechain.value() {
+            block(
+                ui,
+                format!(
+                    "Sidechain ({}, {}, {})",
+                    params.sidechain_channels, params.sidechain_gain, params.sidechain_offset
+                ),
+                Some(Section::Sidechain),
+            );
+        } else {
+            block(ui, format!("Input ({})", params.input_gain), None);
+        }
+        arrow(ui);
+        block(ui, "Band Limit (10 Hz - 20 kHz)".to_string(), None);
+        arrow(ui);
+        block(
+            ui,
+            format!("{} Detection", params.meter_type),
+            Some(Section::Advanced),
+        );
+        arrow(ui);
+        block(ui, format!("Gain Computer ({})", params.mode), None);
+    });
+    ui.horizontal_wrapped(|ui| {
+        ui.strong("Audio:");
+        block(ui, format!("Input ({})", params.input_gain), None);
+        arrow(ui);
+        if params.rms_compensation.value() && params.meter_type.value() == LevelDetection::Rms {
+            block(
+                ui,
+                "RMS Compensation Delay".to_string(),
+                Some(Section::Advanced),
+            );
+            arrow(ui);
+        }
+        if params.sidechain.value() && params.sidechain_offset.value() < 0.0 {
+            block(
+                ui,
+                format!("Sidechain Offset Delay ({})", params.sidechain_offset),
+                Some(Section::Sidechain),
+            );
+            arrow(ui);
+        }
+        block(ui, "Gain".to_string(), None);
+        arrow(ui);
+        if params.note_gate.value() || params.force_closed.value() {
+            block(ui, "Note Gate".to_string(), None);
+            arrow(ui);
+        }
+        block(ui, "Output".to_string(), None);
+    });
+
+    let latency_samples = data.meters.latency_samples.load(Ordering::Relaxed);
+    ui.label(format!(
+        "Latency: {latency_samples} samples ({})",
+        params.latency_output
+    ))
+    .on_hover_text(help::LATENCY);
+}
+
+/// A collapsing header that opens and scrolls into view when the routing diagram jumps to it.
+fn section_header(
+    ui: &mut egui::Ui,
+    title: &str,
+    section: Section,
+    jump_to: Option<Section>,
+) -> egui::CollapsingHeader {
+    let jumping = jump_to == Some(section);
+    if jumping {
+        ui.scroll_to_cursor(Some(egui::Align::TOP));
+    }
+    egui::CollapsingHeader::new(title).open(jumping.then_some(true))
+}
+
+/// The things every control needs access to while drawing the current frame.
+struct ControlsContext<'a> {
+    data: &'a EditorData,
+    param_ids: &'a [(ParamPtr, String)],
+    setter: &'a ParamSetter<'a>,
+    show_help: bool,
+}
+
+fn controls_ui(
+    ui: &mut egui::Ui,
+    data: &EditorData,
+    param_ids: &[(ParamPtr, String)],
+    setter: &ParamSetter,
+    show_help: bool,
+    jump_to: Option<Section>,
+) {
+    let params = &data.params;
+    let context = ControlsContext {
+        data,
+        param_ids,
+        setter,
+        show_help,
+    };
+
+    param_slider(ui, &context, &params.mode).on_hover_text(help::MODE);
+    if show_help {
+        help_text(ui, help::MODE);
+    }
+    ui.label(params.meter_type.name())
+        .on_hover_text(help::LEVEL_DETECTION);
+    param_slider(ui, &context, &params.meter_type).on_hover_text(help::LEVEL_DETECTION);
+    if show_help {
+        help_text(ui, help::LEVEL_DETECTION);
+    }
+    param_slider(ui, &context, &params.freeze_gain_reduction).on_hover_text(help::FREEZE_GR);
+    if show_help {
+        help_text(ui, help::FREEZE_GR);
+    }
+    ui.horizontal(|ui| {
+        param_slider(ui, &context, &params.gr_output_write).on_hover_text(help::GR_OUTPUT);
+        if params.gr_output_write.value() {
+            ui.label(params.gr_output.to_string());
+        }
+    });
+    if show_help {
+        help_text(ui, help::GR_OUTPUT);
+    }
+    param_slider(ui, &context, &params.note_gate).on_hover_text(help::NOTE_GATE);
+    if show_help {
+        help_text(ui, help::NOTE_GATE);
+    }
+    ui.horizontal(|ui| {
+        param_slider(ui, &context, &params.force_open).on_hover_text(help::FORCE_OPEN);
+        param_slider(ui, &context, &params.force_closed).on_hover_text(help::FORCE_CLOSED);
+    });
+    if show_help {
+        help_text(ui, help::FORCE_OPEN);
+        help_text(ui, help::FORCE_CLOSED);
+    }
+    param_slider(ui, &context, &params.lf_hold).on_hover_text(help::LF_HOLD);
+    if show_help {
+        help_text(ui, help::LF_HOLD);
+    }
+    ui.horizontal(|ui| {
+        param_slider(ui, &context, &params.stepped_ratio).on_hover_text(help::STEPPED_RATIO);
+        if params.stepped_ratio.value() {
+            param_slider(ui, &context, &params.ratio_step).on_hover_text(help::STEPPED_RATIO);
+        }
+    });
+    if show_help {
+        help_text(ui, help::STEPPED_RATIO);
+    }
+    ui.add_space(4.0);
+
+    knob_grid(
+        ui,
+        &context,
+        "controls",
+        &[
+            (&params.threshold, help::THRESHOLD),
+            (&params.ratio, help::RATIO),
+            (&params.attack_time, help::ATTACK),
+            (&params.release_time, help::RELEASE),
+            (&params.slow_release_time, help::SLOW_RELEASE),
+            (&params.release_blend, help::RELEASE_BLEND),
+            (&params.transient_bleed, help::TRANSIENT_BLEED),
+            (&params.knee_width, help::KNEE),
+            (&params.peak_blend, help::PEAK_BLEND),
+            (&params.peak_decay, help::PEAK_DECAY),
+            (&params.max_boost, help::MAX_BOOST),
+            (&params.input_gain, help::INPUT_GAIN),
+            (&params.output_gain, help::OUTPUT_GAIN),
+            (&params.dry_wet, help::DRY_WET),
+            (&params.gate_release_time, help::GATE_RELEASE),
+        ],
+    );
+
+    section_header(ui, "Sidechain", Section::Sidechain, jump_to).show(ui, |ui| {
+        ui.horizontal(|ui| {
+            param_slider(ui, &context, &params.sidechain).on_hover_text(help::SIDECHAIN);
+            param_slider(ui, &context, &params.sidechain_invert)
+                .on_hover_text(help::SIDECHAIN_INVERT);
+        });
+        if show_help {
+            help_text(ui, help::SIDECHAIN);
+            help_text(ui, help::SIDECHAIN_INVERT);
+        }
+        param_slider(ui, &context, &params.sidechain_channels)
+            .on_hover_text(help::SIDECHAIN_CHANNELS);
+        if show_help {
+            help_text(ui, help::SIDECHAIN_CHANNELS);
+        }
+        knob_grid(
+            ui,
+            &context,
+            "sidechain-controls",
+            &[
+                (&params.sidechain_gain, help::SIDECHAIN_TRIM),
+                (&params.sidechain_offset, help::SIDECHAIN_OFFSET),
+            ],
+        );
+    });
+
+    egui::CollapsingHeader::new("Link").show(ui, |ui| {
+        ui.horizontal(|ui| {
+            param_slider(ui, &context, &params.link_group).on_hover_text(help::LINK_GROUP);
+            param_slider(ui, &context, &params.link_mode).on_hover_text(help::LINK_MODE);
+        });
+        if show_help {
+            help_text(ui, help::LINK_GROUP);
+            help_text(ui, help::LINK_MODE);
+        }
+        let group = params.link_group.value() as usize;
+        if group > 0 {
+            // this instance counts too, as long as it's processing
+            let members = link::active_members(group);
+            ui.label(match members {
+                0 => "Nothing in this group is processing right now".to_string(),
+                1 => "1 instance in this group".to_string(),
+                members => format!("{members} instances in this group"),
+            });
+        }
+    });
+
+    egui::CollapsingHeader::new("Modulation").show(ui, |ui| {
+        if show_help {
+            help_text(ui, help::MODULATION);
+        }
+        for (name, target, shape) in [
+            ("LFO 1", &params.lfo1_target, &params.lfo1_shape),
+            ("LFO 2", &params.lfo2_target, &params.lfo2_shape),
+        ] {
+            ui.horizontal(|ui| {
+                ui.label(name);
+                param_slider(ui, &context, target).on_hover_text(help::MOD_TARGET);
+                param_slider(ui, &context, shape);
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.label("Envelope");
+            param_slider(ui, &context, &params.env_target).on_hover_text(help::MOD_TARGET);
+        });
+        knob_grid(
+            ui,
+            &context,
+            "modulation-controls",
+            &[
+                (&params.lfo1_rate, help::LFO_RATE),
+                (&params.lfo1_depth, help::MOD_DEPTH),
+                (&params.lfo2_rate, help::LFO_RATE),
+                (&params.lfo2_depth, help::MOD_DEPTH),
+                (&params.env_release, help::ENVELOPE_RELEASE),
+                (&params.env_depth, help::MOD_DEPTH),
+            ],
+        );
+    });
+
+    section_header(ui, "Advanced", Section::Advanced, jump_to).show(ui, |ui| {
+        param_slider(ui, &context, &params.quality).on_hover_text(help::QUALITY);
+        if show_help {
+            help_text(ui, help::QUALITY);
+        }
+        param_slider(ui, &context, &params.rms_compensation).on_hover_text(help::RMS_COMPENSATION);
+        if show_help {
+            help_text(ui, help::RMS_COMPENSATION);
+        }
+        param_slider(ui, &context, &params.db_conversion).on_hover_text(help::DB_CONVERSION);
+        if show_help {
+            help_text(ui, help::DB_CONVERSION);
+        }
+        param_slider(ui, &context, &params.knee_shape).on_hover_text(help::KNEE_SHAPE);
+        if show_help {
+            help_text(ui, help::KNEE_SHAPE);
+        }
+        param_slider(ui, &context, &params.input_pad).on_hover_text(help::INPUT_PAD);
+        if show_help {
+            help_text(ui, help::INPUT_PAD);
+        }
+        block_size_ui(ui, params);
+        if show_help {
+            help_text(ui, help::BLOCK_SIZE);
+        }
+        param_slider(ui, &context, &params.detector_channels)
+            .on_hover_text(help::DETECTOR_CHANNELS);
+        if show_help {
+            help_text(ui, help::DETECTOR_CHANNELS);
+        }
+        knob_grid(
+            ui,
+            &context,
+            "advanced-controls",
+            &[
+                (&params.detector_rise_time, help::DETECTOR_RISE),
+                (&params.detector_fall_time, help::DETECTOR_FALL),
+                (&params.detector_weight_a, help::DETECTOR_WEIGHT),
+                (&params.detector_weight_b, help::DETECTOR_WEIGHT),
+                (&params.tone_depth, help::DYNAMIC_TONE),
+                (&params.param_smoothing_time, help::PARAM_SMOOTHING),
+                (&params.startup_ramp, help::STARTUP_RAMP),
+            ],
+        );
+    });
+}
+
+fn block_size_ui(ui: &mut egui::Ui, params: &GainParams) {
+    let mut block_size = *params.block_size.read().unwrap();
+    ui.horizontal(|ui| {
+        ui.label("Block size").on_hover_text(help::BLOCK_SIZE);
+        egui::ComboBox::from_id_source("block-size")
+            .selected_text(format!("{block_size} samples"))
+            .show_ui(ui, |ui| {
+                for size in BLOCK_SIZES {
+                    ui.selectable_value(&mut block_size, size, format!("{size} samples"));
+                }
+            });
+    });
+
+    if block_size != *params.block_size.read().unwrap() {
+        *params.block_size.write().unwrap() = block_size;
+    }
+}
+
+/// Lays out knobs in as many columns as currently fit.
+fn knob_grid(
+    ui: &mut egui::Ui,
+    context: &ControlsContext,
+    id: &str,
+    knobs: &[(&FloatParam, &str)],
+) {
+    let columns = ((ui.available_width() / CONTROL_WIDTH) as usize).max(1);
+    egui::Grid::new(id)
+        .num_columns(columns)
+        .spacing(Vec2::new(12.0, 8.0))
+        .show(ui, |ui| {
+            for (index, (param, description)) in knobs.iter().enumerate() {
+                ui.vertical(|ui| {
+                    ui.set_max_width(CONTROL_WIDTH);
+                    let response = ui
+                        .add(Knob::for_param(*param, context.setter))
+                        .on_hover_text(*description);
+                    if let Some(param_id) = param_id(context.param_ids, *param) {
+                        midi_learn_menu(response, context.data, param_id);
+                    }
+                    if context.show_help {
+                        help_text(ui, description);
+                    }
+                });
+                if (index + 1) % columns == 0 {
+                    ui.end_row();
+                }
+            }
+        });
+}
+
+/// Shows why the plugin is passing audio through untouched, if it is, with a way to start processing again.
+fn safe_mode_ui(ui: &mut egui::Ui, safe_mode: &SafeMode) {
+    let Some(reason) = safe_mode.reason() else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.colored_label(
+            ui.visuals().error_fg_color,
+            format!("Passing audio through untouched, {}", reason.description()),
+        );
+        if ui
+            .button("Try Again")
+            .on_hover_text("Starts processing again from a clean state")
+            .clicked()
+        {
+            safe_mode.clear();
+        }
+    });
+}
+
+/// Shows whether any changes from outside the editor got turned away before it was opened, see `ParamWriter`.
+fn missed_changes_ui(ui: &mut egui::Ui, writer: &ParamWriter) {
+    let Some((count, last)) = writer.missed() else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        let message = if count == 1 {
+            format!("{last} arrived before the editor was opened and couldn't be applied")
+        } else {
+            format!(
+                "{count} changes arrived before the editor was opened and couldn't be applied, the last one was {last}"
+            )
+        };
+        ui.colored_label(ui.visuals().warn_fg_color, message)
+            .on_hover_text(help::EDITOR_CLOSED);
+        if ui.button("Dismiss").clicked() {
+            writer.clear_missed();
+        }
+    });
+}
+
+/// Shows what MIDI learn is waiting for, if anything.
+fn midi_learn_status_ui(ui: &mut egui::Ui, data: &EditorData) {
+    let Some(param_id) = data.midi_learn.armed() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "MIDI Learn: move a controller to map it to \"{param_id}\""
+        ));
+        if ui.button("Cancel").clicked() {
+            data.midi_learn.cancel();
+        }
+    });
+}
+
+/// A slider for an enum or on/off parameter, with the same right-click menu as the knobs (see `midi_learn_menu()`).
+fn param_slider(
+    ui: &mut egui::Ui,
+    context: &ControlsContext,
+    param: &impl Param,
+) -> egui::Response {
+    let response = ui.add(widgets::ParamSlider::for_param(param, context.setter));
+    if let Some(param_id) = param_id(context.param_ids, param) {
+        midi_learn_menu(response.clone(), context.data, param_id);
+    }
+    response
+}
+
+/// The right-click menu on a control, for locking it, starting MIDI learn and tweaking an existing mapping.
+fn midi_learn_menu(response: egui::Response, data: &EditorData, param_id: &str) {
+    response.context_menu(|ui| {
+        let mut locked = data.params.is_locked(param_id);
+        if ui
+            .checkbox(&mut locked, "Lock")
+            .on_hover_text(help::LOCK)
+            .changed()
+        {
+            let mut locked_params = data.params.locked_params.write().unwrap();
+            if locked {
+                locked_params.insert(param_id.to_string());
+            } else {
+                locked_params.remove(param_id);
+            }
+        }
+
+        if ui.button("MIDI Learn").clicked() {
+            data.midi_learn.arm(param_id);
+            ui.close_menu();
+        }
+
+        let mut mappings: Vec<CcMapping> = data.params.cc_mappings.read().unwrap().clone();
+        let Some(index) = mappings
+            .iter()
+            .position(|mapping| mapping.param_id == param_id)
+        else {
+            return;
+        };
+
+        ui.separator();
+        let mapping = &mut mappings[index];
+        ui.label(format!("Mapped to CC {}", mapping.cc));
+        ui.add(egui::Slider::new(&mut mapping.min, 0.0..=1.0).text("Min"));
+        ui.add(egui::Slider::new(&mut mapping.max, 0.0..=1.0).text("Max"));
+        ui.checkbox(&mut mapping.pickup, "Pickup mode")
+            .on_hover_text("Only take over once the controller reaches the current value");
+        if ui.button("Remove mapping").clicked() {
+            mappings.remove(index);
+            ui.close_menu();
+        }
+
+        if mappings != *data.params.cc_mappings.read().unwrap() {
+            *data.params.cc_mappings.write().unwrap() = mappings;
+        }
+    });
+}
+
+fn help_text(ui: &mut egui::Ui, description: &str) {
+    ui.add(egui::Label::new(egui::RichText::new(description).small().weak()).wrap(true));
+}
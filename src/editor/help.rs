@@ -0,0 +1,129 @@
+//! Descriptions shown in tooltips and in the help overlay.
+//! These mirror the doc comments on the fields of `GainParams`, so if one changes, the other should too.
+
+pub const LEVEL_DETECTION: &str = "How the level of the input is measured. \
+    RMS follows the average loudness of the signal, while Peak reacts to the loudest individual samples. \
+    Hilbert tracks the signal's amplitude directly, which works well with very fast attack times.";
+pub const KNEE_SHAPE: &str = "How the knee eases into compression. Quadratic is a smooth curve, \
+    Ratio Blend is a straight line halfway between no compression and the full ratio.";
+pub const PEAK_BLEND: &str = "Mixes some of the peak level into the RMS level, \
+    so the compressor still catches sharp transients that RMS detection would mostly ignore. RMS detection only.";
+pub const PEAK_DECAY: &str = "How long the peak detector takes to fall back down after a peak. \
+    Short times catch every single peak, long ones give a slow reading closer to a VU meter. \
+    Used by peak detection and the peak blend.";
+pub const THRESHOLD: &str = "The level above which compression starts, in decibels.";
+pub const RATIO: &str = "How strongly the level is reduced above the threshold. \
+    A ratio of 2:1 means that for every 2 dB the level is above the threshold, 1 dB will pass through.";
+pub const ATTACK: &str = "The time it takes before the compressor starts compressing \
+    after the level goes above the threshold.";
+pub const RELEASE: &str = "The time it takes for the compressor to stop compressing \
+    after the level falls below the threshold.";
+pub const KNEE: &str =
+    "Smooths the transition between compression and no compression around the threshold. \
+    Set this to 0 dB for a hard-knee compressor.";
+pub const INPUT_GAIN: &str = "Changes the gain of the incoming signal before compression.";
+pub const OUTPUT_GAIN: &str =
+    "Changes the gain of the signal after compression, and after dry/wet has been applied.";
+pub const DRY_WET: &str = "Blends the uncompressed signal with the compressed signal. \
+    100% only outputs the compressed signal, while 0% essentially applies no compression.";
+pub const NOTE_GATE: &str =
+    "Only lets the output through while a MIDI note is held, so the plugin can be played as a gate.";
+pub const GATE_RELEASE: &str =
+    "How long the note gate takes to close after the last MIDI note is released.";
+pub const DETECTOR_RISE: &str = "How quickly the level detector's reading can rise. \
+    Unlike the attack, this shapes the level the compressor sees. Slower rise times ignore short bursts.";
+pub const DETECTOR_FALL: &str = "How quickly the level detector's reading can fall. \
+    Slower fall times make the compressor hold on to sustained material.";
+pub const DETECTOR_CHANNELS: &str =
+    "Whether the detector weights apply to left and right, or to mid and side.";
+pub const DETECTOR_WEIGHT: &str = "How much this channel counts towards the detector's reading. \
+    Turn one side down to let the other one drive the compression, like the low end of a piano or the snare side \
+    of drum overheads. The gain reduction still applies to both channels.";
+pub const DYNAMIC_TONE: &str = "Tilts the compressed signal's tone as the gain reduction goes up, \
+    reaching the full amount at 12 dB of gain reduction. Turn it up to bring back the top end heavy compression \
+    tends to dull, or down to tame harshness. Leaves the sound alone while nothing's being compressed.";
+pub const DB_CONVERSION: &str =
+    "Whether gain reduction is calculated with exact or slightly faster \
+    decibel conversions. Auto picks based on the Quality setting.";
+pub const FORCE_OPEN: &str = "Holds the gate open regardless of which MIDI notes are held.";
+pub const FORCE_CLOSED: &str =
+    "Holds the output muted, even with the note gate off. Takes priority over Force Open.";
+pub const SIDECHAIN: &str =
+    "Uses the plugin's sidechain input for level detection instead of the main input.";
+pub const SIDECHAIN_TRIM: &str = "Changes the level of the sidechain before level detection. \
+    Doesn't affect the audio that gets compressed.";
+pub const SIDECHAIN_OFFSET: &str = "Lines the sidechain up with the audio. Turn it up if the key arrives too early \
+    (e.g. from a pre-fader send), or down if it arrives too late. Going below zero delays the audio, adding latency.";
+pub const SIDECHAIN_INVERT: &str = "Flips the polarity of the sidechain. \
+    This doesn't change how much the compressor reacts, since only the level is detected.";
+pub const SIDECHAIN_CHANNELS: &str = "Which part of the sidechain is used for level detection. \
+    Useful when the key signal only sits on one side of a stereo send.";
+pub const SLOW_RELEASE: &str =
+    "A second, slower release that follows the average level instead of the peaks. \
+    Only heard when Release Blend is turned up.";
+pub const RELEASE_BLEND: &str = "Blends between the regular release and the slow release. \
+    Mixing in some of the slow release lets transients recover quickly without the rest of the mix pumping.";
+pub const TRANSIENT_BLEED: &str =
+    "Lets part of each transient through before the gain reduction fully engages, \
+    for extra punch without changing the attack time.";
+pub const MODE: &str = "Compress turns the signal down above the threshold. \
+    Upward Expand turns it up instead, to restore dynamics to material that's already been compressed.";
+pub const MAX_BOOST: &str = "The most the signal can be turned up, \
+    in Upward Expand mode or with a ratio below 1:1.";
+pub const QUALITY: &str = "Eco lowers the CPU usage for sessions with lots of instances, \
+    at the cost of slightly steppier gain changes. HQ uses the most accurate math, for mixdowns.";
+pub const PARAM_SMOOTHING: &str =
+    "How quickly the threshold, ratio and knee follow changes and automation. \
+    Shorter times are snappier, longer times avoid zipper noise when automating aggressively.";
+pub const FREEZE_GR: &str =
+    "Holds the gain reduction at its current amount until this is turned off again. \
+    Handy for creative effects, or for hearing static gain reduction next to the moving kind.";
+pub const RMS_COMPENSATION: &str = "RMS detection lags behind the audio. This delays the audio to make up for it, \
+    so the compression lines up with the transients it's reacting to. Adds 50 ms of latency, RMS detection only.";
+pub const LOCK: &str =
+    "Keeps this control where it is when loading a preset or a material, so you can browse around it.";
+pub const LF_HOLD: &str = "Only lets the release move when the audio crosses zero. \
+    Keeps fast releases from distorting bass, at the cost of the release getting a little less precise.";
+pub const SCENE: &str =
+    "Switches between the stored scenes. Automate this or map it to a controller \
+    to jump between setups live. The compression briefly fades out to the dry signal and back in with the new \
+    settings, so nothing clicks. The editor has to have been opened once since the plugin was switched on.";
+pub const STEPPED_RATIO: &str =
+    "Picks the ratio from a fixed set of values like a hardware ratio switch, instead of the ratio knob.";
+pub const INPUT_PAD: &str = "Turns the input down by 6 or 12 dB while it's been above 0 dBFS for a while, \
+    and back up after the compressor. Keeps hot signals from earlier plugins in the range the meters and the knee \
+    are made for, without changing the output level.";
+pub const LINK_GROUP: &str = "Links every instance set to the same group, for example compressors on separate stems \
+    that should react together. Only works between instances in the same host process, up to 16 per group.";
+pub const LINK_MODE: &str = "Shared Detector: every instance reacts to the loudest signal in the group, \
+    with its own settings. Gain Reduction: every instance applies the most gain reduction in the group. \
+    Instances only link up with others in the same mode.";
+pub const MODULATION: &str =
+    "Two LFOs and an envelope follower that move the threshold, the ratio or the dry/wet \
+    on their own, for rhythmic or level-dependent compression without any host automation. \
+    The knobs themselves don't move, the modulation goes on top of them.";
+pub const MOD_TARGET: &str = "Which control this source moves. Off leaves everything alone.";
+pub const MOD_DEPTH: &str = "How far the target gets moved. At 100% that's 24 dB for the threshold, two octaves \
+    (for example 4:1 up to 16:1) for the ratio, and the whole range for the dry/wet. Negative values flip the direction.";
+pub const LFO_RATE: &str = "How fast the LFO cycles, in Hz.";
+pub const ENVELOPE_RELEASE: &str =
+    "How quickly the envelope follower falls back down after the input gets quieter.";
+pub const GR_OUTPUT: &str = "Keeps writing the gain reduction to the GR Output parameter while the host is playing. \
+    Record that parameter as automation to turn the compression into volume automation you can edit. \
+    The editor has to have been opened once since the plugin was switched on, after that it can be closed.";
+pub const LATENCY: &str =
+    "The delay the plugin currently reports to the host, which the host should compensate for. \
+    RMS compensation and a negative sidechain offset both add to it. \
+    Also available as the read-only Latency parameter, which only updates once the editor has been opened.";
+pub const STARTUP_RAMP: &str = "How long the compression takes to fade in after the plugin gets switched on or reset, \
+    which some hosts do whenever playback starts. Smooths over the moment where the detector is still catching up \
+    with the input.";
+pub const BLOCK_SIZE: &str = "How much audio gets processed at once. The compression sounds the same at any block \
+    size, except in Eco quality, where the gain reduction only updates once per block and smaller blocks follow fast \
+    transients more closely. Smaller blocks also update the meters and the GR Output more often, larger ones use \
+    less CPU. \
+    Takes effect the next time the host restarts the plugin's processing, for example after changing the buffer size.";
+pub const EDITOR_CLOSED: &str =
+    "The plugin can only change its own parameters once the host has opened the editor, so program changes, \
+    scenes, SysEx dumps, MIDI controllers and OSC only work after the editor has been opened once since the plugin \
+    was switched on. It can be closed again after that.";
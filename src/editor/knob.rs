@@ -0,0 +1,270 @@
+use std::f32::consts::PI;
+
+use nih_plug::prelude::{Param, ParamSetter};
+use nih_plug_egui::egui::{
+    self, Id, Key, Pos2, Response, Sense, Shape, Stroke, TextEdit, Ui, Vec2, Widget, WidgetInfo,
+};
+
+/// Where the knob's arc starts, measured clockwise from the positive x axis (egui's y axis points down).
+const ARC_START: f32 = 0.75 * PI;
+/// How far the arc sweeps from minimum to maximum.
+const ARC_SWEEP: f32 = 1.5 * PI;
+const ARC_SEGMENTS: usize = 32;
+
+/// How much the normalized value changes per point of vertical dragging.
+const DRAG_SPEED: f32 = 0.005;
+/// Same thing but while shift is held, for fine adjustments.
+const FINE_DRAG_SPEED: f32 = 0.0005;
+/// How much the normalized value changes per arrow key press.
+const KEY_STEP: f32 = 0.01;
+/// Same thing but while shift is held.
+const FINE_KEY_STEP: f32 = 0.001;
+
+/// A rotary control for a single parameter.
+///
+/// - Drag up/down to change the value, hold **shift** while dragging for fine adjustment.
+/// - Double-click the knob to reset the parameter to its default value.
+/// - Click the value below the knob to type in a new value. This goes through the parameter's `string_to_value` formatter,
+///   so anything the parameter can display can also be typed back in.
+/// - The knob can be focused with **tab**. While focused, the arrow keys change the value (again, **shift** for fine
+///   adjustment), **enter** starts typing in a value, and **delete** resets it to the default.
+pub struct Knob<'a, P: Param> {
+    param: &'a P,
+    setter: &'a ParamSetter<'a>,
+    diameter: f32,
+}
+
+impl<'a, P: Param> Knob<'a, P> {
+    pub fn for_param(param: &'a P, setter: &'a ParamSetter<'a>) -> Self {
+        Self {
+            param,
+            setter,
+            diameter: 48.0,
+        }
+    }
+
+    fn set_normalized_value(&self, normalized: f32) {
+        // skip the update if it wouldn't change anything, so we don't spam the host with automation points
+        let value = self.param.preview_plain(normalized);
+        if value != self.param.modulated_plain_value() {
+            self.setter.set_parameter(self.param, value);
+        }
+    }
+
+    /// Handles dragging and double-click resets on the knob itself.
+    fn handle_knob_interaction(&self, ui: &Ui, response: &Response) {
+        // the unsnapped value while dragging, otherwise small drags on stepped parameters would get lost to rounding
+        let drag_value_id = response.id.with("drag-value");
+
+        if response.double_clicked() {
+            self.setter.begin_set_parameter(self.param);
+            self.setter
+                .set_parameter(self.param, self.param.default_plain_value());
+            self.setter.end_set_parameter(self.param);
+            return;
+        }
+
+        if response.drag_started() {
+            self.setter.begin_set_parameter(self.param);
+            ui.memory_mut(|memory| {
+                memory
+                    .data
+                    .insert_temp(drag_value_id, self.param.unmodulated_normalized_value())
+            });
+        }
+
+        if response.dragged() {
+            let speed = if ui.input(|input| input.modifiers.shift) {
+                FINE_DRAG_SPEED
+            } else {
+                DRAG_SPEED
+            };
+            // dragging up should increase the value
+            let delta = -response.drag_delta().y * speed;
+
+            let drag_value = ui.memory_mut(|memory| {
+                let drag_value = memory.data.get_temp_mut_or_insert_with(drag_value_id, || {
+                    self.param.unmodulated_normalized_value()
+                });
+                *drag_value = (*drag_value + delta).clamp(0.0, 1.0);
+                *drag_value
+            });
+            self.set_normalized_value(drag_value);
+        }
+
+        if response.drag_released() {
+            self.setter.end_set_parameter(self.param);
+            ui.memory_mut(|memory| memory.data.remove::<f32>(drag_value_id));
+        }
+    }
+
+    /// Handles the keyboard while the knob has focus.
+    fn handle_keyboard(&self, ui: &Ui, response: &Response) {
+        if !response.has_focus() {
+            return;
+        }
+
+        let (up, down, enter, delete, shift) = ui.input(|input| {
+            (
+                input.key_pressed(Key::ArrowUp) || input.key_pressed(Key::ArrowRight),
+                input.key_pressed(Key::ArrowDown) || input.key_pressed(Key::ArrowLeft),
+                input.key_pressed(Key::Enter),
+                input.key_pressed(Key::Delete) || input.key_pressed(Key::Backspace),
+                input.modifiers.shift,
+            )
+        });
+
+        let step = if shift { FINE_KEY_STEP } else { KEY_STEP };
+        let delta = match (up, down) {
+            (true, false) => step,
+            (false, true) => -step,
+            _ => 0.0,
+        };
+        if delta != 0.0 {
+            let normalized = (self.param.unmodulated_normalized_value() + delta).clamp(0.0, 1.0);
+            self.setter.begin_set_parameter(self.param);
+            self.set_normalized_value(normalized);
+            self.setter.end_set_parameter(self.param);
+        }
+
+        if delete {
+            self.setter.begin_set_parameter(self.param);
+            self.setter
+                .set_parameter(self.param, self.param.default_plain_value());
+            self.setter.end_set_parameter(self.param);
+        }
+
+        if enter {
+            self.start_text_entry(ui, response.id);
+        }
+    }
+
+    /// Opens the text box for typing in a value, prefilled with the current one.
+    fn start_text_entry(&self, ui: &Ui, id: Id) {
+        // prefill without the unit so the user can just start typing a number
+        let text = self
+            .param
+            .normalized_value_to_string(self.param.unmodulated_normalized_value(), false);
+        ui.memory_mut(|memory| memory.data.insert_temp(id.with("text-entry"), text));
+    }
+
+    /// Shows either the current value, or a text box if the user is typing in a new one.
+    fn value_ui(&self, ui: &mut Ui, id: Id) {
+        let text_id = id.with("text-entry");
+        let editing_text = ui.memory(|memory| memory.data.get_temp::<String>(text_id));
+
+        match editing_text {
+            Some(mut text) => {
+                let response =
+                    ui.add(TextEdit::singleline(&mut text).desired_width(self.diameter * 1.5));
+                response.request_focus();
+
+                let (enter, escape) = ui.input(|input| {
+                    (
+                        input.key_pressed(Key::Enter),
+                        input.key_pressed(Key::Escape),
+                    )
+                });
+                // the text box only loses focus on enter once it actually had focus, so the enter press that opened
+                // it from the keyboard doesn't immediately submit it again
+                if enter && response.lost_focus() {
+                    if let Some(normalized) = self.param.string_to_normalized_value(&text) {
+                        self.setter.begin_set_parameter(self.param);
+                        self.setter.set_parameter_normalized(self.param, normalized);
+                        self.setter.end_set_parameter(self.param);
+                    }
+                    ui.memory_mut(|memory| memory.data.remove::<String>(text_id));
+                } else if escape || response.lost_focus() {
+                    ui.memory_mut(|memory| memory.data.remove::<String>(text_id));
+                } else {
+                    ui.memory_mut(|memory| memory.data.insert_temp(text_id, text));
+                }
+            }
+            None => {
+                let value_text = self
+                    .param
+                    .normalized_value_to_string(self.param.modulated_normalized_value(), true);
+                let response = ui
+                    .add(egui::Label::new(value_text).sense(Sense::click()))
+                    .on_hover_text("Click to type in a value");
+                if response.clicked() {
+                    self.start_text_entry(ui, id);
+                }
+            }
+        }
+    }
+
+    fn paint(&self, ui: &Ui, response: &Response) {
+        let rect = response.rect;
+        let center = rect.center();
+        let radius = self.diameter / 2.0 - 3.0;
+        let visuals = ui.style().interact(response);
+        let accent = ui.visuals().selection.bg_fill;
+
+        let painter = ui.painter();
+        painter.circle_filled(center, radius, ui.visuals().extreme_bg_color);
+
+        let arc = |from: f32, to: f32| -> Vec<Pos2> {
+            (0..=ARC_SEGMENTS)
+                .map(|i| {
+                    let angle = from + (to - from) * (i as f32 / ARC_SEGMENTS as f32);
+                    center + Vec2::angled(angle) * radius
+                })
+                .collect()
+        };
+        let value = self.param.modulated_normalized_value();
+        let value_angle = ARC_START + value * ARC_SWEEP;
+
+        painter.add(Shape::line(
+            arc(ARC_START, ARC_START + ARC_SWEEP),
+            Stroke::new(3.0, visuals.bg_fill),
+        ));
+        painter.add(Shape::line(
+            arc(ARC_START, value_angle),
+            Stroke::new(3.0, accent),
+        ));
+        painter.line_segment(
+            [center, center + Vec2::angled(value_angle) * (radius - 4.0)],
+            Stroke::new(2.0, visuals.fg_stroke.color),
+        );
+
+        if response.has_focus() {
+            painter.circle_stroke(center, radius + 2.0, ui.visuals().selection.stroke);
+        }
+    }
+}
+
+impl<P: Param> Widget for Knob<'_, P> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        ui.vertical_centered(|ui| {
+            ui.label(self.param.name());
+
+            let (rect, response) =
+                ui.allocate_exact_size(Vec2::splat(self.diameter), Sense::click_and_drag());
+            self.handle_knob_interaction(ui, &response);
+            self.handle_keyboard(ui, &response);
+            // lets screen readers announce the knob as a slider, with its name and current value
+            response.widget_info(|| {
+                WidgetInfo::slider(
+                    self.param.modulated_normalized_value() as f64,
+                    format!(
+                        "{}: {}",
+                        self.param.name(),
+                        self.param.normalized_value_to_string(
+                            self.param.modulated_normalized_value(),
+                            true
+                        )
+                    ),
+                )
+            });
+            if ui.is_rect_visible(rect) {
+                self.paint(ui, &response);
+            }
+
+            self.value_ui(ui, response.id);
+
+            response
+        })
+        .inner
+    }
+}
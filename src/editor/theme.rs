@@ -0,0 +1,47 @@
+use nih_plug_egui::egui::{Color32, Visuals};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ThemeMode {
+    Dark,
+    Light,
+}
+
+/// The editor's appearance. This is part of the persisted plugin state,
+/// so the editor looks the same after reloading a project.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Theme {
+    pub mode: ThemeMode,
+    /// The accent color as sRGB, used for selections and active widgets.
+    pub accent: [u8; 3],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            mode: ThemeMode::Dark,
+            accent: [90, 170, 250],
+        }
+    }
+}
+
+impl Theme {
+    pub fn accent_color(&self) -> Color32 {
+        let [r, g, b] = self.accent;
+        Color32::from_rgb(r, g, b)
+    }
+
+    /// Builds the egui visuals for this theme, starting from egui's own dark/light defaults.
+    pub fn visuals(&self) -> Visuals {
+        let mut visuals = match self.mode {
+            ThemeMode::Dark => Visuals::dark(),
+            ThemeMode::Light => Visuals::light(),
+        };
+        let accent = self.accent_color();
+        visuals.selection.bg_fill = accent;
+        visuals.hyperlink_color = accent;
+        visuals.widgets.active.bg_fill = accent;
+        visuals.widgets.hovered.bg_stroke.color = accent;
+        visuals
+    }
+}
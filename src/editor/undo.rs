@@ -0,0 +1,177 @@
+use std::sync::{Arc, Mutex};
+
+use nih_plug::{prelude::*, wrapper::state::PluginState};
+
+use crate::param_writer::{ParamBatch, ParamWriter};
+
+/// How many undo steps are kept around.
+const MAX_ENTRIES: usize = 100;
+
+/// A single parameter going from one value to another, as normalized values.
+#[derive(Clone, Copy)]
+struct Change {
+    param: ParamPtr,
+    before: f32,
+    after: f32,
+}
+
+#[derive(Default)]
+struct HistoryState {
+    /// Every entry is one gesture, or a group of them (like loading a preset).
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    /// Gestures that have started but not ended yet, with the value from before they started.
+    open_gestures: Vec<(ParamPtr, f32)>,
+    /// Set while `UndoHistory::group()` is running. The first gesture starts a new entry, the rest get added to it.
+    grouping: Option<bool>,
+    /// Set while `UndoHistory::without_recording()` is running.
+    paused: bool,
+}
+
+/// The editor's own undo/redo history, since a lot of hosts don't reliably undo plugin parameter changes.
+///
+/// This records whole gestures (from grabbing a control to letting go of it), so dragging a knob around is a single
+/// undo step. Only changes made from the editor end up in here, see `RecordingContext`.
+#[derive(Default)]
+pub struct UndoHistory {
+    state: Mutex<HistoryState>,
+}
+
+impl UndoHistory {
+    fn begin_gesture(&self, param: ParamPtr) {
+        // SAFETY: the pointer comes from our own `GainParams`, which lives as long as the plugin does
+        let before = unsafe { param.unmodulated_normalized_value() };
+        let mut state = self.state.lock().unwrap();
+        if !state.paused {
+            state.open_gestures.push((param, before));
+        }
+    }
+
+    fn end_gesture(&self, param: ParamPtr) {
+        let mut state = self.state.lock().unwrap();
+        if state.paused {
+            return;
+        }
+        let Some(index) = state
+            .open_gestures
+            .iter()
+            .position(|(other, _)| *other == param)
+        else {
+            return;
+        };
+        let (_, before) = state.open_gestures.swap_remove(index);
+        // SAFETY: same as above
+        let after = unsafe { param.unmodulated_normalized_value() };
+        if before == after {
+            return;
+        }
+
+        let change = Change {
+            param,
+            before,
+            after,
+        };
+        match state.grouping {
+            Some(true) => {
+                state.undo.push(vec![change]);
+                state.grouping = Some(false);
+            }
+            Some(false) => match state.undo.last_mut() {
+                Some(entry) => entry.push(change),
+                None => state.undo.push(vec![change]),
+            },
+            None => state.undo.push(vec![change]),
+        }
+        if state.undo.len() > MAX_ENTRIES {
+            state.undo.remove(0);
+        }
+        state.redo.clear();
+    }
+
+    /// Runs `f`, and turns every gesture it makes into a single undo step.
+    pub fn group(&self, f: impl FnOnce()) {
+        self.state.lock().unwrap().grouping = Some(true);
+        f();
+        self.state.lock().unwrap().grouping = None;
+    }
+
+    /// Runs `f` without recording any of the gestures it makes, for changes that go through the editor's context but
+    /// weren't made in the editor (see `ParamWriter::drain()`).
+    pub fn without_recording(&self, f: impl FnOnce()) {
+        self.state.lock().unwrap().paused = true;
+        f();
+        self.state.lock().unwrap().paused = false;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.state.lock().unwrap().undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.state.lock().unwrap().redo.is_empty()
+    }
+
+    /// Reverts the last step. Goes through the `ParamWriter` instead of the editor's context, so undoing doesn't get
+    /// recorded as a new step. The change lands on the editor's next frame.
+    pub fn undo(&self, writer: &ParamWriter) {
+        let Some(entry) = self.state.lock().unwrap().undo.pop() else {
+            return;
+        };
+        let mut batch = ParamBatch::default();
+        for change in entry.iter().rev() {
+            batch.set_normalized(change.param, change.before);
+        }
+        writer.apply(batch);
+        self.state.lock().unwrap().redo.push(entry);
+    }
+
+    pub fn redo(&self, writer: &ParamWriter) {
+        let Some(entry) = self.state.lock().unwrap().redo.pop() else {
+            return;
+        };
+        let mut batch = ParamBatch::default();
+        for change in &entry {
+            batch.set_normalized(change.param, change.after);
+        }
+        writer.apply(batch);
+        self.state.lock().unwrap().undo.push(entry);
+    }
+}
+
+/// Wraps the editor's `GuiContext` to record every gesture in the `UndoHistory`, and forwards everything else as is.
+pub struct RecordingContext {
+    pub inner: Arc<dyn GuiContext>,
+    pub history: Arc<UndoHistory>,
+}
+
+impl GuiContext for RecordingContext {
+    fn plugin_api(&self) -> PluginApi {
+        self.inner.plugin_api()
+    }
+
+    fn request_resize(&self) -> bool {
+        self.inner.request_resize()
+    }
+
+    unsafe fn raw_begin_set_parameter(&self, param: ParamPtr) {
+        self.history.begin_gesture(param);
+        self.inner.raw_begin_set_parameter(param)
+    }
+
+    unsafe fn raw_set_parameter_normalized(&self, param: ParamPtr, normalized: f32) {
+        self.inner.raw_set_parameter_normalized(param, normalized)
+    }
+
+    unsafe fn raw_end_set_parameter(&self, param: ParamPtr) {
+        self.inner.raw_end_set_parameter(param);
+        self.history.end_gesture(param);
+    }
+
+    fn get_state(&self) -> PluginState {
+        self.inner.get_state()
+    }
+
+    fn set_state(&self, state: PluginState) {
+        self.inner.set_state(state)
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs, io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use nih_plug::prelude::*;
+
+use crate::params::GainParams;
+
+/// The oldest entries get dropped past this point.
+const MAX_ENTRIES: usize = 2000;
+/// How often `ParamWatcher` compares the parameters against the last time it looked.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Parameters that change the structure of the processing, these get logged as mode switches instead.
+const MODE_PARAM_IDS: [&str; 4] = ["lvldetection", "mode", "kneeshape", "quality"];
+/// The GR output gets written by the plugin itself many times per second, it would drown out everything else. The
+/// latency gets written by the plugin too, and always follows some other change that's already in the log.
+const IGNORED_PARAM_IDS: [&str; 2] = ["groutput", "latency"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EventKind {
+    Parameter,
+    ModeSwitch,
+    /// Factory presets, materials and scenes.
+    Preset,
+    /// The sample rate, channel layout and block size the host set the plugin up with.
+    Layout,
+    /// Something went wrong in the processing, see `SafeMode`.
+    Error,
+}
+
+impl EventKind {
+    fn label(&self) -> &'static str {
+        match self {
+            EventKind::Parameter => "param",
+            EventKind::ModeSwitch => "mode",
+            EventKind::Preset => "preset",
+            EventKind::Layout => "layout",
+            EventKind::Error => "error",
+        }
+    }
+}
+
+struct Entry {
+    /// Since the log was created.
+    time: Duration,
+    kind: EventKind,
+    message: String,
+}
+
+/// An opt-in history of what happened to the plugin, for bug reports along the lines of "it suddenly sounded
+/// different". Nothing gets recorded (or allocated) until it's enabled from the editor, and it isn't saved with the
+/// project.
+///
+/// Parameter changes get picked up by polling, see `ParamWatcher`, so they show up no matter where they came from
+/// (the editor, host automation, MIDI, OSC, ...).
+pub struct EventLog {
+    enabled: AtomicBool,
+    started: Instant,
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            started: Instant::now(),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl EventLog {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        if enabled == self.is_enabled() {
+            return;
+        }
+        if enabled {
+            self.enabled.store(true, Ordering::Relaxed);
+            let unix_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs());
+            self.record(
+                EventKind::Layout,
+                format!("Logging started (unix time {unix_time})"),
+            );
+        } else {
+            self.record(EventKind::Layout, "Logging stopped");
+            self.enabled.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Adds an entry, if the log is enabled. This locks and allocates, so the audio thread shouldn't call it.
+    pub fn record(&self, kind: EventKind, message: impl Into<String>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_back(Entry {
+            time: self.started.elapsed(),
+            kind,
+            message: message.into(),
+        });
+        if entries.len() > MAX_ENTRIES {
+            entries.pop_front();
+        }
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Writes the log to a plain text file, one entry per line.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut text = format!("funih {} event log\n", env!("CARGO_PKG_VERSION"));
+        for entry in self.entries.lock().unwrap().iter() {
+            let _ = writeln!(
+                text,
+                "[{:>10.3} s] {:<6} {}",
+                entry.time.as_secs_f64(),
+                entry.kind.label(),
+                entry.message
+            );
+        }
+        fs::write(path, text)
+    }
+}
+
+/// Polls the parameters on a background thread while the `EventLog` is enabled, and logs whatever changed.
+pub struct ParamWatcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ParamWatcher {
+    pub fn spawn(params: Arc<GainParams>, log: Arc<EventLog>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::Builder::new()
+            .name(String::from("funih-event-log"))
+            .spawn({
+                let stop = stop.clone();
+                move || run_watcher(&params, &log, &stop)
+            })
+            .map_err(|err| nih_error!("Could not spawn the event log thread: {err}"))
+            .ok();
+
+        Self { stop, handle }
+    }
+}
+
+impl Drop for ParamWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run_watcher(params: &GainParams, log: &EventLog, stop: &AtomicBool) {
+    let param_map = params.param_map();
+    // `None` while the log is disabled, so enabling it doesn't log every parameter at once
+    let mut previous: Option<Vec<f32>> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+
+        if !log.is_enabled() {
+            previous = None;
+            continue;
+        }
+
+        // SAFETY: the pointers come from our own `GainParams`, which lives as long as the plugin does
+        let values: Vec<f32> = param_map
+            .iter()
+            .map(|(_, param, _)| unsafe { param.unmodulated_normalized_value() })
+            .collect();
+        if let Some(previous) = &previous {
+            for ((id, param, _), (before, after)) in
+                param_map.iter().zip(previous.iter().zip(&values))
+            {
+                if before == after || IGNORED_PARAM_IDS.contains(&id.as_str()) {
+                    continue;
+                }
+                let kind = if MODE_PARAM_IDS.contains(&id.as_str()) {
+                    EventKind::ModeSwitch
+                } else {
+                    EventKind::Parameter
+                };
+                // SAFETY: same as above
+                let message = unsafe {
+                    format!(
+                        "{}: {} → {}",
+                        param.name(),
+                        param.normalized_value_to_string(*before, true),
+                        param.normalized_value_to_string(*after, true)
+                    )
+                };
+                log.record(kind, message);
+            }
+        }
+        previous = Some(values);
+    }
+}
@@ -0,0 +1,219 @@
+use nih_plug::{prelude::Enum, util};
+
+/// A level or a gain **in decibels**.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Db(pub f32);
+
+/// A linear gain factor or amplitude, where `1.0` is unity gain (0 dB).
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct LinearGain(pub f32);
+
+/// The pair of functions used to convert between `Db` and `LinearGain`.
+/// Both always come from the same family, so going to dB and back doesn't introduce any drift. See `DbConversion`.
+#[derive(Clone, Copy)]
+pub struct Conversions {
+    to_db: fn(f32) -> f32,
+    to_gain: fn(f32) -> f32,
+}
+
+impl Conversions {
+    pub fn new(exact: bool) -> Self {
+        if exact {
+            Self {
+                to_db: util::gain_to_db,
+                to_gain: util::db_to_gain,
+            }
+        } else {
+            Self {
+                to_db: util::gain_to_db_fast,
+                to_gain: util::db_to_gain_fast,
+            }
+        }
+    }
+}
+
+impl LinearGain {
+    pub fn to_db(self, conversions: &Conversions) -> Db {
+        Db((conversions.to_db)(self.0))
+    }
+}
+
+impl Db {
+    pub fn to_gain(self, conversions: &Conversions) -> LinearGain {
+        LinearGain((conversions.to_gain)(self.0))
+    }
+}
+
+// TODO:
+// lookup table (input dB -> output gain), regenerated whenever threshold/ratio/knee change. the gain computer runs on
+// every sample now, but the smoothing changes the curve on every sample too while a parameter moves, so a LUT only
+// pays off for steady settings. revisit (with benchmarks) once there's a multiband/spectral mode.
+
+/// How the curve gets from 1:1 to the full ratio inside the knee.
+#[derive(Clone, Copy, PartialEq, Enum)]
+pub enum KneeShape {
+    /// The slope changes gradually across the knee, which gives a smooth curve.
+    Quadratic,
+    /// A straight line across the knee, with a slope halfway between 1:1 and the ratio. At extreme ratios this gets
+    /// noticeably harder than the quadratic knee, and compresses a bit less right at the threshold.
+    #[name = "Ratio Blend"]
+    RatioBlend,
+}
+
+/// The static curve of the compressor: how loud the output should be for a given input level.
+///
+/// The `ratio` is a plain slope, not a level, so it has no unit. A ratio of `4.0` (4:1) means that for every 4 dB
+/// the input goes over the threshold, the output only goes 1 dB over it. A ratio below `1.0` makes the output go
+/// *further* over the threshold than the input did, which is how upward expansion works.
+#[derive(Clone, Copy)]
+pub struct GainComputer {
+    pub threshold: Db,
+    pub ratio: f32,
+    /// The width of the soft knee, centered on the threshold. `Db(0.0)` is a hard knee.
+    pub knee_width: Db,
+    pub knee_shape: KneeShape,
+}
+
+impl GainComputer {
+    /// The output level for an input level, i.e. the transfer curve you'd see on a compressor's graph.
+    pub fn output_level(&self, input: Db) -> Db {
+        let Db(input) = input;
+        let Db(threshold) = self.threshold;
+        let Db(knee_width) = self.knee_width;
+        let slope = 1.0 / self.ratio - 1.0;
+
+        let difference = input - threshold;
+        if knee_width > 0.0 && 2.0 * difference.abs() <= knee_width {
+            let overshoot = difference + knee_width / 2.0;
+            match self.knee_shape {
+                // inside the knee, blend between the two slopes with a quadratic
+                KneeShape::Quadratic => Db(input + slope * overshoot.powi(2) / (2.0 * knee_width)),
+                // or just draw a straight line between both ends of the knee
+                KneeShape::RatioBlend => Db(input + slope * overshoot / 2.0),
+            }
+        } else if difference > 0.0 {
+            // above the knee, apply the ratio
+            Db(threshold + difference / self.ratio)
+        } else {
+            // below the knee/threshold nothing changes
+            Db(input)
+        }
+    }
+
+    /// The gain to apply to a signal with the detected `level`.
+    ///
+    /// The curve gives us the level the output *should* have, but we need a gain to multiply the input by. In dB,
+    /// that's just the difference between where the output should be and where the input is.
+    pub fn gain(&self, level: LinearGain, conversions: &Conversions) -> LinearGain {
+        let input = level.to_db(conversions);
+        let Db(output) = self.output_level(input);
+
+        Db(output - input.0).to_gain(conversions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every hundredth of a dB from just above -100 dB (where the exact conversion cuts off) up to +30 dB.
+    fn working_range() -> impl Iterator<Item = f32> {
+        (-9999..=3000).map(|centibels| centibels as f32 / 100.0)
+    }
+
+    #[test]
+    fn fast_db_to_gain_matches_exact() {
+        let (exact, fast) = (Conversions::new(true), Conversions::new(false));
+        for db in working_range() {
+            let LinearGain(expected) = Db(db).to_gain(&exact);
+            let LinearGain(actual) = Db(db).to_gain(&fast);
+            let error = (actual - expected).abs() / expected;
+            assert!(error < 2e-6, "{db} dB: {expected} vs {actual}");
+        }
+    }
+
+    #[test]
+    fn fast_gain_to_db_matches_exact() {
+        let (exact, fast) = (Conversions::new(true), Conversions::new(false));
+        for db in working_range() {
+            let gain = Db(db).to_gain(&exact);
+            let Db(expected) = gain.to_db(&exact);
+            let Db(actual) = gain.to_db(&fast);
+            // relative to the value, except right around 0 dB where that would blow up
+            let error = (actual - expected).abs() / expected.abs().max(1.0);
+            assert!(error < 2e-6, "{db} dB: {expected} vs {actual}");
+        }
+    }
+
+    fn computer(ratio: f32, knee_width: f32, knee_shape: KneeShape) -> GainComputer {
+        GainComputer {
+            threshold: Db(-20.0),
+            ratio,
+            knee_width: Db(knee_width),
+            knee_shape,
+        }
+    }
+
+    fn assert_close(Db(actual): Db, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn hard_knee() {
+        let computer = computer(4.0, 0.0, KneeShape::Quadratic);
+        assert_close(computer.output_level(Db(-40.0)), -40.0);
+        assert_close(computer.output_level(Db(-20.0)), -20.0);
+        // 8 dB over the threshold at 4:1 comes out 2 dB over it
+        assert_close(computer.output_level(Db(-12.0)), -18.0);
+        assert_close(computer.output_level(Db(20.0)), -10.0);
+    }
+
+    #[test]
+    fn quadratic_knee() {
+        let computer = computer(4.0, 10.0, KneeShape::Quadratic);
+        // below and above the knee it's the same as the hard knee
+        assert_close(computer.output_level(Db(-30.0)), -30.0);
+        assert_close(computer.output_level(Db(-10.0)), -17.5);
+        // halfway into the knee the slope is halfway to the ratio, so the output sits below the threshold already
+        assert_close(computer.output_level(Db(-20.0)), -20.9375);
+        assert_close(computer.output_level(Db(-17.5)), -19.609375);
+    }
+
+    #[test]
+    fn knees_are_continuous_at_both_edges() {
+        for knee_shape in [KneeShape::Quadratic, KneeShape::RatioBlend] {
+            for ratio in [0.5, 2.0, 4.0, 100.0] {
+                let soft = computer(ratio, 10.0, knee_shape);
+                let hard = computer(ratio, 0.0, knee_shape);
+                // outside the knee both curves are the same, so at the edges the soft one has to meet the hard one
+                for edge in [-25.0, -15.0] {
+                    let Db(expected) = hard.output_level(Db(edge));
+                    assert_close(soft.output_level(Db(edge)), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn expansion_below_unity_ratio() {
+        let computer = computer(0.5, 0.0, KneeShape::Quadratic);
+        assert_close(computer.output_level(Db(-30.0)), -30.0);
+        // 5 dB over the threshold at 1:2 comes out 10 dB over it
+        assert_close(computer.output_level(Db(-15.0)), -10.0);
+
+        let conversions = Conversions::new(true);
+        let LinearGain(below) = computer.gain(Db(-30.0).to_gain(&conversions), &conversions);
+        assert!((below - 1.0).abs() < 1e-5, "{below}");
+        let LinearGain(above) = computer.gain(Db(-15.0).to_gain(&conversions), &conversions);
+        assert!((above - util::db_to_gain(5.0)).abs() < 1e-4, "{above}");
+    }
+
+    #[test]
+    fn only_exact_conversion_snaps_to_silence() {
+        assert_eq!(Db(-100.0).to_gain(&Conversions::new(true)), LinearGain(0.0));
+        assert!(Db(-100.0).to_gain(&Conversions::new(false)).0 > 0.0);
+    }
+}
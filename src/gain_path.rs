@@ -0,0 +1,498 @@
+use crate::{
+    ballistics::{BallisticsCoefficients, GainEnvelope},
+    gain_computer::{Conversions, Db, GainComputer, LinearGain},
+    smoothing::Smoother,
+    GainComputerMode,
+};
+
+/// Turns the detector's level into the gain the compressor applies: the gain computer's threshold, ratio and knee width
+/// get smoothed, the curve turns the level into a target gain, and the envelope adds the attack and release on top.
+///
+/// All of that happens once per sample, so where the host (or the internal block size) splits up the audio doesn't
+/// change the gain, and an automated threshold takes effect on exactly the sample it was automated on. Eco mode is the
+/// exception, it skips ahead a whole block at a time on purpose. Nothing in here touches the host or nih-plug's
+/// parameters, so the tests below run the same code `process()` does.
+pub struct GainPath {
+    threshold: Smoother,
+    ratio: Smoother,
+    knee_width: Smoother,
+    /// Attack and (dual) release, applied to the target gain.
+    envelope: GainEnvelope,
+    /// The polarity of the last sample of audio, for finding zero crossings for the LF hold.
+    was_positive: bool,
+}
+
+impl Default for GainPath {
+    fn default() -> Self {
+        Self {
+            threshold: Smoother::default(),
+            ratio: Smoother::default(),
+            knee_width: Smoother::default(),
+            envelope: GainEnvelope::default(),
+            was_positive: true,
+        }
+    }
+}
+
+impl GainPath {
+    /// Smooths the curve's threshold, ratio and knee width towards `target`'s by `samples` samples. `smoothing` is the
+    /// per-sample coefficient, see `GainParams::param_smoothing_time`.
+    pub fn curve(&mut self, target: &GainComputer, smoothing: f32, samples: usize) -> GainComputer {
+        let threshold = self.threshold.next(target.threshold.0, smoothing, samples);
+        let ratio = self.ratio.next(target.ratio, smoothing, samples);
+        let knee_width = self
+            .knee_width
+            .next(target.knee_width.0, smoothing, samples);
+        GainComputer {
+            threshold: Db(threshold),
+            ratio,
+            knee_width: Db(knee_width),
+            ..*target
+        }
+    }
+
+    /// Advances the envelope by one sample towards `target_gain`, and returns the gain to apply to `input`. The input
+    /// is only used to find the zero crossings for the LF hold.
+    pub fn envelope(
+        &mut self,
+        target_gain: f32,
+        input: [f32; 2],
+        coefficients: &BallisticsCoefficients,
+        lf_hold: bool,
+    ) -> f32 {
+        let positive = input[0] + input[1] >= 0.0;
+        let zero_crossing = lf_hold.then_some(positive != self.was_positive);
+        self.was_positive = positive;
+        self.envelope.next(target_gain, coefficients, zero_crossing)
+    }
+
+    /// Advances the envelope by a whole block at once, for eco mode. `coefficients` need to be for the block rate
+    /// instead of the sample rate. The LF hold needs to see every zero crossing, so it doesn't work here.
+    pub fn envelope_block(
+        &mut self,
+        target_gain: f32,
+        coefficients: &BallisticsCoefficients,
+    ) -> f32 {
+        self.envelope.next(target_gain, coefficients, None)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The gain the envelope heads towards for a detector `level` (linear). Anything that boosts (upward expansion, ratios
+/// below 1:1) is capped at `max_boost`, so a sudden loud input can't blow up the output.
+pub fn target_gain(
+    curve: &GainComputer,
+    mode: GainComputerMode,
+    level: f32,
+    max_boost: LinearGain,
+    conversions: &Conversions,
+) -> f32 {
+    let curve = match mode {
+        GainComputerMode::Compress => *curve,
+        // the same curve with the slope flipped, nothing changes below the threshold so the noise floor is left alone
+        GainComputerMode::UpwardExpand => GainComputer {
+            ratio: curve.ratio.recip(),
+            ..*curve
+        },
+    };
+    let LinearGain(gain) = curve.gain(LinearGain(level), conversions);
+    gain.min(max_boost.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        f32::consts::TAU,
+        path::{Path, PathBuf},
+    };
+
+    use fundsp::MAX_BUFFER_SIZE;
+    use nih_plug::util;
+
+    use super::*;
+    use crate::{
+        detector::{Detector, DetectorCoefficients},
+        gain_computer::KneeShape,
+        smoothing::one_pole_coefficient,
+        LevelDetection,
+    };
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    /// Where the tests automate the threshold. On purpose not on a block (or any of the tested buffers') boundary.
+    const AUTOMATED_AT: usize = 10_007;
+
+    /// The largest difference a render can have from its reference, in (linear) sample values. That's -80 dBFS, way
+    /// more than what different platforms' `powf`s and `sin`s round differently, and way less than anything audible.
+    const GOLDEN_TOLERANCE: f32 = 1e-4;
+
+    /// What a render runs with. Only the threshold gets automated.
+    struct Settings {
+        /// `(sample, threshold in dB)`, sorted by sample and starting at sample 0.
+        threshold: Vec<(usize, f32)>,
+        ratio: f32,
+        knee_width: f32,
+        level_detection: LevelDetection,
+        /// All times are in seconds.
+        smoothing_time: f32,
+        attack_time: f32,
+        release_time: f32,
+        lf_hold: bool,
+    }
+
+    impl Default for Settings {
+        fn default() -> Self {
+            Self {
+                threshold: vec![(0, -20.0)],
+                ratio: 4.0,
+                knee_width: 0.0,
+                level_detection: LevelDetection::Peak,
+                smoothing_time: 0.0,
+                attack_time: 0.0,
+                release_time: 0.0,
+                lf_hold: false,
+            }
+        }
+    }
+
+    /// Runs `input` through the detector and the `GainPath` the way `process()` does, and returns the gain for every
+    /// sample. The host hands the input over in buffers of `buffer_size` samples, which get split up wherever the
+    /// threshold changes (like nih-plug's wrapper does for sample accurate automation), and then into blocks of at most
+    /// `MAX_BUFFER_SIZE` samples.
+    fn render(input: &[[f32; 2]], settings: &Settings, buffer_size: usize) -> Vec<f32> {
+        let detector_coefficients = DetectorCoefficients::new(0.0, 0.0, 0.05, SAMPLE_RATE);
+        let ballistics_coefficients = BallisticsCoefficients::new(
+            settings.attack_time,
+            settings.release_time,
+            settings.release_time,
+            0.0,
+            0.0,
+            SAMPLE_RATE,
+        );
+        let smoothing = one_pole_coefficient(settings.smoothing_time, SAMPLE_RATE);
+        let conversions = Conversions::new(true);
+        let mut detector = Detector::default();
+        let mut gain_path = GainPath::default();
+
+        let mut gains = Vec::with_capacity(input.len());
+        let mut start = 0;
+        while start < input.len() {
+            let next_buffer = (start / buffer_size + 1) * buffer_size;
+            let next_change = settings
+                .threshold
+                .iter()
+                .map(|(at, _)| *at)
+                .find(|at| *at > start)
+                .unwrap_or(input.len());
+            let end = next_buffer
+                .min(next_change)
+                .min(start + MAX_BUFFER_SIZE)
+                .min(input.len());
+            let block = &input[start..end];
+
+            let (_, threshold) = settings
+                .threshold
+                .iter()
+                .rev()
+                .find(|(at, _)| *at <= start)
+                .unwrap();
+            let curve_target = GainComputer {
+                threshold: Db(*threshold),
+                ratio: settings.ratio,
+                knee_width: Db(settings.knee_width),
+                knee_shape: KneeShape::Quadratic,
+            };
+            let readings: Vec<_> = block
+                .iter()
+                .map(|frame| detector.process(*frame, &detector_coefficients))
+                .collect();
+            for (frame, readings) in block.iter().zip(readings) {
+                let curve = gain_path.curve(&curve_target, smoothing, 1);
+                let target_gain = target_gain(
+                    &curve,
+                    GainComputerMode::Compress,
+                    readings.level(settings.level_detection, 0.0),
+                    LinearGain(f32::INFINITY),
+                    &conversions,
+                );
+                gains.push(gain_path.envelope(
+                    target_gain,
+                    *frame,
+                    &ballistics_coefficients,
+                    settings.lf_hold,
+                ));
+            }
+            start = end;
+        }
+        gains
+    }
+
+    /// A 1 kHz sine on both channels, going through `(level in dBFS, length in seconds)` one after the other.
+    fn sine_bursts(bursts: &[(f32, f32)]) -> Vec<[f32; 2]> {
+        bursts
+            .iter()
+            .flat_map(|(level, seconds)| {
+                std::iter::repeat(util::db_to_gain(*level)).take((seconds * SAMPLE_RATE) as usize)
+            })
+            .enumerate()
+            .map(|(index, amplitude)| {
+                let sample = amplitude * (TAU * 1000.0 * index as f32 / SAMPLE_RATE).sin();
+                [sample; 2]
+            })
+            .collect()
+    }
+
+    /// How much further down the automated threshold pulls the gain on every sample, in dB, compared to leaving the
+    /// threshold where it was. A steady -6 dBFS sine goes through both, with the threshold automated from -10 dB to
+    /// -30 dB.
+    fn automation_difference(smoothing_time: f32) -> Vec<f32> {
+        let input = sine_bursts(&[(-6.0, 0.5)]);
+        let settings = Settings {
+            threshold: vec![(0, -10.0), (AUTOMATED_AT, -30.0)],
+            smoothing_time,
+            ..Settings::default()
+        };
+        let automated = render(&input, &settings, 512);
+        let settings = Settings {
+            threshold: vec![(0, -10.0)],
+            ..settings
+        };
+        let steady = render(&input, &settings, 512);
+
+        steady
+            .iter()
+            .zip(automated)
+            .map(|(steady, automated)| util::gain_to_db(steady / automated))
+            .collect()
+    }
+
+    /// The test material in `testdata/` (48 kHz mono, processed as dual mono), and the settings it gets rendered with.
+    /// Each file has a reference render next to it, `<name>.reference.wav`.
+    ///
+    /// After a change that's supposed to change how the compressor sounds, run the tests with `FUNIH_BLESS` set to
+    /// write new references, and listen to them before committing them.
+    fn golden_cases() -> [(&'static str, Settings); 3] {
+        [
+            (
+                "sine_bursts",
+                Settings {
+                    knee_width: 6.0,
+                    level_detection: LevelDetection::Rms,
+                    attack_time: 0.01,
+                    release_time: 0.1,
+                    ..Settings::default()
+                },
+            ),
+            (
+                "drum_loop",
+                Settings {
+                    threshold: vec![(0, -18.0)],
+                    ratio: 6.0,
+                    attack_time: 0.001,
+                    release_time: 0.08,
+                    lf_hold: true,
+                    ..Settings::default()
+                },
+            ),
+            (
+                "vocal",
+                Settings {
+                    threshold: vec![(0, -24.0)],
+                    ratio: 3.0,
+                    knee_width: 12.0,
+                    level_detection: LevelDetection::Rms,
+                    smoothing_time: 0.02,
+                    attack_time: 0.005,
+                    release_time: 0.15,
+                    ..Settings::default()
+                },
+            ),
+        ]
+    }
+
+    fn testdata(file: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(file)
+    }
+
+    fn read_wav(file: &str) -> Vec<f32> {
+        let mut reader = hound::WavReader::open(testdata(file)).unwrap();
+        let spec = reader.spec();
+        assert_eq!(
+            (spec.channels, spec.sample_rate),
+            (1, SAMPLE_RATE as u32),
+            "{file}"
+        );
+        match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().map(Result::unwrap).collect(),
+            hound::SampleFormat::Int => {
+                let full_scale = (1 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.unwrap() as f32 / full_scale)
+                    .collect()
+            }
+        }
+    }
+
+    fn write_wav(file: &str, samples: &[f32]) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE as u32,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(testdata(file), spec).unwrap();
+        for sample in samples {
+            writer.write_sample(*sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    /// Renders one of the `golden_cases()`, only the left channel since both are the same.
+    fn render_golden(name: &str, settings: &Settings) -> Vec<f32> {
+        let input: Vec<_> = read_wav(&format!("{name}.wav"))
+            .into_iter()
+            .map(|sample| [sample; 2])
+            .collect();
+        let gains = render(&input, settings, 512);
+        input
+            .iter()
+            .zip(gains)
+            .map(|([sample, _], gain)| sample * gain)
+            .collect()
+    }
+
+    #[test]
+    fn renders_match_the_references() {
+        for (name, settings) in golden_cases() {
+            let output = render_golden(name, &settings);
+            let reference_file = format!("{name}.reference.wav");
+            if std::env::var_os("FUNIH_BLESS").is_some() {
+                write_wav(&reference_file, &output);
+                continue;
+            }
+
+            let reference = read_wav(&reference_file);
+            assert_eq!(output.len(), reference.len(), "{name}");
+            let (index, error) = output
+                .iter()
+                .zip(&reference)
+                .map(|(output, reference)| (output - reference).abs())
+                .enumerate()
+                .fold((0, 0.0), |worst, (index, error)| {
+                    if error > worst.1 {
+                        (index, error)
+                    } else {
+                        worst
+                    }
+                });
+            assert!(
+                error <= GOLDEN_TOLERANCE,
+                "{name} is off by {error} at sample {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn sine_bursts_settle_on_the_curve() {
+        let [(name, settings), ..] = golden_cases();
+        let input = read_wav(&format!("{name}.wav"));
+        let output = render_golden(name, &settings);
+
+        // the last 20 ms of the -6 dBFS and the -12 dBFS bursts, whose RMS levels are 3 dB lower. the RMS detector
+        // hasn't quite caught up with the level by then, so this only needs to be close
+        for (end, expected_gain_reduction) in
+            [(0.5, (-9.0 + 20.0) * 0.75), (1.0, (-15.0 + 20.0) * 0.75)]
+        {
+            let end = (end * SAMPLE_RATE) as usize;
+            let start = end - (0.02 * SAMPLE_RATE) as usize;
+            let rms = |samples: &[f32]| {
+                (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32)
+                    .sqrt()
+            };
+            let gain_reduction =
+                util::gain_to_db(rms(&input[start..end]) / rms(&output[start..end]));
+            assert!(
+                (gain_reduction - expected_gain_reduction).abs() < 0.25,
+                "{gain_reduction} dB instead of {expected_gain_reduction} dB"
+            );
+        }
+    }
+
+    #[test]
+    fn gain_does_not_depend_on_the_buffer_size() {
+        // in and out of gain reduction, with everything that keeps state between samples doing something
+        let input = sine_bursts(&[(-40.0, 0.1), (-6.0, 0.2), (-30.0, 0.2), (0.0, 0.1)]);
+        let settings = Settings {
+            threshold: vec![(0, -20.0), (AUTOMATED_AT, -26.0)],
+            knee_width: 6.0,
+            level_detection: LevelDetection::Rms,
+            smoothing_time: 0.02,
+            attack_time: 0.005,
+            release_time: 0.05,
+            lf_hold: true,
+            ..Settings::default()
+        };
+
+        let reference = render(&input, &settings, 1);
+        for buffer_size in [7, MAX_BUFFER_SIZE - 1, MAX_BUFFER_SIZE, 100, 512, 4099] {
+            assert!(
+                render(&input, &settings, buffer_size) == reference,
+                "buffer size {buffer_size}"
+            );
+        }
+    }
+
+    #[test]
+    fn automated_threshold_lands_on_the_right_sample() {
+        let difference = automation_difference(0.0);
+
+        assert!(difference[..AUTOMATED_AT].iter().all(|db| *db == 0.0));
+        // the level stays above both thresholds, so 20 dB lower at 4:1 is 15 dB more gain reduction
+        assert!(difference[AUTOMATED_AT..]
+            .iter()
+            .all(|db| (db - 15.0).abs() < 1e-3));
+    }
+
+    #[test]
+    fn smoothing_starts_on_the_automated_sample() {
+        let difference = automation_difference(0.01);
+
+        assert!(difference[..AUTOMATED_AT].iter().all(|db| *db == 0.0));
+        // the threshold only moves a little on the first sample
+        assert!(difference[AUTOMATED_AT] > 0.0 && difference[AUTOMATED_AT] < 0.1);
+        // and from there a little more on every sample, instead of stepping at block boundaries
+        let smoothing = &difference[AUTOMATED_AT..AUTOMATED_AT + 2 * MAX_BUFFER_SIZE];
+        assert!(smoothing.windows(2).all(|pair| pair[1] > pair[0]));
+        // until it's all the way there
+        let settled = difference[AUTOMATED_AT + (0.2 * SAMPLE_RATE) as usize];
+        assert!((settled - 15.0).abs() < 1e-3, "{settled} dB");
+    }
+
+    /// A NaN or an infinity coming in (from a broken plugin earlier in the chain, say) mustn't get stuck in the
+    /// detector, and it mustn't turn into a boost either.
+    #[test]
+    fn broken_input_does_not_stick() {
+        let clean = sine_bursts(&[(-6.0, 1.0)]);
+        let mut broken = clean.clone();
+        broken[1000] = [f32::NAN; 2];
+        broken[2000] = [f32::INFINITY, f32::NEG_INFINITY];
+        broken[3000] = [f32::MAX, f32::NAN];
+        let settings = Settings::default();
+        let gains = render(&broken, &settings, 512);
+
+        assert!(gains
+            .iter()
+            .all(|gain| gain.is_finite() && (0.0..=1.0).contains(gain)));
+        let expected = render(&clean, &settings, 512);
+        let (last, expected) = (gains.last().unwrap(), expected.last().unwrap());
+        assert!(
+            (last - expected).abs() < 1e-4,
+            "{last} instead of {expected}"
+        );
+    }
+}
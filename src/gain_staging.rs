@@ -0,0 +1,131 @@
+use std::sync::atomic::AtomicBool;
+
+use fundsp::hacker::{shared, Shared};
+
+/// How far above the threshold the input's average loudness should sit.
+/// At this level, the louder parts get compressed while the quieter parts pass through untouched.
+const SWEET_SPOT_ABOVE_THRESHOLD_DB: f32 = 6.0;
+/// The input and output gain parameters' range.
+const MAX_SUGGESTED_GAIN_DB: f32 = 30.0;
+
+/// State for the gain staging assistant, shared between the editor and the audio thread.
+/// While `analyzing` is set, the audio thread measures the integrated loudness of the (unprocessed) input
+/// and publishes it in `measured_lufs`. The processed output gets measured at the same time, in `output_lufs`.
+pub struct GainStaging {
+    pub analyzing: AtomicBool,
+    /// `NaN` until enough audio has been measured.
+    pub measured_lufs: Shared,
+    /// The integrated loudness of the output (after the output gain), `NaN` until enough audio has been measured.
+    pub output_lufs: Shared,
+}
+
+impl Default for GainStaging {
+    fn default() -> Self {
+        Self {
+            analyzing: AtomicBool::new(false),
+            measured_lufs: shared(f32::NAN),
+            output_lufs: shared(f32::NAN),
+        }
+    }
+}
+
+/// What the loudness analyzers should measure of a frame. Mono gets processed as dual mono, but BS.1770 measures a mono
+/// signal as a single channel, and both channels would read 3 dB louder than that.
+pub fn measured_frame([left, right]: [f32; 2], channels: usize) -> [f32; 2] {
+    if channels == 1 {
+        [left, 0.0]
+    } else {
+        [left, right]
+    }
+}
+
+impl GainStaging {
+    /// The input gain (in dB) that would put the input's average loudness right in the compressor's sweet spot,
+    /// or `None` if nothing has been measured yet.
+    pub fn suggested_input_gain_db(&self, threshold_db: f32) -> Option<f32> {
+        let measured = self.measured_lufs.value();
+        measured.is_finite().then(|| {
+            (threshold_db + SWEET_SPOT_ABOVE_THRESHOLD_DB - measured)
+                .clamp(-MAX_SUGGESTED_GAIN_DB, MAX_SUGGESTED_GAIN_DB)
+        })
+    }
+
+    /// The output gain (in dB) that would make the output as loud as the input was before any processing, given the
+    /// output gain that was used while measuring. `None` until both sides have been measured.
+    pub fn matched_output_gain_db(&self, output_gain_db: f32) -> Option<f32> {
+        let input = self.measured_lufs.value();
+        let output = self.output_lufs.value();
+        (input.is_finite() && output.is_finite()).then(|| {
+            (output_gain_db + input - output).clamp(-MAX_SUGGESTED_GAIN_DB, MAX_SUGGESTED_GAIN_DB)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nih_plug::util;
+
+    use super::*;
+    use crate::loudness::LoudnessMeter;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /// The integrated loudness of three seconds of a 1 kHz sine at `amplitude`, measured with `channels` channels.
+    /// `frame` turns each sample into the frame that gets measured.
+    fn measure_frames(amplitude: f32, channels: usize, frame: impl Fn(f32) -> [f32; 2]) -> f32 {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+        for n in 0..(SAMPLE_RATE as usize * 3) {
+            let sample =
+                (std::f32::consts::TAU * 1000.0 * n as f32 / SAMPLE_RATE).sin() * amplitude;
+            meter.process(measured_frame(frame(sample), channels));
+        }
+        meter.report().unwrap().integrated_lufs
+    }
+
+    /// Like `process()` sees it, mono gets duplicated to both channels.
+    fn measure_channels(amplitude: f32, channels: usize) -> f32 {
+        measure_frames(amplitude, channels, |sample| [sample, sample])
+    }
+
+    fn measure(amplitude: f32) -> f32 {
+        measure_channels(amplitude, 2)
+    }
+
+    #[test]
+    fn mono_measures_as_a_single_channel() {
+        // BS.1770's reference point: a full scale 1 kHz sine on a single channel reads -3.01 LUFS
+        let mono = measure_channels(1.0, 1);
+        assert!((mono + 3.01).abs() < 0.05, "{mono}");
+        let stereo = measure_channels(1.0, 2);
+        assert!((stereo - mono - 3.01).abs() < 0.05, "{mono} vs {stereo}");
+    }
+
+    #[test]
+    fn mono_pass_through_matches_at_unity() {
+        let staging = GainStaging::default();
+        // the input analyzer gets the dual mono frame, while a mono output only fills in the first channel
+        staging.measured_lufs.set(measure_channels(0.5, 1));
+        staging
+            .output_lufs
+            .set(measure_frames(0.5, 1, |sample| [sample, 0.0]));
+        let matched = staging.matched_output_gain_db(0.0).unwrap();
+        assert!(matched.abs() < 0.01, "{matched}");
+    }
+
+    #[test]
+    fn matching_twice_keeps_the_output_gain() {
+        let staging = GainStaging::default();
+        // a compressor that takes off a steady 6 dB, with the output gain applied after it
+        let compressed = |output_gain_db: f32| 0.5 * 0.5 * util::db_to_gain(output_gain_db);
+        staging.measured_lufs.set(measure(0.5));
+
+        staging.output_lufs.set(measure(compressed(0.0)));
+        let first = staging.matched_output_gain_db(0.0).unwrap();
+        assert!((first - 6.02).abs() < 0.05, "{first}");
+
+        // measuring again with the matched gain applied shouldn't suggest anything different
+        staging.output_lufs.set(measure(compressed(first)));
+        let second = staging.matched_output_gain_db(first).unwrap();
+        assert!((second - first).abs() < 0.01, "{first} then {second}");
+    }
+}
@@ -0,0 +1,103 @@
+use nih_plug::{prelude::Enum, util};
+
+use crate::smoothing::one_pole_coefficient;
+
+/// How long the input has to stay above 0 dBFS before it counts as hot, in seconds.
+const HOT_TIME: f32 = 0.5;
+/// How long the input has to stay below `COOL_LEVEL` (about -6 dBFS) before it stops counting as hot, in seconds.
+/// Much longer than `HOT_TIME`, so the pad doesn't flap on and off between choruses.
+const COOL_TIME: f32 = 5.0;
+const COOL_LEVEL: f32 = 0.5;
+/// How long the peak reading takes to fall, in seconds. Bridges the gaps between the peaks of a waveform, so a hot
+/// signal stays hot even while it's crossing zero.
+const PEAK_DECAY: f32 = 0.05;
+/// How long the pad takes to fade in and out, in seconds.
+const PAD_FADE: f32 = 0.05;
+
+/// An automatic pad in front of the compressor, for inputs that come in hotter than 0 dBFS. That doesn't clip in a
+/// floating point host, so it's easy to end up there with a hot plugin earlier in the chain. The pad gets made up for
+/// after the compressor, so the overall level stays the same, while the detector and the meters see the padded signal.
+#[derive(Clone, Copy, PartialEq, Enum)]
+pub enum InputPad {
+    Off,
+    #[name = "Auto -6 dB"]
+    Auto6,
+    #[name = "Auto -12 dB"]
+    Auto12,
+}
+
+impl InputPad {
+    fn gain(&self) -> f32 {
+        match self {
+            InputPad::Off => 1.0,
+            InputPad::Auto6 => util::db_to_gain(-6.0),
+            InputPad::Auto12 => util::db_to_gain(-12.0),
+        }
+    }
+}
+
+/// Notices sustained input above 0 dBFS, and fades the pad in and out accordingly.
+pub struct PadDetector {
+    peak_decay: f32,
+    fade: f32,
+    hot_samples: usize,
+    cool_samples: usize,
+    peak: f32,
+    /// How many samples in a row `peak` has been above 0 dBFS, or below `COOL_LEVEL` while hot.
+    counter: usize,
+    hot: bool,
+    gain: f32,
+}
+
+impl PadDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            peak_decay: 1.0 - one_pole_coefficient(PEAK_DECAY, sample_rate),
+            fade: one_pole_coefficient(PAD_FADE, sample_rate),
+            hot_samples: (HOT_TIME * sample_rate) as usize,
+            cool_samples: (COOL_TIME * sample_rate) as usize,
+            peak: 0.0,
+            counter: 0,
+            hot: false,
+            gain: 1.0,
+        }
+    }
+
+    /// Takes the raw input and returns the pad's gain for this sample. The detection runs even when the pad is off,
+    /// so the editor can still point out a hot input.
+    pub fn process(&mut self, frame: [f32; 2], pad: InputPad) -> f32 {
+        let abs = frame[0].abs().max(frame[1].abs());
+        self.peak = abs.max(self.peak * self.peak_decay);
+
+        let (counting, limit) = if self.hot {
+            (self.peak < COOL_LEVEL, self.cool_samples)
+        } else {
+            (self.peak >= 1.0, self.hot_samples)
+        };
+        if counting {
+            self.counter += 1;
+            if self.counter >= limit {
+                self.hot = !self.hot;
+                self.counter = 0;
+            }
+        } else {
+            self.counter = 0;
+        }
+
+        let target = if self.hot { pad.gain() } else { 1.0 };
+        self.gain += (target - self.gain) * self.fade;
+        self.gain
+    }
+
+    /// Whether the input has been above 0 dBFS for a while.
+    pub fn is_hot(&self) -> bool {
+        self.hot
+    }
+
+    pub fn reset(&mut self) {
+        self.peak = 0.0;
+        self.counter = 0;
+        self.hot = false;
+        self.gain = 1.0;
+    }
+}
@@ -1,76 +1,410 @@
+mod ballistics;
+mod delay;
+mod delta_capture;
+mod detector;
+mod editor;
+mod event_log;
+mod gain_computer;
+mod gain_path;
+mod gain_staging;
+mod headroom;
+mod link;
+mod loudness;
+mod meter_bus;
+mod midi_learn;
+mod mode_switch;
+mod modulation;
+mod note_gate;
+pub mod offline;
+mod osc;
+mod param_writer;
 mod params;
+mod presets;
+mod safe_mode;
+mod scenes;
+mod session_stats;
+mod sidechain;
+mod smoothing;
+mod state;
+mod switch_fade;
+mod sysex;
+mod test_tone;
+mod tilt;
+mod user_defaults;
 
+use ballistics::BallisticsCoefficients;
 use core::f32;
+use delay::DelayLine;
+use delta_capture::DeltaCapture;
+use detector::{Detector, DetectorCoefficients, Readings, RMS_WINDOW};
+use editor::EditorData;
+use event_log::{EventKind, EventLog, ParamWatcher};
 use fundsp::hacker::*;
+use gain_computer::{Conversions, Db, GainComputer, KneeShape};
+use gain_path::GainPath;
+use gain_staging::GainStaging;
+use headroom::PadDetector;
+use link::{LinkMember, LinkMode};
+use loudness::LoudnessMeter;
+use meter_bus::{HistoryWriter, InputMeter, MeterBus, MeterPublisher, SilenceDetector};
+use midi_learn::MidiLearn;
+use mode_switch::ModeSwitch;
+use modulation::{EnvelopeFollower, Lfo, Modulation};
 use nih_plug::prelude::*;
+use nih_plug::wrapper::state::PluginState;
+use note_gate::NoteGate;
+use osc::{OscReceiver, OscSender};
+use param_writer::{ParamBatch, ParamWriter};
 use params::GainParams;
-use std::sync::Arc;
+use safe_mode::{SafeMode, SafeModeReason};
+use session_stats::StatsCollector;
+use smoothing::one_pole_coefficient;
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{atomic::Ordering, Arc},
+};
+use switch_fade::{StartupRamp, SwitchFade};
+use sysex::StateSysEx;
+use test_tone::{TestTone, ToneGenerator};
+use tilt::TiltEq;
 use typenum::{UInt, UTerm};
-use util::{db_to_gain_fast, gain_to_db_fast};
 
-// type Compressor = Binop<FrameMul<UInt<UTerm, B1>>, Pipe<Monitor, Monitor>, Pipe<Var, Follow<f64>>>;
+// type Compressor = Binop<FrameMul<UInt<UTerm, B1>>, Pass<f64>, Var>;
 // graph: An<Stack<Compressor, Compressor>>
 struct Gain {
     // TODO:
     // use audionode?
-    rms: Shared,
-    peak: Shared,
+    // TODO:
+    // wasm32 build exposing a plain `process(&mut [f32])` for a WebAudio worklet demo. needs the DSP (detector,
+    // gain computer, gain smoothing) pulled out of `Gain` into something that doesn't depend on nih-plug or threads first.
+    detector: Detector,
+    /// Delays the audio (but not the detector) for the RMS latency compensation, see `GainParams::rms_compensation`.
+    compensation_delay: DelayLine,
+    /// Delays the sidechain when it arrives early, see `GainParams::sidechain_offset`.
+    sidechain_delay: DelayLine,
+    /// The latency currently reported to the host, in samples.
+    latency: u32,
+    /// Drives the gain in the graph.
     amplitude: Shared,
+    /// Where all the meter readings get published for the editor and OSC.
+    meter_bus: Arc<MeterBus>,
+    input_meter: InputMeter,
+    meter_publisher: MeterPublisher,
+    silence_detector: SilenceDetector,
+    pad_detector: PadDetector,
+    /// The dynamic tone stage, see `GainParams::tone_depth`.
+    tilt_eq: TiltEq,
+    /// Gathers what goes into `GainParams::session_stats`.
+    stats_collector: StatsCollector,
+    /// This instance's place in its link group, see `GainParams::link_group`.
+    link: LinkMember,
+    gr_history: HistoryWriter,
+    /// Samples left until the next write to `GainParams::gr_output`.
+    gr_output_countdown: usize,
+    /// Samples left until `GainParams::latency_output` gets checked again.
+    latency_output_countdown: usize,
+    /// Smooths the gain computer's parameters, and turns the detector's level into the gain the graph applies.
+    gain_path: GainPath,
+    /// Where the gain was headed at the end of the last block, before linking. That's what gets shared with the link
+    /// group for `LinkMode::GainReduction`.
+    last_target_gain: f32,
+    /// Crossfades the gain between the old and new modes after a switch.
+    mode_switch: ModeSwitch<Modes>,
+    lfos: [Lfo; 2],
+    envelope_follower: EnvelopeFollower,
+    /// The (modulated) dry/wet mix the last block ramped to.
+    previous_dry_wet: f32,
+    /// Streams the meters over OSC. Only created once the plugin gets initialized.
+    osc_sender: Option<OscSender>,
+    /// Turns incoming OSC messages into parameter changes. Also only created once the plugin gets initialized.
+    osc_receiver: Option<OscReceiver>,
+    event_log: Arc<EventLog>,
+    /// Set when processing panics or goes non-finite, see `SafeMode`.
+    safe_mode: Arc<SafeMode>,
+    /// The audio thread's own copy of whether the safe mode is on, to notice the editor clearing it.
+    safe_mode_engaged: bool,
+    /// Feeds parameter changes into the `event_log`. Also only created once the plugin gets initialized.
+    param_watcher: Option<ParamWatcher>,
+    /// Used by everything that isn't the editor to change parameters.
+    param_writer: ParamWriter,
+    /// Handed over in `editor()`, for the OSC thread to get its changes applied on the GUI thread.
+    async_executor: Option<AsyncExecutor<Self>>,
+    midi_learn: Arc<MidiLearn>,
+    note_gate: NoteGate,
+    /// Fades the output out and back in around preset switches from MIDI program changes.
+    program_change_fade: SwitchFade,
+    /// Fades the processed signal out to the dry signal and back in around scene switches. Unlike with program changes
+    /// the audio keeps going, a scene switch is something that happens live.
+    scene_fade: SwitchFade,
+    startup_ramp: StartupRamp,
+    /// The scene selected during the last block, to notice when it changes. `None` until the first block, so
+    /// restoring a project doesn't reapply the scene on top of the settings that were saved with it.
+    last_scene: Option<i32>,
+    /// The parameters that go into SysEx dumps, with their keys. Collected up front, not on the audio thread.
+    sysex_params: Vec<(u32, ParamPtr)>,
+    /// The internal block size from `GainParams::block_size`, validated in `initialize()`.
+    block_size: usize,
+    /// Whether `prepare()` has run yet, see `process()`.
+    prepared: bool,
+    sample_rate: f32,
+    process_mode: ProcessMode,
+    /// The number of main input channels in the current audio IO layout.
+    input_channels: usize,
+    /// The number of main output channels in the current audio IO layout.
+    output_channels: usize,
+    /// Measures the output during offline renders, see `publish_loudness_report()`.
+    loudness_meter: LoudnessMeter,
+    gain_staging: Arc<GainStaging>,
+    /// Measures the input while the gain staging assistant is analyzing.
+    input_analyzer: LoudnessMeter,
+    /// Measures the output at the same time, for matching the output loudness to the input.
+    output_analyzer: LoudnessMeter,
+    /// Whether the gain staging assistant was analyzing during the last block, to notice when it starts again.
+    was_analyzing: bool,
+    delta_capture: Arc<DeltaCapture>,
+    /// The editor's test tone settings, see `ToneGenerator`.
+    test_tone: Arc<TestTone>,
+    tone_generator: ToneGenerator,
+    // TODO:
+    // once the DSP core is extracted, make it no_std and alloc-free after construction (fixed-size buffers, no
+    // boxed graph). `assert_process_allocs` already catches allocations here in debug builds, but only inside a host.
     graph: Box<dyn AudioUnit>,
     input_buffer: BufferArray<UInt<UInt<UTerm, typenum::B1>, typenum::B0>>,
     output_buffer: BufferArray<UInt<UInt<UTerm, typenum::B1>, typenum::B0>>,
     params: Arc<GainParams>,
 }
 
-#[derive(PartialEq, nih_plug::prelude::Enum)]
+// TODO:
+// once there's a multiband mode: an analysis task that FFTs the input in the background and suggests crossover
+// points at the spectral valleys, which the editor can then apply through the param setter on confirmation
+/// Work the audio thread hands off to the background thread, or to the GUI thread for anything that writes parameters
+/// (see `ParamWriter::flush()`).
+pub enum Task {
+    /// An incoming MIDI CC, used for MIDI learn and mapped parameters. `value` is normalized to `0.0..=1.0`.
+    MidiCc { cc: u8, value: f32 },
+    /// Load one of the factory presets, by index.
+    LoadPreset(usize),
+    /// Restore the parameter values from a SysEx dump.
+    RestoreSysEx(StateSysEx),
+    /// Switch to one of the stored scenes, by index. See `GainParams::scene`.
+    LoadScene(usize),
+    /// Write the current gain reduction (in dB) to `GainParams::gr_output`.
+    WriteGrOutput(f32),
+    /// Write the current latency (in milliseconds) to `GainParams::latency_output`.
+    WriteLatencyOutput(f32),
+    /// Apply whatever's been queued up in the `ParamWriter` from other threads, see `ParamWriter::flush()`.
+    FlushParamChanges,
+}
+
+#[derive(Clone, Copy, PartialEq, nih_plug::prelude::Enum)]
 pub enum LevelDetection {
     Rms,
     Peak,
+    /// The amplitude of the analytic signal, see `Detector`.
+    #[name = "Hilbert"]
+    Hilbert,
 }
 
-fn calculate_gain_reduction(gain: f32, threshold: f32, ratio: f32, knee_width: f32) -> f32 {
-    // first, we need to convert our gain to decibels.
-    let input_db = gain_to_db_fast(gain);
-
-    // GAIN COMPUTER
-    let reduced_db = {
-        let difference = input_db - threshold;
-        if 2.0 * (difference).abs() <= knee_width {
-            // if we're within the knee range, use some special calculations!
-            let gain_reduction = (difference + (knee_width / 2.0)).powi(2) / (2.0 * knee_width);
-            input_db + (1.0 / ratio - 1.0) * gain_reduction
-        } else if 2.0 * (difference) > knee_width {
-            // above the knee, apply compression
-            threshold + (difference / ratio)
-        } else {
-            // if we're below the knee/threshold
-            input_db
+/// How often `GainParams::gr_output` gets written while playing, in Hz. Plenty for volume automation, without flooding
+/// the host with gestures.
+const GR_OUTPUT_RATE: f32 = 30.0;
+/// How often `GainParams::latency_output` gets checked against the actual latency, in seconds.
+const LATENCY_OUTPUT_INTERVAL: f32 = 0.25;
+
+/// The graph's buffers (and the other per-block arrays in `process()`) are `MAX_BUFFER_SIZE` long, so blocks can't be
+/// any longer than that. Anything from a single sample up is fine.
+fn validate_block_size(requested: usize) -> usize {
+    requested.clamp(1, MAX_BUFFER_SIZE)
+}
+
+/// Every mode that changes the structure of the signal path, see `ModeSwitch`.
+#[derive(Clone, Copy, PartialEq)]
+struct Modes {
+    level_detection: LevelDetection,
+    gain_computer_mode: GainComputerMode,
+    knee_shape: KneeShape,
+}
+
+/// Whether every sample in the buffer is a regular number, no NaN or infinity.
+fn is_finite(buffer: &Buffer) -> bool {
+    buffer
+        .as_slice_immutable()
+        .iter()
+        .all(|channel| channel.iter().all(|sample| sample.is_finite()))
+}
+
+/// How far the audio gets delayed when compensating for the RMS detector's lag: half of its averaging window.
+fn rms_compensation_samples(sample_rate: f32) -> u32 {
+    (RMS_WINDOW / 2.0 * sample_rate).round() as u32
+}
+
+/// Converts a sidechain offset (see `GainParams::sidechain_offset`) to samples.
+fn sidechain_offset_samples(offset: f32, sample_rate: f32) -> u32 {
+    (offset.abs() * sample_rate).round() as u32
+}
+
+/// Trades processing cost for quality, per instance.
+#[derive(PartialEq, nih_plug::prelude::Enum)]
+pub enum Quality {
+    /// For tracking sessions with lots of instances: the gain only changes once per block instead of on every
+    /// sample, and the gain computer always uses the fast dB conversions.
+    Eco,
+    Normal,
+    /// For mixdowns: the gain computer always uses the exact dB conversions.
+    #[name = "HQ"]
+    High,
+}
+
+/// What the gain computer does above the threshold.
+#[derive(Clone, Copy, PartialEq, nih_plug::prelude::Enum)]
+pub enum GainComputerMode {
+    /// Turns the level down above the threshold, by the ratio.
+    Compress,
+    /// Turns the level *up* above the threshold instead, with the ratio flipped around (4:1 becomes 1:4).
+    /// Restores some dynamics to material that's already been squashed. Limited by `GainParams::max_boost`.
+    #[name = "Upward Expand"]
+    UpwardExpand,
+}
+
+/// The classic hardware-style ratio switch positions, for `GainParams::stepped_ratio`.
+#[derive(PartialEq, nih_plug::prelude::Enum)]
+pub enum RatioStep {
+    #[name = "1.5:1"]
+    OneAndAHalf,
+    #[name = "2:1"]
+    Two,
+    #[name = "3:1"]
+    Three,
+    #[name = "4:1"]
+    Four,
+    #[name = "6:1"]
+    Six,
+    #[name = "8:1"]
+    Eight,
+    #[name = "10:1"]
+    Ten,
+    #[name = "20:1"]
+    Twenty,
+    #[name = "∞:1"]
+    Infinity,
+}
+
+impl RatioStep {
+    pub fn ratio(&self) -> f32 {
+        match self {
+            RatioStep::OneAndAHalf => 1.5,
+            RatioStep::Two => 2.0,
+            RatioStep::Three => 3.0,
+            RatioStep::Four => 4.0,
+            RatioStep::Six => 6.0,
+            RatioStep::Eight => 8.0,
+            RatioStep::Ten => 10.0,
+            RatioStep::Twenty => 20.0,
+            // not actually infinite, the smoother can't interpolate from infinity. the continuous ratio's maximum is
+            // already a limiter for all intents and purposes
+            RatioStep::Infinity => 100.0,
         }
-    };
-    // to be totally honest, i'm not sure why this has to be done.
-    let final_db = reduced_db - input_db;
-    // convert back to linear space as a factor to multiply the input
-    db_to_gain_fast(final_db)
+    }
+}
+
+/// Which decibel conversions the gain computer uses.
+///
+/// The fast conversions (`db_to_gain_fast`/`gain_to_db_fast`) go through `exp`/`ln` instead of `powf`/`log10`.
+/// Within the range the compressor works in, the two differ by a few ULPs (a relative error below `2e-6`, way below
+/// anything audible, see the tests in `gain_computer`). The only real difference is at the very bottom: the exact
+/// conversions snap everything at or below -100 dB to silence, while the fast ones keep going.
+#[derive(PartialEq, nih_plug::prelude::Enum)]
+pub enum DbConversion {
+    /// Follows the `Quality` setting. In normal quality, that's fast while playing back and exact while rendering offline.
+    Auto,
+    Fast,
+    Exact,
+}
+
+impl DbConversion {
+    fn is_exact(&self, quality: &Quality, process_mode: ProcessMode) -> bool {
+        match self {
+            DbConversion::Auto => match quality {
+                Quality::Eco => false,
+                Quality::Normal => process_mode == ProcessMode::Offline,
+                Quality::High => true,
+            },
+            DbConversion::Fast => false,
+            DbConversion::Exact => true,
+        }
+    }
 }
 
 impl Default for Gain {
     fn default() -> Self {
-        let rms = shared(0.0);
-        let peak = shared(0.0);
         let amplitude = shared(1.0);
 
-        #[allow(clippy::precedence)]
-        let compressor = (monitor(&peak, Meter::Peak(0.1)) >> monitor(&rms, Meter::Rms(0.1)))
-            * (var(&amplitude) >> follow(0.01));
+        // level detection happens in `Detector` and attack/release in `GainEnvelope`, the graph only applies the gain
+        // TODO:
+        // oversampling doesn't exist yet. once it does, add a quality selector for its filters
+        // (minimum-phase IIR for low latency vs linear-phase FIR for quality)
+        // toggling it will change the latency, so it'll need a crossfade (or a short mute) around the switch and the
+        // dry path delayed to match. `SwitchFade` already does the fade-out/fade-in part for program changes.
+        let compressor = pass() * var(&amplitude);
 
         let graph = compressor.clone() | compressor;
+        let params = Arc::new(GainParams::new());
 
         Self {
-            rms,
-            peak,
+            detector: Detector::default(),
+            compensation_delay: DelayLine::default(),
+            sidechain_delay: DelayLine::default(),
+            latency: 0,
             amplitude,
+            meter_bus: Arc::new(MeterBus::default()),
+            input_meter: InputMeter::new(44100.0),
+            meter_publisher: MeterPublisher::new(44100.0),
+            silence_detector: SilenceDetector::new(44100.0),
+            pad_detector: PadDetector::new(44100.0),
+            tilt_eq: TiltEq::new(44100.0),
+            stats_collector: StatsCollector::default(),
+            link: LinkMember::default(),
+            gr_history: HistoryWriter::new(44100.0),
+            gr_output_countdown: 0,
+            latency_output_countdown: 0,
+            gain_path: GainPath::default(),
+            last_target_gain: 1.0,
+            mode_switch: ModeSwitch::default(),
+            lfos: Default::default(),
+            envelope_follower: EnvelopeFollower::default(),
+            previous_dry_wet: 1.0,
+            osc_sender: None,
+            osc_receiver: None,
+            event_log: Arc::new(EventLog::default()),
+            safe_mode: Arc::new(SafeMode::default()),
+            safe_mode_engaged: false,
+            param_watcher: None,
+            param_writer: ParamWriter::new(params.editor_state.clone()),
+            async_executor: None,
+            midi_learn: Arc::new(MidiLearn::default()),
+            note_gate: NoteGate::default(),
+            program_change_fade: SwitchFade::default(),
+            scene_fade: SwitchFade::default(),
+            startup_ramp: StartupRamp::default(),
+            last_scene: None,
+            sysex_params: sysex::dump_params(&params),
+            block_size: params::DEFAULT_BLOCK_SIZE,
+            prepared: false,
+            sample_rate: 44100.0,
+            process_mode: ProcessMode::Realtime,
+            input_channels: 2,
+            output_channels: 2,
+            loudness_meter: LoudnessMeter::new(44100.0),
+            gain_staging: Arc::new(GainStaging::default()),
+            input_analyzer: LoudnessMeter::new(44100.0),
+            output_analyzer: LoudnessMeter::new(44100.0),
+            was_analyzing: false,
+            delta_capture: Arc::new(DeltaCapture::default()),
+            test_tone: Arc::new(TestTone::default()),
+            tone_generator: ToneGenerator::default(),
             graph: Box::new(graph),
-            params: Arc::new(GainParams::new()),
+            params,
 
             input_buffer: BufferArray::<U2>::new(),
             output_buffer: BufferArray::<U2>::new(),
@@ -78,6 +412,633 @@ impl Default for Gain {
     }
 }
 
+impl Gain {
+    /// (Re)builds everything that depends on the sample rate or the channel layout. This allocates, so it normally
+    /// only runs from `initialize()`. `process()` falls back on it when the host never told us what it's doing.
+    fn prepare(&mut self, sample_rate: f32, input_channels: usize, output_channels: usize) {
+        self.sample_rate = sample_rate;
+        self.input_channels = input_channels;
+        self.output_channels = output_channels;
+        self.loudness_meter = LoudnessMeter::new(self.sample_rate);
+        self.input_analyzer = LoudnessMeter::new(self.sample_rate);
+        self.output_analyzer = LoudnessMeter::new(self.sample_rate);
+        self.input_meter = InputMeter::new(self.sample_rate);
+        self.meter_publisher = MeterPublisher::new(self.sample_rate);
+        self.silence_detector = SilenceDetector::new(self.sample_rate);
+        self.pad_detector = PadDetector::new(self.sample_rate);
+        self.tilt_eq = TiltEq::new(self.sample_rate);
+        self.gr_history = HistoryWriter::new(self.sample_rate);
+        self.meter_bus.reset();
+        self.delta_capture.allocate(self.sample_rate);
+        // the audio also gets delayed when the sidechain arrives late, so there has to be room for both
+        let max_sidechain_offset =
+            sidechain_offset_samples(params::MAX_SIDECHAIN_OFFSET, self.sample_rate);
+        self.compensation_delay = DelayLine::new(
+            (rms_compensation_samples(self.sample_rate) + max_sidechain_offset) as usize,
+        );
+        self.sidechain_delay = DelayLine::new(max_sidechain_offset as usize);
+        self.prepared = true;
+    }
+
+    /// Publishes the loudness of the last offline render (if there was one) to the log and to `GainParams::loudness_report`,
+    /// where the editor picks it up, along with the most gain reduction during the render. Hosts don't tell us when a
+    /// render is done, so this gets called whenever the plugin gets reinitialized or deactivated, which is what hosts do
+    /// once they're done rendering.
+    fn publish_loudness_report(&mut self) {
+        if !self.loudness_meter.has_measurement() {
+            return;
+        }
+
+        if let Some(report) = self.loudness_meter.report() {
+            nih_log!(
+                "Offline render: {:.1} LUFS integrated, {:.1} dBTP true peak",
+                report.integrated_lufs,
+                report.true_peak_dbtp
+            );
+            *self.params.loudness_report.write().unwrap() = Some(report);
+        }
+        // `initialize()` resets the meters, so this covers exactly the render
+        let max_gr_db = -util::gain_to_db(self.meter_bus.gr_max_hold.value());
+        nih_log!(
+            "Offline render: {max_gr_db:.1} dB of gain reduction at most, at {}",
+            meter_bus::format_position(self.meter_bus.gr_max_hold_time.value())
+                .unwrap_or_else(|| "an unknown position".to_string())
+        );
+        self.loudness_meter.reset();
+    }
+
+    fn handle_event(
+        &mut self,
+        event: PluginNoteEvent<Self>,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        match event {
+            NoteEvent::NoteOn { note, .. } => self.note_gate.note_on(note),
+            NoteEvent::NoteOff { note, .. } => self.note_gate.note_off(note),
+            NoteEvent::MidiCC { cc, value, .. } => context.execute_gui(Task::MidiCc { cc, value }),
+            NoteEvent::MidiSysEx { timing, message } => match message {
+                StateSysEx::DumpRequest => context.send_event(NoteEvent::MidiSysEx {
+                    timing,
+                    message: StateSysEx::dump(&self.sysex_params),
+                }),
+                StateSysEx::Dump { .. } => context.execute_gui(Task::RestoreSysEx(message)),
+            },
+            NoteEvent::MidiProgramChange { program, .. } => {
+                if (program as usize) < presets::FACTORY_PRESETS.len() {
+                    self.program_change_fade.start(program);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Everything `process()` does, minus the safe mode around it.
+    fn process_dsp(
+        &mut self,
+        buffer: &mut Buffer,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // TODO:
+        // use BigBlockAdapter
+
+        // some hosts change the sample rate or the layout without calling `initialize()` (or never call it at all).
+        // the block size doesn't matter here, `iter_blocks()` never hands out more than the buffers can hold
+        let sample_rate = context.transport().sample_rate;
+        let channels = buffer.channels();
+        if channels == 0 {
+            return ProcessStatus::Normal;
+        }
+        if !self.prepared || sample_rate != self.sample_rate || channels != self.output_channels {
+            nih_log!(
+                "Got {channels} channels at {sample_rate} Hz without being initialized for that, setting up again"
+            );
+            // the input layout can't be seen from here. in-place processing means there can't be more inputs than
+            // outputs though, so that's what gets assumed
+            let input_channels = if self.prepared {
+                self.input_channels.min(channels)
+            } else {
+                channels
+            };
+            util::permit_alloc(|| {
+                self.prepare(sample_rate, input_channels, channels);
+                self.event_log.record(
+                    EventKind::Layout,
+                    format!("Set up from process(): {input_channels} in, {channels} out, {sample_rate} Hz"),
+                );
+            });
+            self.reset();
+        }
+
+        // events get handled as we write the output, so note-gate changes land on the right sample
+        let mut next_event = context.next_event();
+
+        let scene = self.params.scene.value();
+        if self
+            .last_scene
+            .is_some_and(|last_scene| last_scene != scene)
+            && scene > 0
+        {
+            self.scene_fade.start(scene as u8 - 1);
+        }
+        self.last_scene = Some(scene);
+
+        // only there if the host actually connected something, otherwise detection falls back to the main input
+        let sidechain = aux.inputs.first().map(|buffer| buffer.as_slice_immutable());
+        // for the GR max hold's timestamp
+        let position_seconds = context.transport().pos_seconds();
+        let use_sidechain = self.params.sidechain.value();
+        let sidechain_channels = self.params.sidechain_channels.value();
+        let detector_channels = self.params.detector_channels.value();
+
+        // an RMS detector lags behind the audio by about half its window. delaying the audio by that much lines the
+        // gain reduction back up with the energy it's reacting to, at the cost of some latency
+        let compensate = self.params.rms_compensation.value()
+            && self.params.meter_type.value() == LevelDetection::Rms;
+        let rms_latency = if compensate {
+            rms_compensation_samples(self.sample_rate)
+        } else {
+            0
+        };
+        // a key that arrives early gets delayed to line up with the audio. one that arrives late can't be sped up, so
+        // the audio gets delayed instead, which adds latency
+        let sidechain_offset = if use_sidechain && sidechain.is_some() {
+            self.params.sidechain_offset.value()
+        } else {
+            0.0
+        };
+        let offset_samples = sidechain_offset_samples(sidechain_offset, self.sample_rate);
+        let (sidechain_delay, audio_delay) = if sidechain_offset >= 0.0 {
+            (offset_samples as usize, 0)
+        } else {
+            (0, offset_samples)
+        };
+        let latency = rms_latency + audio_delay;
+        if latency != self.latency {
+            self.latency = latency;
+            context.set_latency_samples(latency);
+        }
+        self.meter_bus
+            .latency_samples
+            .store(latency, Ordering::Relaxed);
+        // checked every so often instead of only when the latency changes, since the write can't go through before
+        // the editor has been opened
+        self.latency_output_countdown = self
+            .latency_output_countdown
+            .saturating_sub(buffer.samples());
+        if self.latency_output_countdown == 0 {
+            self.latency_output_countdown = (self.sample_rate * LATENCY_OUTPUT_INTERVAL) as usize;
+            let latency_ms = latency as f32 / self.sample_rate * 1000.0;
+            if (self.params.latency_output.value() - latency_ms).abs() > 0.005 {
+                context.execute_gui(Task::WriteLatencyOutput(latency_ms));
+            }
+        }
+        let compensation = (latency as usize).min(self.compensation_delay.max_delay());
+
+        // offset is the sample offset from beginning of buffer
+        for (offset, mut block) in buffer.iter_blocks(self.block_size) {
+            let analyzing = self.gain_staging.analyzing.load(Ordering::Relaxed);
+            if analyzing && !self.was_analyzing {
+                self.input_analyzer.reset();
+                self.output_analyzer.reset();
+                self.gain_staging.measured_lufs.set(f32::NAN);
+                self.gain_staging.output_lufs.set(f32::NAN);
+            }
+            self.was_analyzing = analyzing;
+
+            let input_pad = self.params.input_pad.value();
+            // the pad's gain for every sample, so the output can undo exactly what the input did
+            let mut pad_gains = [1.0; MAX_BUFFER_SIZE];
+            // the detector's readings for every sample, the gain gets worked out per sample from these further down
+            let mut readings = [Readings::default(); MAX_BUFFER_SIZE];
+
+            let detector_coefficients = DetectorCoefficients::new(
+                self.params.detector_rise_time.value(),
+                self.params.detector_fall_time.value(),
+                self.params.peak_decay.value(),
+                self.sample_rate,
+            );
+
+            // write into input buffer
+            for (sample_index, mut channel_samples) in block.iter_samples().enumerate() {
+                let input_gain = self.params.input_gain.smoothed.next();
+                let sidechain_gain = self.params.sidechain_gain.smoothed.next();
+                let mut frame = [0.0; 2];
+                for (channel_index, frame_sample) in frame.iter_mut().enumerate() {
+                    // mono inputs get treated as dual mono, so detection and the graph always see two channels
+                    *frame_sample = channel_samples
+                        .get_mut(channel_index.min(self.input_channels.saturating_sub(1)))
+                        .map_or(0.0, |sample| *sample);
+                }
+                // everything after this sees the test tone as if it was the input
+                let frame = self
+                    .tone_generator
+                    .process(frame, &self.test_tone, self.sample_rate);
+                let pad = self.pad_detector.process(frame, input_pad);
+                pad_gains[sample_index] = pad;
+                // only the audio path gets delayed, the detector still sees the input as it comes in
+                let delayed = self.compensation_delay.process(frame, compensation);
+                for (channel_index, sample) in delayed.into_iter().enumerate() {
+                    self.input_buffer.buffer_mut().set_f32(
+                        channel_index,
+                        sample_index,
+                        sample * input_gain * pad,
+                    );
+                }
+                let sidechain_frame = sidechain
+                    .filter(|_| use_sidechain)
+                    .and_then(|channels| sidechain::frame(channels, offset + sample_index))
+                    .map(|frame| self.sidechain_delay.process(frame, sidechain_delay));
+                let detector_frame = match sidechain_frame {
+                    Some(sidechain_frame) => {
+                        let polarity = if self.params.sidechain_invert.value() {
+                            -1.0
+                        } else {
+                            1.0
+                        };
+                        sidechain_channels
+                            .route(sidechain_frame)
+                            .map(|sample| sample * sidechain_gain * polarity)
+                    }
+                    None => frame.map(|sample| sample * input_gain * pad),
+                };
+                let detector_weights = [
+                    self.params.detector_weight_a.smoothed.next(),
+                    self.params.detector_weight_b.smoothed.next(),
+                ];
+                let detector_frame = detector_channels.weight(detector_frame, detector_weights);
+                readings[sample_index] = self
+                    .detector
+                    .process(detector_frame, &detector_coefficients);
+                self.silence_detector.process(frame);
+                if !self.silence_detector.is_silent() {
+                    self.input_meter
+                        .process(frame.map(|sample| sample * input_gain * pad));
+                }
+                // the assistant measures the input *before* the input gain, since that's what it's suggesting a value for
+                if analyzing {
+                    self.input_analyzer
+                        .process(gain_staging::measured_frame(frame, self.input_channels));
+                }
+            }
+            if analyzing {
+                if let Some(report) = self.input_analyzer.report() {
+                    self.gain_staging.measured_lufs.set(report.integrated_lufs);
+                }
+            }
+
+            // while it's silent the meters hold their last readings, and the editor dims them
+            let silent = self.silence_detector.is_silent();
+            self.meter_bus.silent.store(silent, Ordering::Relaxed);
+            self.meter_bus
+                .hot_input
+                .store(self.pad_detector.is_hot(), Ordering::Relaxed);
+
+            let modes = Modes {
+                level_detection: self.params.meter_type.value(),
+                gain_computer_mode: self.params.mode.value(),
+                knee_shape: self.params.knee_shape.value(),
+            };
+            let mode_crossfade = self
+                .mode_switch
+                .next(modes, block.samples(), self.sample_rate);
+            let peak_blend = self.params.peak_blend.value();
+            // the level at the end of the block, for everything that only looks at it once per block
+            let level = readings[block.samples() - 1].level(modes.level_detection, peak_blend);
+            self.link.set_group(self.params.link_group.value() as usize);
+            let link_mode = self.params.link_mode.value();
+            // the loudest level in the group, for the shared detector
+            let linked_level = if link_mode == LinkMode::Detector {
+                self.link.exchange(link_mode, level, f32::max)
+            } else {
+                None
+            };
+            // the most gain reduction in the group, for linked gain reduction
+            let linked_gain = if link_mode == LinkMode::GainReduction {
+                self.link
+                    .exchange(link_mode, self.last_target_gain, f32::min)
+            } else {
+                None
+            };
+
+            // the wrapper splits the buffer wherever a parameter changes (`SAMPLE_ACCURATE_AUTOMATION`), so every
+            // block starts at the right sample and reading the parameters once per block doesn't miss anything. the
+            // gain computer's parameters then get smoothed (by their own smoothers, so the smoothing time can be
+            // changed) and turned into a gain one sample at a time, see `GainPath`.
+            let block_len = block.samples() as u32;
+            let smoothing =
+                one_pole_coefficient(self.params.param_smoothing_time.value(), self.sample_rate);
+            let ratio = if self.params.stepped_ratio.value() {
+                self.params.ratio_step.value().ratio()
+            } else {
+                self.params.ratio.value()
+            };
+            let curve_target = GainComputer {
+                threshold: Db(self.params.threshold.value()),
+                ratio,
+                knee_width: Db(self.params.knee_width.value()),
+                knee_shape: modes.knee_shape,
+            };
+
+            // the modulation goes on top of the smoothed values, the parameters themselves stay where they are
+            let mut modulation = Modulation::default();
+            for (lfo, (rate, shape, target, depth)) in self.lfos.iter_mut().zip([
+                (
+                    &self.params.lfo1_rate,
+                    &self.params.lfo1_shape,
+                    &self.params.lfo1_target,
+                    &self.params.lfo1_depth,
+                ),
+                (
+                    &self.params.lfo2_rate,
+                    &self.params.lfo2_shape,
+                    &self.params.lfo2_target,
+                    &self.params.lfo2_depth,
+                ),
+            ]) {
+                let value = lfo.next(
+                    rate.value(),
+                    shape.value(),
+                    block.samples(),
+                    self.sample_rate,
+                );
+                modulation.add(target.value(), value, depth.value());
+            }
+            let envelope = self.envelope_follower.next(
+                level,
+                self.params.env_release.value(),
+                block.samples(),
+                self.sample_rate,
+            );
+            modulation.add(
+                self.params.env_target.value(),
+                envelope,
+                self.params.env_depth.value(),
+            );
+            let dry_wet = modulation.dry_wet(self.params.dry_wet.smoothed.next_step(block_len));
+            let quality = self.params.quality.value();
+            let exact = self
+                .params
+                .db_conversion
+                .value()
+                .is_exact(&quality, self.process_mode);
+
+            let conversions = Conversions::new(exact);
+            let max_boost = Db(self.params.max_boost.value()).to_gain(&conversions);
+            let modulate = |curve: GainComputer| GainComputer {
+                threshold: Db(modulation.threshold(curve.threshold.0)),
+                ratio: modulation.ratio(curve.ratio),
+                ..curve
+            };
+            // where the gain is headed on the `index`th sample of the block, before linking
+            let target_gain_at = |curve: &GainComputer, index: usize| {
+                let target_gain_for = |modes: Modes| {
+                    let level = readings[index].level(modes.level_detection, peak_blend);
+                    let level = linked_level.map_or(level, |linked_level| level.max(linked_level));
+                    let curve = GainComputer {
+                        knee_shape: modes.knee_shape,
+                        ..*curve
+                    };
+                    gain_path::target_gain(
+                        &curve,
+                        modes.gain_computer_mode,
+                        level,
+                        max_boost,
+                        &conversions,
+                    )
+                };
+                let target_gain = target_gain_for(modes);
+                // right after a mode switch, both paths run and the old one fades out
+                match &mode_crossfade {
+                    Some(crossfade) => {
+                        let old_gain = target_gain_for(crossfade.from);
+                        target_gain + (old_gain - target_gain) * crossfade.amount(index)
+                    }
+                    None => target_gain,
+                }
+            };
+            let link_gain = |target_gain: f32| {
+                linked_gain.map_or(target_gain, |linked_gain| target_gain.min(linked_gain))
+            };
+
+            // in eco mode the envelope only runs once per block, so it needs coefficients for that rate instead
+            let ramp_len = block.samples();
+            let envelope_rate = if quality == Quality::Eco {
+                self.sample_rate / ramp_len as f32
+            } else {
+                self.sample_rate
+            };
+            let ballistics_coefficients = BallisticsCoefficients::new(
+                self.params.attack_time.smoothed.next_step(block_len),
+                self.params.release_time.smoothed.next_step(block_len),
+                self.params.slow_release_time.smoothed.next_step(block_len),
+                self.params.release_blend.smoothed.next_step(block_len),
+                self.params.transient_bleed.smoothed.next_step(block_len),
+                envelope_rate,
+            );
+
+            let lf_hold = self.params.lf_hold.value();
+            let freeze = self.params.freeze_gain_reduction.value();
+            if freeze || quality == Quality::Eco {
+                // the smoothers skip ahead by the whole block, and the gain only gets worked out for its last sample.
+                // while frozen that's only for the link group: the gain stays wherever it was when freeze got turned
+                // on, and the envelope picks up from there again once it's released
+                let curve = modulate(self.gain_path.curve(&curve_target, smoothing, ramp_len));
+                self.last_target_gain = target_gain_at(&curve, ramp_len - 1);
+                if !freeze {
+                    self.amplitude.set(self.gain_path.envelope_block(
+                        link_gain(self.last_target_gain),
+                        &ballistics_coefficients,
+                    ));
+                }
+                self.graph.process(
+                    ramp_len,
+                    &self.input_buffer.buffer_ref(),
+                    &mut self.output_buffer.buffer_mut(),
+                );
+            } else {
+                // the gain changes on every sample, so this means ticking the graph one sample at a time
+                for index in 0..ramp_len {
+                    let curve = modulate(self.gain_path.curve(&curve_target, smoothing, 1));
+                    self.last_target_gain = target_gain_at(&curve, index);
+
+                    let input_buffer = self.input_buffer.buffer_ref();
+                    let input = [input_buffer.at_f32(0, index), input_buffer.at_f32(1, index)];
+                    self.amplitude.set(self.gain_path.envelope(
+                        link_gain(self.last_target_gain),
+                        input,
+                        &ballistics_coefficients,
+                        lf_hold,
+                    ));
+                    let mut output = [0.0; 2];
+                    self.graph.tick(&input, &mut output);
+                    for (channel_index, sample) in output.into_iter().enumerate() {
+                        self.output_buffer
+                            .buffer_mut()
+                            .set_f32(channel_index, index, sample);
+                    }
+                }
+            }
+            self.meter_bus.update_max_hold(
+                self.amplitude.value(),
+                position_seconds.map(|seconds| seconds + offset as f64 / self.sample_rate as f64),
+            );
+            self.gr_history.process(
+                self.amplitude.value(),
+                block.samples(),
+                &self.meter_bus.gr_history,
+            );
+            // the write goes through the host like any other gesture, so it gets handed off to the GUI thread. only while
+            // playing, there's nothing to record otherwise
+            if self.params.gr_output_write.value() && context.transport().playing {
+                self.gr_output_countdown = self.gr_output_countdown.saturating_sub(block.samples());
+                if self.gr_output_countdown == 0 {
+                    self.gr_output_countdown = (self.sample_rate / GR_OUTPUT_RATE) as usize;
+                    context.execute_gui(Task::WriteGrOutput(util::gain_to_db(
+                        self.amplitude.value(),
+                    )));
+                }
+            }
+
+            let measure_loudness = self.process_mode == ProcessMode::Offline && !silent;
+            let note_gate_enabled = self.params.note_gate.value();
+            let gate_release = self.params.gate_release_time.value();
+            let force_open = self.params.force_open.value();
+            let force_closed = self.params.force_closed.value();
+            let startup_ramp_time = self.params.startup_ramp.value();
+            let tilt_db = TiltEq::tilt_db(self.amplitude.value(), self.params.tone_depth.value());
+
+            // dry minus wet, for the delta capture
+            let mut delta = [[0.0; 2]; MAX_BUFFER_SIZE];
+
+            // write from output buffer
+            let mut output_peak = [0.0f32; 2];
+            for (index, mut channel_samples) in block.iter_samples().enumerate() {
+                while let Some(event) = next_event {
+                    if event.timing() > (offset + index) as u32 {
+                        break;
+                    }
+                    self.handle_event(event, context);
+                    next_event = context.next_event();
+                }
+
+                // the gate keeps running (held open) even when it's not in use, so forcing it closed or turning the
+                // note gate on fades from wherever it currently is instead of jumping
+                let gate_override = if force_closed {
+                    Some(false)
+                } else if force_open || !note_gate_enabled {
+                    Some(true)
+                } else {
+                    None
+                };
+                let mut gate = self
+                    .note_gate
+                    .next(gate_override, gate_release, self.sample_rate);
+                let (program_change_gain, program) =
+                    self.program_change_fade.next(self.sample_rate);
+                if let Some(program) = program {
+                    context.execute_gui(Task::LoadPreset(program as usize));
+                }
+                gate *= program_change_gain;
+                // the scene's values land while only the dry signal can be heard, so stepped and on/off parameters
+                // switch without clicking
+                let (scene_gain, scene) = self.scene_fade.next(self.sample_rate);
+                if let Some(scene) = scene {
+                    context.execute_gui(Task::LoadScene(scene as usize));
+                }
+                // after the dry/wet mix, so it also works as a makeup gain for parallel compression
+                let output_gain = self.params.output_gain.smoothed.next();
+
+                // makes up for the input pad
+                let pad_compensation = pad_gains[index].recip();
+                // ramped across the block, so a modulated mix doesn't step at block boundaries
+                let t = (index + 1) as f32 / ramp_len as f32;
+                let mix = self.previous_dry_wet + (dry_wet - self.previous_dry_wet) * t;
+                let mix =
+                    mix * self.startup_ramp.next(startup_ramp_time, self.sample_rate) * scene_gain;
+                let wet = self.tilt_eq.process(
+                    [0, 1].map(|n| self.output_buffer.buffer_ref().at_f32(n, index)),
+                    tilt_db,
+                );
+                let mut frame = [0.0; 2];
+                let mut dry_frame = [0.0; 2];
+                for (n, frame_sample) in frame.iter_mut().enumerate() {
+                    let wet = wet[n] * pad_compensation;
+                    let dry = self.input_buffer.buffer_ref().at_f32(n, index) * pad_compensation;
+                    let mixed = dry + (wet - dry) * mix;
+                    delta[index][n] = dry - mixed;
+
+                    let sample_from_buf = mixed * gate * output_gain;
+                    if let Some(output_sample) = channel_samples.get_mut(n) {
+                        *output_sample = sample_from_buf;
+                        output_peak[n] = output_peak[n].max(sample_from_buf.abs());
+                        *frame_sample = sample_from_buf;
+                        dry_frame[n] = dry;
+                    }
+                }
+                if !silent {
+                    self.stats_collector.process_frame(dry_frame, frame);
+                }
+                if measure_loudness {
+                    self.loudness_meter.process(frame);
+                }
+                // as many channels as the input got measured with, so matching the loudness compares like with like
+                if analyzing {
+                    self.output_analyzer.process(gain_staging::measured_frame(
+                        frame,
+                        self.input_channels.min(self.output_channels),
+                    ));
+                }
+            }
+            if analyzing {
+                if let Some(report) = self.output_analyzer.report() {
+                    self.gain_staging.output_lufs.set(report.integrated_lufs);
+                }
+            }
+            self.previous_dry_wet = dry_wet;
+            if self
+                .meter_publisher
+                .process(block.samples(), output_peak, self.amplitude.value())
+            {
+                if !silent {
+                    self.input_meter.publish(&self.meter_bus);
+                }
+                self.meter_publisher.publish(&self.meter_bus, silent);
+            }
+            self.delta_capture.record(&delta[..block.samples()]);
+
+            if !silent {
+                self.stats_collector.process_block(
+                    block.samples(),
+                    self.amplitude.value(),
+                    self.sample_rate,
+                );
+            }
+            if self.stats_collector.should_flush() {
+                // never wait for the editor here, whatever doesn't make it in this time goes in with the next flush
+                let mut stats = self.params.session_stats.try_write().ok();
+                self.stats_collector.flush(stats.as_deref_mut());
+            }
+        }
+
+        // anything left over (events shouldn't be timed past the end of the buffer, but just in case)
+        while let Some(event) = next_event {
+            self.handle_event(event, context);
+            next_event = context.next_event();
+        }
+
+        // the compensation delay still has `latency` samples of audio in it when the input stops, tell the host to
+        // keep calling us until that's out so bounces don't cut it off. the release doesn't need a tail, it only
+        // shapes the gain of audio that's still coming in.
+        // the same goes for lookahead/oversampling once those exist, their delays get added to `latency`
+        if self.latency > 0 {
+            ProcessStatus::Tail(self.latency)
+        } else {
+            ProcessStatus::Normal
+        }
+    }
+}
+
 impl Plugin for Gain {
     const NAME: &'static str = "Gain";
     const VENDOR: &'static str = "Moist Plugins GmbH";
@@ -90,27 +1051,81 @@ impl Plugin for Gain {
 
     // The first audio IO layout is used as the default. The other layouts may be selected either
     // explicitly or automatically by the host or the user depending on the plugin API/backend.
+    // TODO:
+    // surround layouts + channel link groups (e.g. L/R linked, C independent, LFE bypassed, surrounds linked),
+    // each with their own detector and trim. blocked on the DSP not being hardwired to a stereo (U2) graph.
     const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
         AudioIOLayout {
             main_input_channels: NonZeroU32::new(2),
             main_output_channels: NonZeroU32::new(2),
 
-            aux_input_ports: &[],
+            aux_input_ports: &[new_nonzero_u32(2)],
             aux_output_ports: &[],
 
             // Individual ports and the layout as a whole can be named here. By default these names
             // are generated as needed. This layout will be called 'Stereo', while the other one is
             // given the name 'Mono' based no the number of input and output channels.
-            names: PortNames::const_default(),
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
         },
         AudioIOLayout {
             main_input_channels: NonZeroU32::new(1),
             main_output_channels: NonZeroU32::new(1),
+            aux_input_ports: &[new_nonzero_u32(1)],
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        // mono source on a stereo bus, the input just gets copied to both sides
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(2)],
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        // the sidechain doesn't have to match the main bus, see `sidechain::frame()` for how the channels get mixed.
+        // mono key on a stereo bus
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(1)],
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
             ..AudioIOLayout::const_default()
         },
+        // stereo key on a mono bus
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+            aux_input_ports: &[new_nonzero_u32(2)],
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        // TODO:
+        // surround layouts. the detector and graph are hardwired to two channels right now. once there are more,
+        // add an option to leave the LFE channel out of detection and/or pass it through without gain reduction
+        // TODO:
+        // 2-in/1-out for downmix chains. nih-plug processes in place, so the buffer only has as many channels as
+        // the output and the right input channel never makes it to `process()`.
     ];
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+    // notes are used for the note gate, CCs for MIDI learn, and program changes to switch presets
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    // only used to answer SysEx dump requests
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
     // Setting this to `true` will tell the wrapper to split the buffer up into smaller blocks
     // whenever there are inter-buffer parameter changes. This way no changes to the plugin are
     // required to support sample accurate automation and the wrapper handles all of the boring
@@ -118,76 +1133,271 @@ impl Plugin for Gain {
     // splits.
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
-    // If the plugin can send or receive SysEx messages, it can define a type to wrap around those
-    // messages here. The type implements the `SysExMessage` trait, which allows conversion to and
-    // from plain byte buffers.
-    type SysExMessage = ();
-    // More advanced plugins can use this to run expensive background tasks. See the field's
-    // documentation for more information. `()` means that the plugin does not have any background
-    // tasks.
-    type BackgroundTask = ();
+    // SysEx is used to dump and restore the plugin's settings, see `StateSysEx`
+    type SysExMessage = StateSysEx;
+    // Anything that shouldn't happen on the audio thread (like changing parameters in response to MIDI)
+    // gets sent off as a task and handled in `task_executor()`.
+    type BackgroundTask = Task;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
-    fn process(
-        &mut self,
-        buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
-    ) -> ProcessStatus {
-        // TODO:
-        // use BigBlockAdapter
+    fn filter_state(state: &mut PluginState) {
+        state::migrate(state);
+    }
 
-        // offset is the sample offset from beginning of buffer,
-        // we dont care about that here
-        for (_offset, mut block) in buffer.iter_blocks(MAX_BUFFER_SIZE) {
-            // write into input buffer
-            for (sample_index, mut channel_samples) in block.iter_samples().enumerate() {
-                for channel_index in 0..=1 {
-                    let sample = *channel_samples.get_mut(channel_index).unwrap();
-                    self.input_buffer
-                        .buffer_mut()
-                        .set_f32(channel_index, sample_index, sample);
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        self.async_executor = Some(async_executor);
+        editor::create(EditorData {
+            params: self.params.clone(),
+            meters: self.meter_bus.clone(),
+            param_writer: self.param_writer.clone(),
+            midi_learn: self.midi_learn.clone(),
+            gain_staging: self.gain_staging.clone(),
+            delta_capture: self.delta_capture.clone(),
+            test_tone: self.test_tone.clone(),
+            event_log: self.event_log.clone(),
+            safe_mode: self.safe_mode.clone(),
+        })
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        let param_writer = self.param_writer.clone();
+        let midi_learn = self.midi_learn.clone();
+        let param_ptrs: HashMap<String, ParamPtr> = self
+            .params
+            .param_map()
+            .into_iter()
+            .map(|(id, ptr, _group)| (id, ptr))
+            .collect();
+        let sysex_params = self.sysex_params.clone();
+        let event_log = self.event_log.clone();
+        let program_change_landed = self.program_change_fade.landed();
+        let scene_landed = self.scene_fade.landed();
+
+        // everything that writes parameters gets here through `execute_gui()`, so the changes can be applied right
+        // away, whether the editor is open or not
+        Box::new(move |task| {
+            match task {
+                Task::MidiCc { cc, value } => {
+                    midi_learn.handle_cc(&params, &param_ptrs, &param_writer, cc, value)
+                }
+                Task::RestoreSysEx(StateSysEx::Dump { entries, len }) => {
+                    let mut batch = ParamBatch::default();
+                    for (key, value) in &entries[..len] {
+                        // values for parameters that have been removed since the dump was made are simply skipped
+                        if let Some((_, param)) =
+                            sysex_params.iter().find(|(other, _)| other == key)
+                        {
+                            batch.set_normalized(*param, *value);
+                        }
+                    }
+                    if !param_writer.apply(batch) {
+                        nih_log!("Could not restore the SysEx dump before the editor is opened");
+                        param_writer.report_missed("SysEx dump");
+                    }
+                }
+                Task::RestoreSysEx(StateSysEx::DumpRequest) => (),
+                Task::LoadScene(index) => {
+                    let scene = params.scenes.read().unwrap()[index].clone();
+                    match scene {
+                        Some(scene) => {
+                            if scene.apply(&params, &param_ptrs, &param_writer) {
+                                event_log.record(EventKind::Preset, format!("Scene {}", index + 1));
+                            } else {
+                                nih_log!(
+                                    "Could not switch to scene {} before the editor is opened",
+                                    index + 1
+                                );
+                                param_writer.report_missed(format!("Scene {}", index + 1));
+                            }
+                        }
+                        None => nih_log!("Scene {} is empty, nothing to switch to", index + 1),
+                    }
+                    // only once the values are in, so the fade doesn't start coming back in before that
+                    param_writer.flush();
+                    scene_landed.store(true, Ordering::Release);
+                }
+                Task::WriteGrOutput(gr_db) => {
+                    let param = &params.gr_output;
+                    // not logging anything before the editor has been opened, this happens many times per second
+                    param_writer.set_normalized(param.as_ptr(), param.preview_normalized(gr_db));
+                }
+                Task::WriteLatencyOutput(latency_ms) => {
+                    let param = &params.latency_output;
+                    param_writer
+                        .set_normalized(param.as_ptr(), param.preview_normalized(latency_ms));
+                }
+                Task::FlushParamChanges => (),
+                Task::LoadPreset(index) => {
+                    let preset = &presets::FACTORY_PRESETS[index];
+                    if presets::apply(preset, &params, &param_ptrs, &param_writer) {
+                        *params.loaded_preset.write().unwrap() = Some(preset.name.to_string());
+                        event_log.record(
+                            EventKind::Preset,
+                            format!("Factory preset '{}' from a program change", preset.name),
+                        );
+                    } else {
+                        nih_log!(
+                            "Could not load preset '{}' before the editor is opened",
+                            preset.name
+                        );
+                        param_writer.report_missed(format!("Program change to '{}'", preset.name));
+                    }
+                    param_writer.flush();
+                    program_change_landed.store(true, Ordering::Release);
                 }
             }
+            param_writer.flush();
+        })
+    }
 
-            let level = match self.params.meter_type.value() {
-                LevelDetection::Rms => self.rms.value(),
-                LevelDetection::Peak => self.peak.value(),
-            };
-
-            let threshold = self.params.threshold.value();
-            let ratio = self.params.ratio.value();
-            let knee = self.params.knee_width.value();
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.publish_loudness_report();
+        self.process_mode = buffer_config.process_mode;
+        let requested_block_size = *self.params.block_size.read().unwrap();
+        self.block_size = validate_block_size(requested_block_size);
+        if self.block_size != requested_block_size {
+            nih_log!(
+                "Internal block size {requested_block_size} is out of range, using {} instead",
+                self.block_size
+            );
+        }
+        self.prepare(
+            buffer_config.sample_rate,
+            audio_io_layout
+                .main_input_channels
+                .map_or(0, |channels| channels.get() as usize),
+            audio_io_layout
+                .main_output_channels
+                .map_or(0, |channels| channels.get() as usize),
+        );
+        self.event_log.record(
+            EventKind::Layout,
+            format!(
+                "Initialized: {} in, {} out, {} Hz, {} sample blocks, {:?}",
+                self.input_channels,
+                self.output_channels,
+                self.sample_rate,
+                self.block_size,
+                self.process_mode
+            ),
+        );
 
-            self.amplitude
-                .set(calculate_gain_reduction(level, threshold, ratio, knee));
+        // the OSC threads only need to exist once, initialize() can be called again whenever the host feels like it
+        if self.osc_sender.is_none() {
+            self.osc_sender = Some(OscSender::spawn(
+                self.params.clone(),
+                self.meter_bus.clone(),
+            ));
+        }
+        if self.osc_receiver.is_none() {
+            self.osc_receiver = Some(OscReceiver::spawn(
+                self.params.clone(),
+                self.param_writer.clone(),
+                self.async_executor.clone(),
+            ));
+        }
+        if self.param_watcher.is_none() {
+            self.param_watcher = Some(ParamWatcher::spawn(
+                self.params.clone(),
+                self.event_log.clone(),
+            ));
+        }
 
-            self.graph.process(
-                block.samples(),
-                &self.input_buffer.buffer_ref(),
-                &mut self.output_buffer.buffer_mut(),
-            );
+        true
+    }
 
-            // write from output buffer
-            for (index, mut channel_samples) in block.iter_samples().enumerate() {
-                for n in 0..=1 {
-                    let sample_from_buf = self.output_buffer.buffer_ref().at_f32(n, index);
-                    *channel_samples.get_mut(n).unwrap() = sample_from_buf;
-                }
-            }
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // the editor clears the safe mode to try again, which starts over from a clean state
+        if self.safe_mode_engaged && !self.safe_mode.is_engaged() {
+            self.safe_mode_engaged = false;
+            self.reset();
+        }
+        if self.safe_mode_engaged {
+            // processing happens in place, so leaving the buffer alone passes the input straight through
+            return ProcessStatus::Normal;
         }
 
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| self.process_dsp(buffer, aux, context)));
+        let reason = match result {
+            Ok(status) if is_finite(buffer) => return status,
+            Ok(_) => SafeModeReason::NonFinite,
+            Err(_) => SafeModeReason::Panic,
+        };
+
+        // the input has already been overwritten, so this block goes silent. the next one gets passed through
+        for channel in buffer.as_slice() {
+            channel.fill(0.0);
+        }
+        self.safe_mode_engaged = true;
+        self.safe_mode.engage(reason);
+        util::permit_alloc(|| {
+            nih_error!("Switching to passthrough because {}", reason.description());
+            self.event_log.record(
+                EventKind::Error,
+                format!("Safe mode: {}", reason.description()),
+            );
+        });
         ProcessStatus::Normal
     }
 
     // This can be used for cleaning up special resources like socket connections whenever the
-    // plugin is deactivated. Most plugins won't need to do anything here.
-    fn deactivate(&mut self) {}
+    // plugin is deactivated.
+    fn deactivate(&mut self) {
+        // the host's context keeps the plugin alive, and this is the last thing hosts call before getting rid of it
+        self.param_writer.detach();
+        self.publish_loudness_report();
+        self.stats_collector
+            .flush(Some(&mut self.params.session_stats.write().unwrap()));
+    }
+
+    fn reset(&mut self) {
+        self.detector.reset();
+        self.compensation_delay.reset();
+        self.sidechain_delay.reset();
+        self.input_meter.reset();
+        self.meter_publisher.reset();
+        self.silence_detector.reset();
+        self.pad_detector.reset();
+        self.tilt_eq.reset();
+        self.tone_generator.reset();
+        self.gr_history.reset();
+        self.gain_path.reset();
+        self.last_target_gain = 1.0;
+        self.mode_switch.reset();
+        for lfo in &mut self.lfos {
+            lfo.reset();
+        }
+        self.envelope_follower.reset();
+        self.previous_dry_wet = self.params.dry_wet.value();
+        self.note_gate.reset();
+        self.program_change_fade.reset();
+        self.scene_fade.reset();
+        self.startup_ramp.reset();
+    }
 }
 
+// TODO:
+// preselect a material (`presets::MATERIALS`) from the CLAP track-info extension when the plugin first gets inserted,
+// e.g. "Drums" on a track whose name contains "drum"/"kick"/"snare", "Vocals" for "vox"/"vocal", and "Bus" when the
+// track-info flags say it's a bus. nih-plug (at the pinned rev) doesn't expose track-info or any other host context
+// to plugins, so this needs that added upstream first. it should only ever apply on first insert (no saved state
+// yet, see `state_version`) and never on top of a restored project. the audio-ports-config side is already covered
+// by `AUDIO_IO_LAYOUTS`, which nih-plug turns into CLAP port configs.
 impl ClapPlugin for Gain {
     const CLAP_ID: &'static str = "com.moist-plugins-gmbh.gain";
     const CLAP_DESCRIPTION: Option<&'static str> = Some("A smoothed gain parameter example plugin");
@@ -0,0 +1,169 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering},
+        OnceLock,
+    },
+    time::Instant,
+};
+
+use nih_plug::prelude::Enum;
+
+/// How many link groups there are to pick from. `GainParams::link_group` goes from 0 (not linked) up to this.
+pub const LINK_GROUP_COUNT: usize = 8;
+/// How many instances fit in a single group.
+const MAX_MEMBERS: usize = 16;
+/// Members that haven't published anything for this long (in milliseconds) get left out, so an instance that got
+/// bypassed, or a track that stopped playing, doesn't hold the whole group down.
+const STALE_AFTER_MS: u64 = 100;
+
+/// What linked instances share with each other.
+#[derive(Clone, Copy, PartialEq, Enum)]
+pub enum LinkMode {
+    /// Every instance sees the loudest detector level in the group, but turns that into gain reduction with its own
+    /// settings.
+    #[name = "Shared Detector"]
+    Detector,
+    /// Every instance applies the most gain reduction in the group, like one compressor across all of them.
+    #[name = "Gain Reduction"]
+    GainReduction,
+}
+
+impl LinkMode {
+    fn index(&self) -> u8 {
+        match self {
+            LinkMode::Detector => 0,
+            LinkMode::GainReduction => 1,
+        }
+    }
+}
+
+/// One instance's spot in a group.
+struct Slot {
+    claimed: AtomicBool,
+    /// The `LinkMode` the value belongs to. Instances in the same group but in different modes ignore each other.
+    mode: AtomicU8,
+    /// An `f32`, as bits.
+    value: AtomicU32,
+    /// When `value` was last published, see `now_ms()`.
+    updated: AtomicU64,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            claimed: AtomicBool::new(false),
+            mode: AtomicU8::new(0),
+            value: AtomicU32::new(0),
+            updated: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the slot's owner has published something recently. `updated` is `0` until it has.
+    fn is_fresh(&self, now: u64) -> bool {
+        let updated = self.updated.load(Ordering::Relaxed);
+        self.claimed.load(Ordering::Relaxed)
+            && updated > 0
+            && now.saturating_sub(updated) <= STALE_AFTER_MS
+    }
+
+    fn fresh_value(&self, mode: u8, now: u64) -> Option<f32> {
+        (self.is_fresh(now) && self.mode.load(Ordering::Relaxed) == mode)
+            .then(|| f32::from_bits(self.value.load(Ordering::Relaxed)))
+    }
+}
+
+// every instance in the same process (which is how most hosts run plugins) sees the same statics, that's all the
+// linking needs. instances in separate processes (sandboxed hosts) simply don't see each other
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot::new();
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_GROUP: [Slot; MAX_MEMBERS] = [EMPTY_SLOT; MAX_MEMBERS];
+static GROUPS: [[Slot; MAX_MEMBERS]; LINK_GROUP_COUNT] = [EMPTY_GROUP; LINK_GROUP_COUNT];
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// Milliseconds since the first instance asked, starting at 1 so `0` can mean "never".
+fn now_ms() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_millis() as u64 + 1
+}
+
+/// How many instances have published to `group` (1-based, like `GainParams::link_group`) recently, in any mode.
+pub fn active_members(group: usize) -> usize {
+    let Some(slots) = group.checked_sub(1).and_then(|index| GROUPS.get(index)) else {
+        return 0;
+    };
+    let now = now_ms();
+    slots.iter().filter(|slot| slot.is_fresh(now)).count()
+}
+
+/// An instance's membership in a link group. Lock-free, so it's fine to use from the audio thread. The values get
+/// exchanged once per block, and instances don't necessarily process in lockstep, so what an instance sees from the
+/// others can be a block behind.
+#[derive(Default)]
+pub struct LinkMember {
+    /// The group's index (0-based) and this instance's slot in it.
+    membership: Option<(usize, usize)>,
+}
+
+impl LinkMember {
+    /// Joins `group` (1-based, `0` leaves whatever group this was in). Doesn't do anything if that's already the group.
+    /// If the group is full the instance stays unlinked, and tries again the next time this gets called.
+    pub fn set_group(&mut self, group: usize) {
+        let index = group
+            .checked_sub(1)
+            .filter(|index| *index < LINK_GROUP_COUNT);
+        if self.membership.map(|(current, _)| current) == index {
+            return;
+        }
+
+        self.leave();
+        let Some(index) = index else {
+            return;
+        };
+        let slot = GROUPS[index].iter().position(|slot| {
+            slot.claimed
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        });
+        self.membership = slot.map(|slot| (index, slot));
+    }
+
+    /// Publishes this instance's value, and returns the other fresh members' values folded together with `combine`, or
+    /// `None` if there aren't any.
+    pub fn exchange(
+        &self,
+        mode: LinkMode,
+        value: f32,
+        combine: impl Fn(f32, f32) -> f32,
+    ) -> Option<f32> {
+        let (index, own_slot) = self.membership?;
+        let now = now_ms();
+        let slots = &GROUPS[index];
+
+        let slot = &slots[own_slot];
+        slot.mode.store(mode.index(), Ordering::Relaxed);
+        slot.value.store(value.to_bits(), Ordering::Relaxed);
+        slot.updated.store(now, Ordering::Relaxed);
+
+        slots
+            .iter()
+            .enumerate()
+            .filter(|(other_slot, _)| *other_slot != own_slot)
+            .filter_map(|(_, slot)| slot.fresh_value(mode.index(), now))
+            .reduce(combine)
+    }
+
+    fn leave(&mut self) {
+        if let Some((index, slot)) = self.membership.take() {
+            let slot = &GROUPS[index][slot];
+            // so whoever gets this slot next doesn't start out with our last value
+            slot.updated.store(0, Ordering::Relaxed);
+            slot.claimed.store(false, Ordering::Release);
+        }
+    }
+}
+
+impl Drop for LinkMember {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
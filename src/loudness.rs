@@ -0,0 +1,312 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+/// Blocks quieter than this are ignored entirely (the absolute gate).
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks more than this far below the ungated loudness are ignored (the relative gate).
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// Loudness is measured over 400 ms windows, which overlap by 75%, so a new one starts every 100 ms.
+const STEPS_PER_WINDOW: usize = 4;
+const STEP_TIME: f64 = 0.1;
+/// Instead of keeping every block around (which would mean allocating on the audio thread as the song gets longer),
+/// blocks are counted in 0.1 LU wide bins. This is plenty precise for a delivery report.
+const HISTOGRAM_BIN_WIDTH: f64 = 0.1;
+/// Enough bins to go from the absolute gate up to +10 LUFS.
+const HISTOGRAM_BINS: usize = 800;
+/// How many taps each phase of the true peak filter has.
+const TRUE_PEAK_TAPS: usize = 12;
+/// The 4x oversampling filter for true peak measurements from ITU-R BS.1770-4, Annex 2, split into its four phases.
+/// Every input sample gives one output sample per phase. The last two phases are the first two backwards.
+const TRUE_PEAK_PHASES: [[f32; TRUE_PEAK_TAPS]; 4] = [
+    [
+        0.0017089843750,
+        0.0109863281250,
+        -0.0196533203125,
+        0.0332031250000,
+        -0.0594482421875,
+        0.1373291015625,
+        0.9721679687500,
+        -0.1022949218750,
+        0.0476074218750,
+        -0.0266113281250,
+        0.0148925781250,
+        -0.0083007812500,
+    ],
+    [
+        -0.0291748046875,
+        0.0292968750000,
+        -0.0517578125000,
+        0.0891113281250,
+        -0.1665039062500,
+        0.4650878906250,
+        0.7797851562500,
+        -0.2003173828125,
+        0.1015625000000,
+        -0.0582275390625,
+        0.0330810546875,
+        -0.0189208984375,
+    ],
+    [
+        -0.0189208984375,
+        0.0330810546875,
+        -0.0582275390625,
+        0.1015625000000,
+        -0.2003173828125,
+        0.7797851562500,
+        0.4650878906250,
+        -0.1665039062500,
+        0.0891113281250,
+        -0.0517578125000,
+        0.0292968750000,
+        -0.0291748046875,
+    ],
+    [
+        -0.0083007812500,
+        0.0148925781250,
+        -0.0266113281250,
+        0.0476074218750,
+        -0.1022949218750,
+        0.9721679687500,
+        0.1373291015625,
+        -0.0594482421875,
+        0.0332031250000,
+        -0.0196533203125,
+        0.0109863281250,
+        0.0017089843750,
+    ],
+];
+
+/// The end result of measuring a whole offline render.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LoudnessReport {
+    /// Integrated (gated) loudness as per ITU-R BS.1770.
+    pub integrated_lufs: f32,
+    /// The true peak as per ITU-R BS.1770 (the highest peak after 4x oversampling), in dBTP.
+    pub true_peak_dbtp: f32,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+/// The two stage K-weighting filter from BS.1770, with the coefficients recalculated for any sample rate.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    // stage 1: the high shelf modeling the acoustic effect of the head
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10.0f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b0: (vh + vb * k / q + k * k) / a0,
+        b1: 2.0 * (k * k - vh) / a0,
+        b2: (vh - vb * k / q + k * k) / a0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+        ..Default::default()
+    };
+
+    // stage 2: the RLB high pass
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b0: 1.0,
+        b1: -2.0,
+        b2: 1.0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+        ..Default::default()
+    };
+
+    [shelf, high_pass]
+}
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Measures integrated loudness and true peak of a stereo signal.
+/// Doesn't allocate after construction, so it's safe to feed from the audio thread.
+pub struct LoudnessMeter {
+    filters: [[Biquad; 2]; 2],
+    step_length: usize,
+    /// Samples processed in the current 100 ms step.
+    step_position: usize,
+    /// Sum of squared K-weighted samples (summed over channels) for the current and last few steps.
+    step_energies: [f64; STEPS_PER_WINDOW],
+    steps_seen: usize,
+
+    histogram_counts: Box<[u32; HISTOGRAM_BINS]>,
+    histogram_power: Box<[f64; HISTOGRAM_BINS]>,
+
+    /// The last few samples of each channel, newest first, for the true peak filter.
+    history: [[f32; TRUE_PEAK_TAPS]; 2],
+    true_peak: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        let filters = k_weighting(sample_rate as f64);
+        Self {
+            filters: [filters, filters],
+            step_length: ((sample_rate as f64 * STEP_TIME) as usize).max(1),
+            step_position: 0,
+            step_energies: [0.0; STEPS_PER_WINDOW],
+            steps_seen: 0,
+            histogram_counts: Box::new([0; HISTOGRAM_BINS]),
+            histogram_power: Box::new([0.0; HISTOGRAM_BINS]),
+            history: [[0.0; TRUE_PEAK_TAPS]; 2],
+            true_peak: 0.0,
+        }
+    }
+
+    /// Whether anything has been measured since the last reset.
+    pub fn has_measurement(&self) -> bool {
+        self.steps_seen >= STEPS_PER_WINDOW
+    }
+
+    pub fn reset(&mut self) {
+        for channel in &mut self.filters {
+            for filter in channel {
+                filter.z1 = 0.0;
+                filter.z2 = 0.0;
+            }
+        }
+        self.step_position = 0;
+        self.step_energies = [0.0; STEPS_PER_WINDOW];
+        self.steps_seen = 0;
+        self.histogram_counts.fill(0);
+        self.histogram_power.fill(0.0);
+        self.history = [[0.0; TRUE_PEAK_TAPS]; 2];
+        self.true_peak = 0.0;
+    }
+
+    pub fn process(&mut self, frame: [f32; 2]) {
+        let current_step = self.steps_seen % STEPS_PER_WINDOW;
+        for (channel, sample) in frame.into_iter().enumerate() {
+            let [shelf, high_pass] = &mut self.filters[channel];
+            let weighted = high_pass.process(shelf.process(sample as f64));
+            self.step_energies[current_step] += weighted * weighted;
+
+            self.update_true_peak(channel, sample);
+        }
+
+        self.step_position += 1;
+        if self.step_position == self.step_length {
+            self.finish_step();
+        }
+    }
+
+    fn update_true_peak(&mut self, channel: usize, sample: f32) {
+        let history = &mut self.history[channel];
+        history.rotate_right(1);
+        history[0] = sample;
+
+        for coefficients in &TRUE_PEAK_PHASES {
+            let oversampled: f32 = coefficients
+                .iter()
+                .zip(history.iter())
+                .map(|(coefficient, sample)| coefficient * sample)
+                .sum();
+            self.true_peak = self.true_peak.max(oversampled.abs());
+        }
+    }
+
+    fn finish_step(&mut self) {
+        self.step_position = 0;
+        self.steps_seen += 1;
+
+        if self.steps_seen >= STEPS_PER_WINDOW {
+            let window_length = (self.step_length * STEPS_PER_WINDOW) as f64;
+            let power = self.step_energies.iter().sum::<f64>() / window_length;
+            let loudness = power_to_lufs(power);
+            if loudness > ABSOLUTE_GATE_LUFS {
+                let bin = ((loudness - ABSOLUTE_GATE_LUFS) / HISTOGRAM_BIN_WIDTH) as usize;
+                let bin = bin.min(HISTOGRAM_BINS - 1);
+                self.histogram_counts[bin] += 1;
+                self.histogram_power[bin] += power;
+            }
+        }
+
+        // the oldest step gets overwritten by the next one
+        self.step_energies[self.steps_seen % STEPS_PER_WINDOW] = 0.0;
+    }
+
+    /// The integrated loudness and true peak of everything measured since the last reset,
+    /// or `None` if nothing loud enough to measure has come through.
+    pub fn report(&self) -> Option<LoudnessReport> {
+        let gated_power = |from_bin: usize| {
+            let (count, power) =
+                (from_bin..HISTOGRAM_BINS).fold((0u64, 0.0), |(count, power), bin| {
+                    (
+                        count + self.histogram_counts[bin] as u64,
+                        power + self.histogram_power[bin],
+                    )
+                });
+            (count > 0).then(|| power / count as f64)
+        };
+
+        let ungated = gated_power(0)?;
+        let relative_gate = power_to_lufs(ungated) + RELATIVE_GATE_LU;
+        let relative_bin =
+            ((relative_gate - ABSOLUTE_GATE_LUFS) / HISTOGRAM_BIN_WIDTH).max(0.0) as usize;
+        let integrated = gated_power(relative_bin.min(HISTOGRAM_BINS - 1)).unwrap_or(ungated);
+
+        Some(LoudnessReport {
+            integrated_lufs: power_to_lufs(integrated) as f32,
+            true_peak_dbtp: 20.0 * self.true_peak.max(1e-10).log10(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_4, TAU};
+
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn measure_sine(frequency: f32, amplitude: f32, phase: f32) -> LoudnessReport {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+        for n in 0..SAMPLE_RATE as usize {
+            let sample = (TAU * frequency * n as f32 / SAMPLE_RATE + phase).sin() * amplitude;
+            meter.process([sample, sample]);
+        }
+        meter.report().unwrap()
+    }
+
+    #[test]
+    fn true_peak_of_low_sine_matches_its_amplitude() {
+        let report = measure_sine(1000.0, 0.5, 0.0);
+        assert!((report.true_peak_dbtp + 6.02).abs() < 0.1, "{report:?}");
+    }
+
+    #[test]
+    fn true_peak_finds_peaks_between_samples() {
+        // at a quarter of the sample rate, shifted by 45 degrees, every sample lands 3 dB below the actual peak
+        let report = measure_sine(SAMPLE_RATE / 4.0, 0.5, FRAC_PI_4);
+        assert!((report.true_peak_dbtp + 6.02).abs() < 0.2, "{report:?}");
+    }
+}
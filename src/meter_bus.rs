@@ -0,0 +1,360 @@
+//! Everything the DSP shows to the outside world (the editor, OSC) goes through here. The rules:
+//!
+//! - The audio thread only ever stores into atomics. It never takes a lock and never allocates, so a slow, hidden or
+//!   closed editor can't hold it up.
+//! - Everything has a fixed size, decided when the bus gets created. Nothing grows with how long the editor hasn't
+//!   been looking.
+//! - Anything with more than one value per block (histories, and later on spectra) gets decimated to a fixed rate on
+//!   the audio thread, independent of the block size and of how often the editor redraws. Readers just take a
+//!   snapshot whenever they get around to it, and may see a point from the next update mixed in, which is harmless
+//!   for displays.
+//! - The single readings get published at a fixed rate too, holding on to the most extreme value in between (see
+//!   `MeterPublisher`), so the meters look the same no matter how large the host's buffers are.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use fundsp::hacker::{shared, Shared};
+
+use crate::smoothing::one_pole_coefficient;
+
+/// The averaging time of the input RMS meters, in seconds. Same as the RMS detector's.
+const RMS_WINDOW: f32 = 0.1;
+/// How long the input peak meters take to fall back down, in seconds.
+const PEAK_DECAY: f32 = 0.1;
+/// Anything quieter than this (-90 dBFS, as a linear amplitude) counts as silence.
+const SILENCE_THRESHOLD: f32 = 3.162_278e-5;
+/// How long the input has to stay silent before the meters pause, in seconds.
+const SILENCE_TIME: f32 = 0.5;
+/// How many points a `History` keeps.
+pub const HISTORY_LENGTH: usize = 128;
+/// How many points per second get added to a `History`, so 128 points is a little over 4 seconds.
+const HISTORY_RATE: f32 = 30.0;
+/// How many times per second the levels and the gain reduction get published, see `MeterPublisher`.
+const PUBLISH_RATE: f32 = 60.0;
+
+/// Every meter reading the DSP publishes, shared between the DSP and everything that displays or sends them
+/// (the editor, OSC). All readings are linear amplitudes/gains stored in atomics, so reading them never blocks the
+/// audio thread and publishing never blocks the readers.
+///
+/// The bus is created along with the plugin and cleared in `initialize()`, rather than created there, so an editor
+/// that's opened before the plugin gets initialized still ends up looking at the right readings.
+pub struct MeterBus {
+    /// The input's RMS level per channel, after the input gain.
+    pub input_rms: [Shared; 2],
+    /// The input's peak level per channel, after the input gain.
+    pub input_peak: [Shared; 2],
+    /// The linear gain factor currently applied by the compressor. Below `1.0` means gain reduction.
+    pub gain_reduction: Shared,
+    /// The peak level per channel of the most recently processed block of output.
+    pub output_peak: [Shared; 2],
+    /// Set while the input is silent, see `SilenceDetector`. The other readings hold still in the meantime.
+    pub silent: AtomicBool,
+    /// Set while the input has been above 0 dBFS for a while, see `headroom::PadDetector`.
+    pub hot_input: AtomicBool,
+    /// The last few seconds of `gain_reduction`, written through a `HistoryWriter`.
+    pub gr_history: History,
+    /// The lowest `gain_reduction` (so the most gain reduction) since the last reset, see `update_max_hold()`.
+    pub gr_max_hold: Shared,
+    /// Where on the host's timeline the max hold was reached, in seconds. `NaN` if the host didn't tell us.
+    pub gr_max_hold_time: Shared,
+    /// Set from the editor to have the audio thread start the max hold over. Going through the audio thread means a
+    /// reset can't race with it publishing a new maximum.
+    pub reset_gr_max_hold: AtomicBool,
+    /// The latency currently reported to the host, in samples.
+    pub latency_samples: AtomicU32,
+}
+
+impl Default for MeterBus {
+    fn default() -> Self {
+        Self {
+            input_rms: [shared(0.0), shared(0.0)],
+            input_peak: [shared(0.0), shared(0.0)],
+            gain_reduction: shared(1.0),
+            output_peak: [shared(0.0), shared(0.0)],
+            silent: AtomicBool::new(false),
+            hot_input: AtomicBool::new(false),
+            gr_history: History::new(1.0),
+            gr_max_hold: shared(1.0),
+            gr_max_hold_time: shared(f32::NAN),
+            reset_gr_max_hold: AtomicBool::new(false),
+            latency_samples: AtomicU32::new(0),
+        }
+    }
+}
+
+impl MeterBus {
+    pub fn reset(&self) {
+        for reading in self
+            .input_rms
+            .iter()
+            .chain(&self.input_peak)
+            .chain(&self.output_peak)
+        {
+            reading.set(0.0);
+        }
+        self.gain_reduction.set(1.0);
+        self.silent.store(false, Ordering::Relaxed);
+        self.hot_input.store(false, Ordering::Relaxed);
+        self.gr_history.reset(1.0);
+        self.gr_max_hold.set(1.0);
+        self.gr_max_hold_time.set(f32::NAN);
+        self.reset_gr_max_hold.store(false, Ordering::Relaxed);
+    }
+
+    /// Called from the audio thread with the gain that's currently applied, and where in the song that is.
+    pub fn update_max_hold(&self, gain: f32, position_seconds: Option<f64>) {
+        if self.reset_gr_max_hold.swap(false, Ordering::Relaxed) {
+            self.gr_max_hold.set(1.0);
+            self.gr_max_hold_time.set(f32::NAN);
+        }
+        if gain < self.gr_max_hold.value() {
+            self.gr_max_hold.set(gain);
+            self.gr_max_hold_time
+                .set(position_seconds.map_or(f32::NAN, |seconds| seconds as f32));
+        }
+    }
+
+    /// The louder of the two input RMS readings.
+    pub fn input_rms_max(&self) -> f32 {
+        max_of(&self.input_rms)
+    }
+
+    /// The louder of the two input peak readings.
+    pub fn input_peak_max(&self) -> f32 {
+        max_of(&self.input_peak)
+    }
+
+    /// The louder of the two output peak readings.
+    pub fn output_peak_max(&self) -> f32 {
+        max_of(&self.output_peak)
+    }
+}
+
+/// Formats a position on the timeline (like `MeterBus::gr_max_hold_time`) as minutes and seconds.
+/// Returns `None` for unknown (`NaN`) positions.
+pub fn format_position(seconds: f32) -> Option<String> {
+    if !seconds.is_finite() {
+        return None;
+    }
+
+    let minutes = (seconds / 60.0).floor();
+    Some(format!("{minutes:.0}:{:04.1}", seconds - minutes * 60.0))
+}
+
+fn max_of(readings: &[Shared; 2]) -> f32 {
+    readings[0].value().max(readings[1].value())
+}
+
+/// Measures the input for the `MeterBus`. This runs on the audio thread, and gets published by the `MeterPublisher`.
+/// Unlike the `Detector`, the channels are metered separately.
+pub struct InputMeter {
+    rms_coefficient: f32,
+    peak_decay: f32,
+    mean_square: [f32; 2],
+    peak: [f32; 2],
+    /// The highest `peak` since the last publish.
+    held_peak: [f32; 2],
+}
+
+impl InputMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            rms_coefficient: one_pole_coefficient(RMS_WINDOW, sample_rate),
+            peak_decay: 1.0 - one_pole_coefficient(PEAK_DECAY, sample_rate),
+            mean_square: [0.0; 2],
+            peak: [0.0; 2],
+            held_peak: [0.0; 2],
+        }
+    }
+
+    pub fn process(&mut self, frame: [f32; 2]) {
+        for (channel, sample) in frame.into_iter().enumerate() {
+            self.mean_square[channel] +=
+                (sample * sample - self.mean_square[channel]) * self.rms_coefficient;
+            self.peak[channel] = sample.abs().max(self.peak[channel] * self.peak_decay);
+            self.held_peak[channel] = self.held_peak[channel].max(self.peak[channel]);
+        }
+    }
+
+    pub fn publish(&mut self, bus: &MeterBus) {
+        for (channel, mean_square) in self.mean_square.iter().enumerate() {
+            bus.input_rms[channel].set(mean_square.sqrt());
+            bus.input_peak[channel].set(self.held_peak[channel]);
+        }
+        self.held_peak = self.peak;
+    }
+
+    pub fn reset(&mut self) {
+        self.mean_square = [0.0; 2];
+        self.peak = [0.0; 2];
+        self.held_peak = [0.0; 2];
+    }
+}
+
+/// Notices when the input has gone silent for a while, e.g. when playback stops or between songs.
+/// The meters and the loudness measurement pause while it's silent, instead of falling to -inf and dragging the
+/// integrated readings down with them.
+pub struct SilenceDetector {
+    hold_samples: usize,
+    silent_samples: usize,
+}
+
+impl SilenceDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            hold_samples: (SILENCE_TIME * sample_rate) as usize,
+            silent_samples: 0,
+        }
+    }
+
+    pub fn process(&mut self, frame: [f32; 2]) {
+        if frame.iter().all(|sample| sample.abs() < SILENCE_THRESHOLD) {
+            self.silent_samples = self.silent_samples.saturating_add(1);
+        } else {
+            self.silent_samples = 0;
+        }
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.silent_samples >= self.hold_samples
+    }
+
+    pub fn reset(&mut self) {
+        self.silent_samples = 0;
+    }
+}
+
+/// A fixed-size ring of readings over time.
+pub struct History {
+    points: [Shared; HISTORY_LENGTH],
+    /// Where the next point goes, which is also where the oldest one is.
+    next: AtomicUsize,
+}
+
+impl History {
+    fn new(value: f32) -> Self {
+        Self {
+            points: std::array::from_fn(|_| shared(value)),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn push(&self, value: f32) {
+        let next = self.next.load(Ordering::Relaxed);
+        self.points[next].set(value);
+        self.next
+            .store((next + 1) % HISTORY_LENGTH, Ordering::Release);
+    }
+
+    fn reset(&self, value: f32) {
+        for point in &self.points {
+            point.set(value);
+        }
+        self.next.store(0, Ordering::Release);
+    }
+
+    /// Every point, from oldest to newest.
+    pub fn snapshot(&self) -> [f32; HISTORY_LENGTH] {
+        let next = self.next.load(Ordering::Acquire);
+        std::array::from_fn(|index| self.points[(next + index) % HISTORY_LENGTH].value())
+    }
+}
+
+/// Publishes the single readings at `PUBLISH_RATE`. In between, it keeps the highest output peak and the gain that's
+/// furthest from unity (so the most gain reduction, or the most boost when expanding). Publishing at the end of every
+/// block instead would show whatever that block happened to end on: with small blocks, most peaks would be gone again
+/// by the time the editor looks.
+pub struct MeterPublisher {
+    /// How many samples go between publishes.
+    interval: f32,
+    elapsed: f32,
+    output_peak: [f32; 2],
+    /// `None` right after publishing.
+    extreme_gain: Option<f32>,
+}
+
+impl MeterPublisher {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            interval: sample_rate / PUBLISH_RATE,
+            elapsed: 0.0,
+            output_peak: [0.0; 2],
+            extreme_gain: None,
+        }
+    }
+
+    /// Adds a block of `samples` samples. Returns `true` when it's time to publish.
+    pub fn process(&mut self, samples: usize, output_peak: [f32; 2], gain: f32) -> bool {
+        for (held, peak) in self.output_peak.iter_mut().zip(output_peak) {
+            *held = held.max(peak);
+        }
+        self.extreme_gain = match self.extreme_gain {
+            Some(held) if held.ln().abs() >= gain.ln().abs() => Some(held),
+            _ => Some(gain),
+        };
+
+        self.elapsed += samples as f32;
+        if self.elapsed < self.interval {
+            return false;
+        }
+        // a single huge block counts as one publish, there's nothing new to show for the rest of it
+        self.elapsed %= self.interval;
+        true
+    }
+
+    /// Publishes the output peak and the gain reduction, and starts collecting again. The output peak holds still
+    /// while the input is silent, like the input meters.
+    pub fn publish(&mut self, bus: &MeterBus, silent: bool) {
+        if !silent {
+            for (reading, peak) in bus.output_peak.iter().zip(self.output_peak) {
+                reading.set(peak);
+            }
+        }
+        if let Some(gain) = self.extreme_gain.take() {
+            bus.gain_reduction.set(gain);
+        }
+        self.output_peak = [0.0; 2];
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.output_peak = [0.0; 2];
+        self.extreme_gain = None;
+    }
+}
+
+/// Decimates a gain to `HISTORY_RATE` on the audio thread, keeping the most extreme value in between.
+/// Anything else worth plotting over time should get its own writer like this, instead of being sent every block.
+pub struct HistoryWriter {
+    /// How many samples go into every point.
+    interval: f32,
+    elapsed: f32,
+    /// The lowest gain (i.e. the most gain reduction) since the last point, so short dips still show up.
+    lowest: f32,
+}
+
+impl HistoryWriter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            interval: sample_rate / HISTORY_RATE,
+            elapsed: 0.0,
+            lowest: 1.0,
+        }
+    }
+
+    /// Adds a block of `samples` samples that ended at `gain`.
+    pub fn process(&mut self, gain: f32, samples: usize, history: &History) {
+        self.lowest = self.lowest.min(gain);
+        self.elapsed += samples as f32;
+        while self.elapsed >= self.interval {
+            history.push(self.lowest);
+            self.elapsed -= self.interval;
+            self.lowest = gain;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.lowest = 1.0;
+    }
+}
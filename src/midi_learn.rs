@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{param_writer::ParamWriter, params::GainParams};
+
+/// How close (normalized) a controller needs to get to the parameter's current value before pickup mode lets it through.
+const PICKUP_TOLERANCE: f32 = 0.02;
+
+/// Maps a MIDI CC to a parameter. Persisted with the rest of the plugin state.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CcMapping {
+    pub cc: u8,
+    pub param_id: String,
+    /// The normalized parameter value a CC value of `0` maps to.
+    pub min: f32,
+    /// The normalized parameter value a CC value of `127` maps to. This can be lower than `min` to invert the controller.
+    pub max: f32,
+    /// When enabled, the controller only takes over once it has reached the parameter's current value,
+    /// instead of making the parameter jump to wherever the controller happens to be.
+    pub pickup: bool,
+}
+
+impl CcMapping {
+    pub fn new(cc: u8, param_id: String) -> Self {
+        Self {
+            cc,
+            param_id,
+            min: 0.0,
+            max: 1.0,
+            pickup: false,
+        }
+    }
+
+    fn target(&self, cc_value: f32) -> f32 {
+        self.min + (self.max - self.min) * cc_value
+    }
+}
+
+/// Runtime-only pickup state for a single controller.
+#[derive(Default)]
+struct PickupState {
+    picked_up: bool,
+    /// Where the controller was the last time it moved, to detect it crossing the parameter's value.
+    last_target: Option<f32>,
+    /// What we last set the parameter to. If the parameter moves away from this, something else changed it.
+    last_written: Option<f32>,
+}
+
+/// MIDI learn state shared between the editor (arming learn mode) and the background task that handles incoming CCs.
+/// The mappings themselves live in `GainParams::cc_mappings` so they get saved with the project.
+#[derive(Default)]
+pub struct MidiLearn {
+    /// The parameter the next incoming CC will be mapped to.
+    armed: Mutex<Option<String>>,
+    pickup: Mutex<HashMap<u8, PickupState>>,
+}
+
+impl MidiLearn {
+    pub fn arm(&self, param_id: &str) {
+        *self.armed.lock().unwrap() = Some(param_id.to_string());
+    }
+
+    pub fn cancel(&self) {
+        *self.armed.lock().unwrap() = None;
+    }
+
+    pub fn armed(&self) -> Option<String> {
+        self.armed.lock().unwrap().clone()
+    }
+
+    /// Called from the task executor (on the GUI thread) for every incoming CC. `value` is the CC value normalized to
+    /// `0.0..=1.0`.
+    pub fn handle_cc(
+        &self,
+        params: &GainParams,
+        param_ptrs: &HashMap<String, ParamPtr>,
+        writer: &ParamWriter,
+        cc: u8,
+        value: f32,
+    ) {
+        if let Some(param_id) = self.armed.lock().unwrap().take() {
+            learn(&params.cc_mappings, cc, param_id);
+            self.pickup.lock().unwrap().remove(&cc);
+            return;
+        }
+
+        let mappings = params.cc_mappings.read().unwrap();
+        let mut pickup = self.pickup.lock().unwrap();
+        for mapping in mappings.iter().filter(|mapping| mapping.cc == cc) {
+            let Some(param) = param_ptrs.get(&mapping.param_id) else {
+                continue;
+            };
+            let target = mapping.target(value);
+            // a value that's still waiting to be applied counts as already written, or fast controller movements
+            // would keep losing the pickup
+            let current = writer.pending_normalized(*param).unwrap_or_else(|| {
+                // SAFETY: the pointer comes from our own `GainParams`, which lives as long as the plugin does
+                unsafe { param.unmodulated_normalized_value() }
+            });
+
+            let state = pickup.entry(cc).or_default();
+            if mapping.pickup {
+                // someone else (automation, the editor) moved the parameter, so we need to pick it up again
+                if state
+                    .last_written
+                    .is_some_and(|written| (written - current).abs() > PICKUP_TOLERANCE)
+                {
+                    state.picked_up = false;
+                }
+                if !state.picked_up {
+                    let crossed = state.last_target.is_some_and(|last| {
+                        (last - current).signum() != (target - current).signum()
+                    });
+                    state.picked_up = crossed || (target - current).abs() <= PICKUP_TOLERANCE;
+                }
+                state.last_target = Some(target);
+                if !state.picked_up {
+                    continue;
+                }
+            }
+
+            if writer.set_normalized(*param, target) {
+                state.last_written = Some(target);
+            } else {
+                writer.report_missed(format!("CC {cc} to \"{}\"", mapping.param_id));
+            }
+        }
+    }
+}
+
+/// Maps `cc` to `param_id`, replacing whatever that CC was mapped to before.
+fn learn(mappings: &RwLock<Vec<CcMapping>>, cc: u8, param_id: String) {
+    let mut mappings = mappings.write().unwrap();
+    mappings.retain(|mapping| mapping.cc != cc);
+    mappings.push(CcMapping::new(cc, param_id));
+}
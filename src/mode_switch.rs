@@ -0,0 +1,120 @@
+/// How long the old and the new signal path run side by side after a mode switch, in seconds.
+const CROSSFADE_TIME: f32 = 0.01;
+
+/// Crossfades between the old and the new signal path when a mode that changes the structure of the processing
+/// (detector type, compression vs expansion, ...) gets switched, so that doesn't click during playback.
+///
+/// `T` is a snapshot of every such mode. While a crossfade is running, `process()` runs the path for both the old and
+/// the new snapshot and blends them. New structural modes only need to be added to that snapshot to get this for free.
+/// Switching again halfway through starts over from the latest modes, which is good enough for something that only
+/// happens when someone's clicking around.
+pub struct ModeSwitch<T> {
+    /// `None` until the first block, so the modes the plugin starts out with don't get faded in.
+    current: Option<T>,
+    /// The modes from before the last switch, and how many samples of the crossfade are left.
+    fading_from: Option<(T, usize)>,
+}
+
+impl<T> Default for ModeSwitch<T> {
+    fn default() -> Self {
+        Self {
+            current: None,
+            fading_from: None,
+        }
+    }
+}
+
+impl<T: Copy + PartialEq> ModeSwitch<T> {
+    /// Called once per block with the current modes. Returns the crossfade away from the old modes while one is
+    /// running.
+    pub fn next(&mut self, modes: T, block_len: usize, sample_rate: f32) -> Option<Crossfade<T>> {
+        let crossfade_len = ((CROSSFADE_TIME * sample_rate) as usize).max(1);
+        if let Some(current) = self.current {
+            if current != modes {
+                self.fading_from = Some((current, crossfade_len));
+            }
+        }
+        self.current = Some(modes);
+
+        let (old, remaining) = self.fading_from?;
+        let left = remaining.saturating_sub(block_len);
+        self.fading_from = (left > 0).then_some((old, left));
+        Some(Crossfade {
+            from: old,
+            remaining,
+            length: crossfade_len,
+        })
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// A crossfade that's running during the current block, see `ModeSwitch::next()`.
+pub struct Crossfade<T> {
+    /// The modes from before the switch.
+    pub from: T,
+    /// How many samples of the crossfade were left at the start of the block.
+    remaining: usize,
+    length: usize,
+}
+
+impl<T> Crossfade<T> {
+    /// How much of the old modes' path should still be mixed in on the `index`th sample of the block, going from 1 down
+    /// to 0. This only depends on how long ago the switch was, not on how the blocks were split up since.
+    pub fn amount(&self, index: usize) -> f32 {
+        self.remaining.saturating_sub(index + 1) as f32 / self.length as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const LENGTH: usize = 1000;
+    /// Where the modes switch. The wrapper starts a new block there, like it does for any parameter change.
+    const SWITCH_AT: usize = 301;
+
+    /// How much of the old modes' path gets mixed in on every sample, with the audio split into blocks of `block_size`.
+    fn amounts(block_size: usize) -> Vec<f32> {
+        let mut mode_switch = ModeSwitch::default();
+        let mut amounts = Vec::with_capacity(LENGTH);
+        let mut start = 0;
+        while start < LENGTH {
+            let (modes, block_end) = if start < SWITCH_AT {
+                (0, SWITCH_AT)
+            } else {
+                (1, LENGTH)
+            };
+            let end = (start + block_size).min(block_end);
+            let crossfade = mode_switch.next(modes, end - start, SAMPLE_RATE);
+            amounts.extend((0..end - start).map(|index| {
+                crossfade.as_ref().map_or(0.0, |crossfade| {
+                    assert_eq!(crossfade.from, 0);
+                    crossfade.amount(index)
+                })
+            }));
+            start = end;
+        }
+        amounts
+    }
+
+    #[test]
+    fn crossfade_does_not_depend_on_the_block_size() {
+        let reference = amounts(1);
+        for block_size in [7, 63, 64, 100] {
+            assert!(amounts(block_size) == reference, "block size {block_size}");
+        }
+
+        let crossfade_len = (CROSSFADE_TIME * SAMPLE_RATE) as usize;
+        assert!(reference[..SWITCH_AT].iter().all(|amount| *amount == 0.0));
+        assert!(reference[SWITCH_AT] > 0.99);
+        let fading = &reference[SWITCH_AT..SWITCH_AT + crossfade_len];
+        assert!(fading.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(reference[SWITCH_AT + crossfade_len - 1..]
+            .iter()
+            .all(|amount| *amount == 0.0));
+    }
+}
@@ -0,0 +1,133 @@
+use std::f32::consts::TAU;
+
+use nih_plug::{prelude::Enum, util};
+
+use crate::smoothing::one_pole_coefficient;
+
+/// How far a depth of 100% moves the threshold, in dB.
+const THRESHOLD_RANGE_DB: f32 = 24.0;
+/// How far a depth of 100% moves the ratio, in octaves (so 2 means a 4:1 ratio can go from 1:1 to 16:1).
+const RATIO_RANGE_OCTAVES: f32 = 2.0;
+/// The envelope follower maps this range of input levels to 0..1.
+const ENVELOPE_FLOOR_DB: f32 = -60.0;
+/// The envelope follower's attack, in seconds. Only the release is adjustable.
+const ENVELOPE_ATTACK: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq, Enum)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Square,
+    /// Ramps down from the top, for pumping.
+    #[name = "Saw Down"]
+    SawDown,
+}
+
+/// Where a modulation source gets routed to.
+#[derive(Clone, Copy, PartialEq, Enum)]
+pub enum ModTarget {
+    Off,
+    Threshold,
+    Ratio,
+    #[name = "Dry/Wet"]
+    DryWet,
+}
+
+/// A free-running LFO, stepped once per block. Outputs -1..1.
+#[derive(Default)]
+pub struct Lfo {
+    /// 0..1
+    phase: f32,
+}
+
+impl Lfo {
+    /// Returns the value at the start of the block, and then moves on by `block_len` samples. `rate` is in Hz.
+    pub fn next(&mut self, rate: f32, shape: LfoShape, block_len: usize, sample_rate: f32) -> f32 {
+        let phase = self.phase;
+        self.phase = (self.phase + rate * block_len as f32 / sample_rate).fract();
+
+        match shape {
+            LfoShape::Sine => (phase * TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::SawDown => 1.0 - 2.0 * phase,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+}
+
+/// Follows the detector's level, stepped once per block. Outputs 0..1, with `ENVELOPE_FLOOR_DB` at 0 and 0 dBFS at 1.
+#[derive(Default)]
+pub struct EnvelopeFollower {
+    value: f32,
+}
+
+impl EnvelopeFollower {
+    /// `level` is a linear gain, `release` is in seconds.
+    pub fn next(&mut self, level: f32, release: f32, block_len: usize, sample_rate: f32) -> f32 {
+        let target =
+            (1.0 - util::gain_to_db(level).max(ENVELOPE_FLOOR_DB) / ENVELOPE_FLOOR_DB).min(1.0);
+        let time = if target > self.value {
+            ENVELOPE_ATTACK
+        } else {
+            release
+        };
+        let block_rate = sample_rate / block_len as f32;
+        self.value += (target - self.value) * one_pole_coefficient(time, block_rate);
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+    }
+}
+
+/// The sum of everything routed to each target for the current block, with every source already scaled by its depth.
+/// This gets applied on top of the (smoothed) parameter values in `process()`, the parameters themselves never change.
+#[derive(Default, Clone, Copy)]
+pub struct Modulation {
+    threshold: f32,
+    ratio: f32,
+    dry_wet: f32,
+}
+
+impl Modulation {
+    /// Adds a source's output (-1..1 for LFOs, 0..1 for the envelope) times its depth (-1..1) to a target.
+    pub fn add(&mut self, target: ModTarget, value: f32, depth: f32) {
+        let amount = value * depth;
+        match target {
+            ModTarget::Off => (),
+            ModTarget::Threshold => self.threshold += amount,
+            ModTarget::Ratio => self.ratio += amount,
+            ModTarget::DryWet => self.dry_wet += amount,
+        }
+    }
+
+    pub fn threshold(&self, threshold_db: f32) -> f32 {
+        threshold_db + self.threshold * THRESHOLD_RANGE_DB
+    }
+
+    /// Ratios are modulated in octaves, so the same depth sounds about as strong at 2:1 as at 8:1. Never crosses over
+    /// from compression to expansion (or back), since that would flip the gain reduction around.
+    pub fn ratio(&self, ratio: f32) -> f32 {
+        let modulated = ratio * (self.ratio * RATIO_RANGE_OCTAVES).exp2();
+        if ratio >= 1.0 {
+            modulated.max(1.0)
+        } else {
+            modulated.min(1.0)
+        }
+    }
+
+    pub fn dry_wet(&self, dry_wet: f32) -> f32 {
+        (dry_wet + self.dry_wet).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::smoothing::one_pole_coefficient;
+
+/// How long the gate takes to open once a note is pressed. Short enough to feel instant, long enough not to click.
+const OPEN_TIME: f32 = 0.001;
+
+/// Opens the output while any MIDI note is held, and fades it back out with a release once all notes are let go.
+/// Turns the plugin into a gate/stutter effect that can be played from a keyboard.
+pub struct NoteGate {
+    held_notes: [bool; 128],
+    held_count: usize,
+    /// The current gain applied to the output, `0.0` (closed) to `1.0` (open).
+    gain: f32,
+}
+
+impl Default for NoteGate {
+    fn default() -> Self {
+        Self {
+            held_notes: [false; 128],
+            held_count: 0,
+            gain: 0.0,
+        }
+    }
+}
+
+impl NoteGate {
+    pub fn note_on(&mut self, note: u8) {
+        if let Some(held) = self.held_notes.get_mut(note as usize) {
+            if !*held {
+                *held = true;
+                self.held_count += 1;
+            }
+        }
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        if let Some(held) = self.held_notes.get_mut(note as usize) {
+            if *held {
+                *held = false;
+                self.held_count -= 1;
+            }
+        }
+    }
+
+    /// Closes the gate right away, for when the transport stops or the plugin gets reset.
+    pub fn reset(&mut self) {
+        self.held_notes = [false; 128];
+        self.held_count = 0;
+        self.gain = 0.0;
+    }
+
+    /// Advances the gate by a single sample and returns the gain to apply to that sample.
+    /// `open_override` forces the gate open (`Some(true)`) or closed (`Some(false)`) regardless of the held notes,
+    /// it still opens and closes with the usual timings. `release_time` is in seconds.
+    pub fn next(
+        &mut self,
+        open_override: Option<bool>,
+        release_time: f32,
+        sample_rate: f32,
+    ) -> f32 {
+        let open = open_override.unwrap_or(self.held_count > 0);
+        let (target, time) = if open {
+            (1.0, OPEN_TIME)
+        } else {
+            (0.0, release_time)
+        };
+        self.gain += (target - self.gain) * one_pole_coefficient(time, sample_rate);
+        self.gain
+    }
+}
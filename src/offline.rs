@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use nih_plug::prelude::*;
+
+use crate::{
+    ballistics::BallisticsCoefficients,
+    detector::{Detector, DetectorCoefficients},
+    gain_computer::{Conversions, Db, GainComputer, LinearGain},
+    gain_path::{self, GainPath},
+    params::GainParams,
+    presets::FACTORY_PRESETS,
+    smoothing::one_pole_coefficient,
+    user_defaults::UserDefaults,
+    GainComputerMode, LevelDetection,
+};
+
+/// The compressor without anything that needs a host, for the `funih-render` tool and the fuzz target: the input gain,
+/// the detector, the `GainPath`, the dry/wet mix and the output gain, all running once per sample like in `process()`.
+///
+/// The settings can't change while it runs, and everything around the core (the sidechain, linking, modulation, the
+/// note gate, the tone and the meters) is left out. The dB conversions are always exact, like an offline render in
+/// the plugin.
+pub struct OfflineCompressor {
+    detector: Detector,
+    gain_path: GainPath,
+    detector_coefficients: DetectorCoefficients,
+    ballistics_coefficients: BallisticsCoefficients,
+    conversions: Conversions,
+    curve: GainComputer,
+    smoothing: f32,
+    mode: GainComputerMode,
+    level_detection: LevelDetection,
+    peak_blend: f32,
+    max_boost: LinearGain,
+    lf_hold: bool,
+    input_gain: f32,
+    output_gain: f32,
+    dry_wet: f32,
+    /// The gain applied to the last frame, see `gain()`.
+    gain: f32,
+}
+
+impl OfflineCompressor {
+    /// Sets up the compressor with `values`, which are plain parameter values by ID like in `FACTORY_PRESETS` (so enums
+    /// and bools are their index). Anything that's left out stays at its factory default, the user's defaults don't
+    /// apply here. Values outside of a parameter's range get clamped to it. Returns the ID that isn't a parameter if
+    /// there is one.
+    pub fn new(values: &HashMap<String, f32>, sample_rate: f32) -> Result<Self, String> {
+        let factory = GainParams::with_defaults(&UserDefaults::default());
+        let param_map = factory.param_map();
+        let mut clamped = HashMap::new();
+        for (id, value) in values {
+            let Some((_, param, _)) = param_map.iter().find(|(other, _, _)| other == id) else {
+                return Err(id.clone());
+            };
+            // SAFETY: the pointer comes from `factory`, which is still around
+            let value = unsafe { param.preview_plain(param.preview_normalized(*value)) };
+            clamped.insert(id.clone(), value);
+        }
+
+        Ok(Self::from_params(
+            &GainParams::with_defaults(&UserDefaults::from_values(clamped)),
+            sample_rate,
+        ))
+    }
+
+    fn from_params(params: &GainParams, sample_rate: f32) -> Self {
+        let conversions = Conversions::new(true);
+        let ratio = if params.stepped_ratio.value() {
+            params.ratio_step.value().ratio()
+        } else {
+            params.ratio.value()
+        };
+
+        Self {
+            detector: Detector::default(),
+            gain_path: GainPath::default(),
+            detector_coefficients: DetectorCoefficients::new(
+                params.detector_rise_time.value(),
+                params.detector_fall_time.value(),
+                params.peak_decay.value(),
+                sample_rate,
+            ),
+            ballistics_coefficients: BallisticsCoefficients::new(
+                params.attack_time.value(),
+                params.release_time.value(),
+                params.slow_release_time.value(),
+                params.release_blend.value(),
+                params.transient_bleed.value(),
+                sample_rate,
+            ),
+            curve: GainComputer {
+                threshold: Db(params.threshold.value()),
+                ratio,
+                knee_width: Db(params.knee_width.value()),
+                knee_shape: params.knee_shape.value(),
+            },
+            smoothing: one_pole_coefficient(params.param_smoothing_time.value(), sample_rate),
+            mode: params.mode.value(),
+            level_detection: params.meter_type.value(),
+            peak_blend: params.peak_blend.value(),
+            max_boost: Db(params.max_boost.value()).to_gain(&conversions),
+            lf_hold: params.lf_hold.value(),
+            input_gain: params.input_gain.value(),
+            output_gain: params.output_gain.value(),
+            dry_wet: params.dry_wet.value(),
+            gain: 1.0,
+            conversions,
+        }
+    }
+
+    /// Processes a single stereo frame. Mono audio should go in as dual mono, like the plugin does with it.
+    pub fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        let input = frame.map(|sample| sample * self.input_gain);
+        let level = self
+            .detector
+            .process(input, &self.detector_coefficients)
+            .level(self.level_detection, self.peak_blend);
+        let curve = self.gain_path.curve(&self.curve, self.smoothing, 1);
+        let target_gain =
+            gain_path::target_gain(&curve, self.mode, level, self.max_boost, &self.conversions);
+        self.gain = self.gain_path.envelope(
+            target_gain,
+            input,
+            &self.ballistics_coefficients,
+            self.lf_hold,
+        );
+
+        input.map(|sample| {
+            let wet = sample * self.gain;
+            (sample + (wet - sample) * self.dry_wet) * self.output_gain
+        })
+    }
+
+    /// The gain the compressor applied to the last frame, before the dry/wet mix.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// The highest `gain()` these settings allow: unity when compressing at 1:1 or above, `max_boost` otherwise.
+    pub fn max_gain(&self) -> f32 {
+        if self.mode == GainComputerMode::Compress && self.curve.ratio >= 1.0 {
+            1.0
+        } else {
+            self.max_boost.0.max(1.0)
+        }
+    }
+}
+
+/// Turns normalized values (`0.0..=1.0`, like a host sends them) into settings for `OfflineCompressor::new()`, one for
+/// every parameter in the order `Params::param_map()` lists them. Parameters left over once `normalized` runs out keep
+/// their defaults. This is how the fuzz target picks its settings.
+pub fn settings_from_normalized(normalized: impl IntoIterator<Item = f32>) -> HashMap<String, f32> {
+    let factory = GainParams::with_defaults(&UserDefaults::default());
+    factory
+        .param_map()
+        .into_iter()
+        .zip(normalized)
+        .map(|((id, param, _), normalized)| {
+            // SAFETY: the pointer comes from `factory`, which is still around
+            (id, unsafe {
+                param.preview_plain(normalized.clamp(0.0, 1.0))
+            })
+        })
+        .collect()
+}
+
+/// The values of the factory preset called `name` (ignoring case), in the form `OfflineCompressor::new()` takes.
+pub fn factory_preset(name: &str) -> Option<HashMap<String, f32>> {
+    FACTORY_PRESETS
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name))
+        .map(|preset| {
+            preset
+                .values
+                .iter()
+                .map(|(id, value)| (id.to_string(), *value))
+                .collect()
+        })
+}
+
+pub fn factory_preset_names() -> impl Iterator<Item = &'static str> {
+    FACTORY_PRESETS.iter().map(|preset| preset.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    #[test]
+    fn unknown_ids_are_rejected() {
+        let values = HashMap::from([
+            (String::from("threshold"), -20.0),
+            (String::from("nope"), 1.0),
+        ]);
+        assert_eq!(
+            OfflineCompressor::new(&values, SAMPLE_RATE).err(),
+            Some(String::from("nope"))
+        );
+    }
+
+    /// A full scale sine for a second. Every preset's threshold is below that, so once it has settled (half a second
+    /// in) the output has to be quieter than the input.
+    #[test]
+    fn factory_presets_compress_a_loud_sine() {
+        let length = SAMPLE_RATE as usize;
+        for name in factory_preset_names() {
+            let values = factory_preset(name).unwrap();
+            let mut compressor = OfflineCompressor::new(&values, SAMPLE_RATE).unwrap();
+            let mut settled_peak = 0.0f32;
+            for index in 0..length {
+                let sample = (std::f32::consts::TAU * 1000.0 * index as f32 / SAMPLE_RATE).sin();
+                let [left, right] = compressor.process([sample; 2]);
+                assert!(left.is_finite() && right.is_finite(), "{name}");
+                if index >= length / 2 {
+                    settled_peak = settled_peak.max(left.abs());
+                }
+            }
+            assert!(settled_peak < 0.9, "{name}: {settled_peak}");
+        }
+    }
+}
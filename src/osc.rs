@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use nih_plug::prelude::*;
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use serde::{Deserialize, Serialize};
+
+use crate::{meter_bus::MeterBus, param_writer::ParamWriter, params::GainParams, Gain, Task};
+
+/// How often the meter readings get sent out. ~30 Hz is plenty for any kind of meter display.
+const SEND_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Where (and whether) metering data is streamed over OSC. Persisted with the rest of the plugin state.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct OscOutputSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// Whether `host` can be on another machine. Otherwise the socket only exists on the loopback interface.
+    #[serde(default)]
+    pub allow_remote: bool,
+}
+
+impl Default for OscOutputSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::from("127.0.0.1"),
+            port: 9000,
+            allow_remote: false,
+        }
+    }
+}
+
+/// Which port (if any) to listen on for OSC parameter control. Persisted with the rest of the plugin state.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct OscInputSettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// Whether other machines can send messages. Otherwise only programs on this machine can, since anyone who can
+    /// reach the port can change every parameter.
+    #[serde(default)]
+    pub allow_remote: bool,
+}
+
+impl Default for OscInputSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9001,
+            allow_remote: false,
+        }
+    }
+}
+
+/// The address to bind to: every interface if that's been opted into, otherwise just the loopback interface.
+fn bind_address(allow_remote: bool) -> IpAddr {
+    if allow_remote {
+        Ipv4Addr::UNSPECIFIED.into()
+    } else {
+        Ipv4Addr::LOCALHOST.into()
+    }
+}
+
+/// The prefix for parameter control messages. The rest of the address is the parameter's ID,
+/// e.g. `/funih/param/threshold`.
+const PARAM_ADDRESS_PREFIX: &str = "/funih/param/";
+/// How long the listener blocks waiting for a packet before checking whether it should stop or rebind.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+/// Big enough for any message we'd reasonably get, OSC over UDP doesn't really do huge packets anyway.
+const RECEIVE_BUFFER_SIZE: usize = 1536;
+
+/// A background thread that periodically sends the current meter readings as OSC messages.
+/// All readings are in decibels:
+///
+/// - `/funih/input/rms`
+/// - `/funih/input/peak`
+/// - `/funih/output/peak`
+/// - `/funih/gr` (as a positive amount of gain reduction)
+///
+/// The audio thread never talks to this directly, it only writes to the (atomic) meters.
+/// The thread is stopped when this is dropped.
+pub struct OscSender {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl OscSender {
+    pub fn spawn(params: Arc<GainParams>, meters: Arc<MeterBus>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::Builder::new()
+            .name(String::from("funih-osc-out"))
+            .spawn({
+                let stop = stop.clone();
+                move || run_sender(&params, &meters, &stop)
+            })
+            .map_err(|err| nih_error!("Could not spawn the OSC output thread: {err}"))
+            .ok();
+
+        Self { stop, handle }
+    }
+}
+
+impl Drop for OscSender {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run_sender(params: &GainParams, meters: &MeterBus, stop: &AtomicBool) {
+    // only bound while sending is enabled, along with whether it's bound to every interface
+    let mut socket: Option<(UdpSocket, bool)> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(SEND_INTERVAL);
+
+        let settings = params.osc_output.read().unwrap().clone();
+        if !settings.enabled {
+            socket = None;
+            continue;
+        }
+        if !matches!(&socket, Some((_, allow_remote)) if *allow_remote == settings.allow_remote) {
+            socket = UdpSocket::bind((bind_address(settings.allow_remote), 0))
+                .map(|socket| (socket, settings.allow_remote))
+                .map_err(|err| nih_error!("Could not bind the OSC output socket: {err}"))
+                .ok();
+        }
+        let Some((socket, _)) = &socket else {
+            continue;
+        };
+
+        let readings = [
+            ("/funih/input/rms", util::gain_to_db(meters.input_rms_max())),
+            (
+                "/funih/input/peak",
+                util::gain_to_db(meters.input_peak_max()),
+            ),
+            (
+                "/funih/output/peak",
+                util::gain_to_db(meters.output_peak_max()),
+            ),
+            (
+                "/funih/gr",
+                -util::gain_to_db(meters.gain_reduction.value()),
+            ),
+        ];
+        for (addr, value) in readings {
+            let packet = OscPacket::Message(OscMessage {
+                addr: addr.to_string(),
+                args: vec![OscType::Float(value)],
+            });
+            // a missing receiver isn't worth complaining about every 33 ms, so send errors are ignored
+            if let Ok(buffer) = encoder::encode(&packet) {
+                let _ = socket.send_to(&buffer, (settings.host.as_str(), settings.port));
+            }
+        }
+    }
+}
+
+/// A background thread that listens for OSC messages and turns them into parameter changes.
+/// Messages look like `/funih/param/<id> <value>`, where the value is the **normalized** (`0.0` to `1.0`) parameter value.
+///
+/// Only listens on the loopback interface unless `OscInputSettings::allow_remote` is set. Changes go through
+/// `ParamWriter`, so the host sees them as regular gestures, and get applied on the GUI thread through `executor`.
+/// That also means nothing goes through before the editor has been opened once.
+/// The thread is stopped when this is dropped.
+pub struct OscReceiver {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl OscReceiver {
+    /// Without an `executor` the changes wait for the editor to apply them.
+    pub fn spawn(
+        params: Arc<GainParams>,
+        writer: ParamWriter,
+        executor: Option<AsyncExecutor<Gain>>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::Builder::new()
+            .name(String::from("funih-osc-in"))
+            .spawn({
+                let stop = stop.clone();
+                move || run_receiver(&params, &writer, executor.as_ref(), &stop)
+            })
+            .map_err(|err| nih_error!("Could not spawn the OSC input thread: {err}"))
+            .ok();
+
+        Self { stop, handle }
+    }
+}
+
+impl Drop for OscReceiver {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run_receiver(
+    params: &GainParams,
+    writer: &ParamWriter,
+    executor: Option<&AsyncExecutor<Gain>>,
+    stop: &AtomicBool,
+) {
+    let param_ptrs: HashMap<String, ParamPtr> = params
+        .param_map()
+        .into_iter()
+        .map(|(id, ptr, _group)| (id, ptr))
+        .collect();
+    let mut buffer = [0u8; RECEIVE_BUFFER_SIZE];
+    // the socket and the port and interfaces it's bound to, only while listening is enabled
+    let mut socket: Option<(UdpSocket, u16, bool)> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        let settings = params.osc_input.read().unwrap().clone();
+        if !settings.enabled {
+            socket = None;
+            thread::sleep(RECEIVE_TIMEOUT);
+            continue;
+        }
+        let bound_to = (settings.port, settings.allow_remote);
+        if !matches!(&socket, Some((_, port, allow_remote)) if (*port, *allow_remote) == bound_to) {
+            // close the old socket first, in case it's bound to the same port
+            drop(socket.take());
+            socket = UdpSocket::bind((bind_address(settings.allow_remote), settings.port))
+                .and_then(|socket| {
+                    socket.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+                    Ok((socket, settings.port, settings.allow_remote))
+                })
+                .map_err(|err| {
+                    nih_error!("Could not listen for OSC on port {}: {err}", settings.port)
+                })
+                .ok();
+        }
+        let Some((socket, _, _)) = &socket else {
+            thread::sleep(RECEIVE_TIMEOUT);
+            continue;
+        };
+
+        // timeouts end up here too, and that's fine
+        let Ok(size) = socket.recv(&mut buffer) else {
+            continue;
+        };
+        match decoder::decode_udp(&buffer[..size]) {
+            Ok((_, packet)) => {
+                handle_packet(packet, &param_ptrs, writer);
+                if let Some(executor) = executor {
+                    executor.execute_gui(Task::FlushParamChanges);
+                }
+            }
+            Err(err) => nih_log!("Received a malformed OSC packet: {err:?}"),
+        }
+    }
+}
+
+fn handle_packet(packet: OscPacket, param_ptrs: &HashMap<String, ParamPtr>, writer: &ParamWriter) {
+    match packet {
+        OscPacket::Message(message) => {
+            let Some(param) = message
+                .addr
+                .strip_prefix(PARAM_ADDRESS_PREFIX)
+                .and_then(|id| param_ptrs.get(id))
+            else {
+                return;
+            };
+            let value = match message.args.first() {
+                Some(OscType::Float(value)) => *value,
+                Some(OscType::Double(value)) => *value as f32,
+                Some(OscType::Int(value)) => *value as f32,
+                _ => return,
+            };
+            if !writer.set_normalized(*param, value) {
+                nih_log!("Ignoring OSC parameter change, the editor hasn't been opened yet");
+                writer.report_missed(format!("OSC message to {}", message.addr));
+            }
+        }
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                handle_packet(packet, param_ptrs, writer);
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use nih_plug::prelude::*;
+use nih_plug_egui::EguiState;
+
+/// The host's context, as handed to the editor. See `ParamWriter::attach()`.
+type SharedContext = Arc<Mutex<Option<Arc<dyn GuiContext>>>>;
+
+/// How many batches can be waiting at once. They get applied as soon as the GUI thread gets around to it, so this only
+/// fills up if it stops doing that.
+const MAX_PENDING: usize = 256;
+
+/// Lets code outside of the editor (OSC, MIDI learn, presets, ...) change parameters the same way the editor does,
+/// wrapped in begin/end gestures so the host records the changes like any other automation.
+///
+/// Hosts expect those gestures to come from the GUI thread, so changes get queued here and applied from there: by the
+/// editor the next time it draws (see `drain()`), or by whoever ran on the GUI thread to make the change, see
+/// `flush()`. nih-plug only hands out a `GuiContext` when the editor gets opened, so the writer holds on to that one
+/// (see `attach()`) and keeps using it after the editor is closed again. The context keeps the plugin alive, so it gets
+/// let go of in `Plugin::deactivate()`, which hosts always call before getting rid of the plugin.
+///
+/// That leaves the time before the editor has been opened (since the plugin was last activated), where nothing can go
+/// through and `apply()` returns `false`. Whoever got turned away should `report_missed()` it, so the editor can tell
+/// the user once it's opened.
+///
+/// Everything that changes more than one parameter at once (presets, scenes, SysEx, undo) should go through a
+/// `ParamBatch`, so the host sees all of the gestures start before any value changes, and another writer (say, OSC on
+/// its own thread) can't end up halfway through it.
+#[derive(Clone)]
+pub struct ParamWriter {
+    editor_state: Arc<EguiState>,
+    pending: Arc<Mutex<VecDeque<ParamBatch>>>,
+    context: SharedContext,
+    missed: Arc<Mutex<Missed>>,
+}
+
+/// The changes that couldn't be applied because the editor hadn't been opened yet, see `ParamWriter::report_missed()`.
+#[derive(Default)]
+struct Missed {
+    count: usize,
+    last: Option<String>,
+}
+
+impl ParamWriter {
+    pub fn new(editor_state: Arc<EguiState>) -> Self {
+        Self {
+            editor_state,
+            pending: Arc::new(Mutex::new(VecDeque::new())),
+            context: Arc::new(Mutex::new(None)),
+            missed: Arc::new(Mutex::new(Missed::default())),
+        }
+    }
+
+    /// Holds on to the host's context, so changes keep going through after the editor is closed. The editor calls this
+    /// every time it's opened.
+    pub fn attach(&self, context: Arc<dyn GuiContext>) {
+        *self.context.lock().unwrap() = Some(context);
+    }
+
+    /// Lets go of the context again, so the plugin can be dropped. Only `Plugin::deactivate()` should call this.
+    pub fn detach(&self) {
+        *self.context.lock().unwrap() = None;
+    }
+
+    /// Whether changes can go through right now, i.e. whether the editor is open or has been opened since the plugin
+    /// was last activated.
+    pub fn is_available(&self) -> bool {
+        self.editor_state.is_open() || self.context.lock().unwrap().is_some()
+    }
+
+    /// Sets a single parameter as one gesture. Returns `false` if there's no way to do that yet.
+    pub fn set_normalized(&self, param: ParamPtr, normalized: f32) -> bool {
+        let mut batch = ParamBatch::default();
+        batch.set_normalized(param, normalized);
+        self.apply(batch)
+    }
+
+    /// Queues every change in `batch` to be applied in one go. Returns `false` (without changing anything) if there's
+    /// no way to do that yet.
+    pub fn apply(&self, batch: ParamBatch) -> bool {
+        if !self.is_available() {
+            return false;
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= MAX_PENDING {
+            return false;
+        }
+        pending.push_back(batch);
+        true
+    }
+
+    /// The last value queued for `param` that hasn't been applied yet, if there is one.
+    pub fn pending_normalized(&self, param: ParamPtr) -> Option<f32> {
+        let pending = self.pending.lock().unwrap();
+        pending.iter().rev().find_map(|batch| {
+            batch
+                .changes
+                .iter()
+                .find(|(other, _)| *other == param)
+                .map(|(_, normalized)| *normalized)
+        })
+    }
+
+    /// Remembers that `description` (say, "Scene 2") couldn't be applied. This allocates, so the audio thread shouldn't
+    /// call it.
+    pub fn report_missed(&self, description: impl Into<String>) {
+        let mut missed = self.missed.lock().unwrap();
+        missed.count += 1;
+        missed.last = Some(description.into());
+    }
+
+    /// How many changes have been missed since the last `clear_missed()`, and what the last one was.
+    pub fn missed(&self) -> Option<(usize, String)> {
+        let missed = self.missed.lock().unwrap();
+        missed.last.clone().map(|last| (missed.count, last))
+    }
+
+    pub fn clear_missed(&self) {
+        *self.missed.lock().unwrap() = Missed::default();
+    }
+
+    /// Applies everything that's been queued up, in order. Only the editor calls this, from the GUI thread.
+    pub fn drain(&self, setter: &ParamSetter) {
+        let pending = mem::take(&mut *self.pending.lock().unwrap());
+        for batch in pending {
+            batch.apply_with_setter(setter);
+        }
+    }
+
+    /// Applies everything that's been queued up through the context from `attach()`, whether the editor is open or
+    /// not. This has to be called from the GUI thread, in practice from a task that was started with
+    /// `ProcessContext::execute_gui()`. Does nothing if there's no context to do it with, the queue is left for the
+    /// editor then.
+    pub fn flush(&self) {
+        let Some(context) = self.context.lock().unwrap().clone() else {
+            return;
+        };
+        let pending = mem::take(&mut *self.pending.lock().unwrap());
+        for batch in pending {
+            batch.apply_with_context(context.as_ref());
+        }
+    }
+}
+
+/// A set of parameter changes that get applied together, either through a `ParamWriter` or from the editor with
+/// `apply_with_setter()`. Every gesture begins before the first value changes and ends after the last one, so hosts
+/// that record automation see a single edit instead of a string of separate ones.
+#[derive(Default)]
+pub struct ParamBatch {
+    /// Normalized values. Setting the same parameter twice replaces the earlier value.
+    changes: Vec<(ParamPtr, f32)>,
+}
+
+impl ParamBatch {
+    pub fn set_normalized(&mut self, param: ParamPtr, normalized: f32) {
+        let normalized = normalized.clamp(0.0, 1.0);
+        match self.changes.iter_mut().find(|(other, _)| *other == param) {
+            Some(change) => change.1 = normalized,
+            None => self.changes.push((param, normalized)),
+        }
+    }
+
+    /// Sets a parameter to a plain (unnormalized) value.
+    pub fn set_plain(&mut self, param: ParamPtr, plain: f32) {
+        // SAFETY: the pointers come from our own `GainParams`, which lives as long as the plugin does
+        let normalized = unsafe { param.preview_normalized(plain) };
+        self.set_normalized(param, normalized);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Applies the batch from the editor, through its `ParamSetter`.
+    pub fn apply_with_setter(&self, setter: &ParamSetter) {
+        self.apply_with_context(setter.raw_context);
+    }
+
+    fn apply_with_context(&self, context: &dyn GuiContext) {
+        if self.is_empty() {
+            return;
+        }
+
+        // SAFETY: same as above
+        unsafe {
+            for (param, _) in &self.changes {
+                context.raw_begin_set_parameter(*param);
+            }
+            for (param, normalized) in &self.changes {
+                context.raw_set_parameter_normalized(*param, *normalized);
+            }
+            for (param, _) in self.changes.iter().rev() {
+                context.raw_end_set_parameter(*param);
+            }
+        }
+    }
+}
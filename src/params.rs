@@ -1,31 +1,161 @@
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
 
 use nih_plug::{
     formatters::{self, v2s_f32_rounded},
-    params::{EnumParam, FloatParam, Params},
-    prelude::{FloatRange, SmoothingStyle},
+    params::{BoolParam, EnumParam, FloatParam, IntParam, Params},
+    prelude::{FloatRange, IntRange, SmoothingStyle},
     util,
 };
 
-use crate::LevelDetection;
+use nih_plug_egui::EguiState;
+
+use crate::{
+    detector::{DetectorChannels, RMS_WINDOW},
+    editor::{self, theme::Theme, MeterLayout},
+    gain_computer::KneeShape,
+    headroom::InputPad,
+    link::{LinkMode, LINK_GROUP_COUNT},
+    loudness::LoudnessReport,
+    midi_learn::CcMapping,
+    modulation::{LfoShape, ModTarget},
+    osc::{OscInputSettings, OscOutputSettings},
+    scenes::{Scene, SCENE_COUNT},
+    session_stats::SessionStats,
+    sidechain::SidechainChannels,
+    state::CURRENT_STATE_VERSION,
+    user_defaults::UserDefaults,
+    DbConversion, GainComputerMode, LevelDetection, Quality, RatioStep,
+};
+
+/// Parameters that aren't settings: the outputs the plugin writes itself (`gr_output` and `latency_output`), and the
+/// scene selector (restoring that would switch scenes on top of the values that were just restored). Scenes, SysEx
+/// dumps and the user defaults all leave these out.
+pub const NON_SETTING_PARAM_IDS: [&str; 3] = ["groutput", "latency", "scene"];
 
 pub const DEFAULT_THRESHOLD: f32 = -10.0;
+/// How long the peak detector takes to fall back down, in seconds.
+pub const DEFAULT_PEAK_DECAY: f32 = 0.1;
 pub const DEFAULT_RATIO: f32 = 4.0;
+pub const DEFAULT_STARTUP_RAMP: f32 = 0.05;
+/// Anything below 1:1 expands above the threshold instead of compressing.
+pub const MIN_RATIO: f32 = 0.5;
 pub const DEFAULT_KNEE: f32 = 5.0;
 pub const DEFAULT_ATTACK_TIME: f32 = 0.001;
 pub const DEFAULT_RELEASE_TIME: f32 = 0.05;
+pub const DEFAULT_SLOW_RELEASE_TIME: f32 = 1.0;
+pub const DEFAULT_GATE_RELEASE_TIME: f32 = 0.02;
+pub const DEFAULT_MAX_BOOST: f32 = 6.0;
+pub const DEFAULT_PARAM_SMOOTHING_TIME: f32 = 0.01;
+pub const DEFAULT_GR_DISPLAY_SMOOTHING: f32 = 0.15;
+/// The block sizes the editor offers for `GainParams::block_size`. The internal buffers are `MAX_BUFFER_SIZE` long,
+/// so that's as large as it gets.
+pub const BLOCK_SIZES: [usize; 4] = [8, 16, 32, fundsp::MAX_BUFFER_SIZE];
+pub const DEFAULT_BLOCK_SIZE: usize = fundsp::MAX_BUFFER_SIZE;
+/// The most gain reduction `GainParams::gr_output` can show, in dB.
+pub const MAX_GR_OUTPUT_DB: f32 = 48.0;
+/// The most latency `GainParams::latency_output` can show, in milliseconds: the RMS compensation plus the longest
+/// sidechain offset.
+pub const MAX_LATENCY_OUTPUT_MS: f32 = (RMS_WINDOW / 2.0 + MAX_SIDECHAIN_OFFSET) * 1000.0;
+/// How far the sidechain can be moved in either direction, in seconds.
+pub const MAX_SIDECHAIN_OFFSET: f32 = 0.02;
 
 #[derive(Params)]
 pub struct GainParams {
+    /// The editor's window size. This is persisted so the editor reopens at whatever size the user last left it at.
+    #[persist = "editor-state"]
+    pub editor_state: Arc<EguiState>,
+    /// The editor's color scheme, persisted alongside the window size.
+    #[persist = "editor-theme"]
+    pub theme: RwLock<Theme>,
+    /// Where metering data gets sent over OSC, if at all.
+    #[persist = "osc-output"]
+    pub osc_output: RwLock<OscOutputSettings>,
+    /// Which port to listen on for OSC parameter control, if at all.
+    #[persist = "osc-input"]
+    pub osc_input: RwLock<OscInputSettings>,
+    /// MIDI CC to parameter mappings created with MIDI learn.
+    #[persist = "midi-cc-mappings"]
+    pub cc_mappings: RwLock<Vec<CcMapping>>,
+    /// The output loudness of the most recent offline render, so it's still there after reloading the project.
+    #[persist = "loudness-report"]
+    pub loudness_report: RwLock<Option<LoudnessReport>>,
+    /// How hard the plugin has worked across the whole project, see `SessionStats`. Reset from the editor.
+    #[persist = "session-stats"]
+    pub session_stats: RwLock<SessionStats>,
+    /// The name of the factory preset that was loaded last, which the editor compares the current settings against.
+    #[persist = "loaded-preset"]
+    pub loaded_preset: RwLock<Option<String>>,
+    /// How the editor lays out the meters, see `MeterLayout`.
+    #[persist = "meter-layout"]
+    pub meter_layout: RwLock<MeterLayout>,
+    /// How much the GR meter's readout gets smoothed in the editor, in seconds. Doesn't affect the audio at all.
+    #[persist = "gr-display-smoothing"]
+    pub gr_display_smoothing: RwLock<f32>,
+    /// The longest stretch of audio the DSP processes at once, in samples. The gain gets updated on every sample
+    /// regardless, except with `Quality::Eco` (and while the gain reduction is frozen, for the link group), where it's
+    /// once per block. Otherwise this only changes how often the meters and `gr_output` get published, and how much
+    /// CPU the per-block work costs. Gets validated (and only picked up) in `initialize()`, see `BLOCK_SIZES`.
+    #[persist = "block-size"]
+    pub block_size: RwLock<usize>,
+    /// The version of the state format this was saved with. Older states get migrated when loading, see `state.rs`.
+    #[persist = "state-version"]
+    pub state_version: RwLock<u32>,
+    /// Parameter snapshots the `scene` parameter switches between, stored from the editor.
+    #[persist = "scenes"]
+    pub scenes: RwLock<[Option<Scene>; SCENE_COUNT]>,
+    /// The IDs of the parameters that loading a preset or a material should leave alone. Anything else that changes
+    /// a whole bunch of parameters at once should skip these too.
+    #[persist = "locked-params"]
+    pub locked_params: RwLock<HashSet<String>>,
+    /// Links this instance with every other instance in the same group (in the same host process), `0` is off. Lets
+    /// compressors on separate stems react together, see `LinkMode`.
+    #[id = "linkgroup"]
+    pub link_group: IntParam,
+    /// What gets shared within the link group.
+    #[id = "linkmode"]
+    pub link_mode: EnumParam<LinkMode>,
+    /// Switches to one of the stored `scenes` whenever it changes, for jumping between setups live. `0` is off.
+    #[id = "scene"]
+    pub scene: IntParam,
+    /// Whether the plugin compresses or expands upwards above the threshold. See `GainComputerMode`.
+    #[id = "mode"]
+    pub mode: EnumParam<GainComputerMode>,
+    /// The most the gain computer is allowed to boost **in decibels**, when expanding upwards or with a ratio below 1:1.
+    #[id = "maxboost"]
+    pub max_boost: FloatParam,
+    /// How far the output gets tilted as the gain reduction goes up, **in decibels**, reached at 12 dB of gain
+    /// reduction. Positive values brighten the compressed signal, negative ones darken it, `0.0` turns it off.
+    #[id = "tonedepth"]
+    pub tone_depth: FloatParam,
+    /// How *the level* of the incoming signal is measured. See `LevelDetection`.
     #[id = "lvldetection"]
     pub meter_type: EnumParam<LevelDetection>,
+    /// How much of the peak level gets mixed into the RMS level, `0.0` being pure RMS.
+    /// Only used with RMS level detection.
+    #[id = "peakblend"]
+    pub peak_blend: FloatParam,
+    /// How long the peak detector takes to fall back down after a peak, in seconds. Independent from the RMS window,
+    /// so peak detection can go from catching every single peak to a slow, VU-like reading. Affects `peak_blend` too.
+    #[id = "peakdecay"]
+    pub peak_decay: FloatParam,
+    /// The level **in decibels** above which compression is applied.
     #[id = "threshold"]
     pub threshold: FloatParam,
-    /// The compression ratio as the left side of the ratio **in decibels**.
+    /// The compression ratio as the left side of the ratio. It's a plain slope without a unit, so `4.0` means 4:1.
     /// For example, a ratio of `2.0` would be equivalent to a ratio of 2:1,
     /// which means that for every 2db that *the level* is above the `threshold`, 1db will pass through.
+    /// Ratios below `1.0` expand instead: `0.5` (displayed as 1:2) turns every 1db above the threshold into 2db.
     #[id = "ratio"]
     pub ratio: FloatParam,
+    /// When enabled, `ratio_step` is used instead of `ratio`.
+    #[id = "ratiostepped"]
+    pub stepped_ratio: BoolParam,
+    /// The ratio as a hardware-style switch, see `RatioStep`. Only used when `stepped_ratio` is enabled.
+    #[id = "ratiostep"]
+    pub ratio_step: EnumParam<RatioStep>,
     /// The time it takes before the compressor starts compressing after *the level* is above the threshold.
     ///
     /// **NOTE**: The actual underlying value is the filter coefficient for the compressor, however the value is converted and displayed in (milli)seconds.
@@ -36,13 +166,33 @@ pub struct GainParams {
     /// **NOTE**: The actual underlying value is the release filter coefficient for the compressor, however the value is converted and displayed in (milli)seconds.
     #[id = "release"]
     pub release_time: FloatParam,
+    /// The second, slower release stage. Only heard when `release_blend` is above 0%.
+    #[id = "slowrelease"]
+    pub slow_release_time: FloatParam,
+    /// Blends between the regular (fast) release at 0% and the slow release at 100%.
+    #[id = "releaseblend"]
+    pub release_blend: FloatParam,
+    /// Holds the release until the audio crosses zero, so fast releases don't distort bass. See `GainEnvelope`.
+    #[id = "lfhold"]
+    pub lf_hold: BoolParam,
+    /// How much of the gain reduction is held back right as an attack starts, letting part of the transient through.
+    /// This fades out over a few milliseconds, independently of the attack time.
+    #[id = "bleed"]
+    pub transient_bleed: FloatParam,
     /// The knee width **in decibels**. This smooths the transition between compression and no compression around the threshold.
     /// If you'd like a *hard-knee compressor*, set this value to `0.0`.
     #[id = "knee"]
     pub knee_width: FloatParam,
+    /// How the knee gets from 1:1 to the full ratio, see `KneeShape`.
+    #[id = "kneeshape"]
+    pub knee_shape: EnumParam<KneeShape>,
     /// Modify the gain of the incoming signal ***before*** compression.
     #[id = "ingain"]
     pub input_gain: FloatParam,
+    /// Pads the input before the compressor when it's been above 0 dBFS for a while, and makes up for it afterwards.
+    /// See `InputPad`.
+    #[id = "inpad"]
+    pub input_pad: EnumParam<InputPad>,
     /// Modify the gain of the incoming signal ***after*** compression ***AND*** after dry/wet has been applied.
     #[id = "outgain"]
     pub output_gain: FloatParam,
@@ -51,18 +201,236 @@ pub struct GainParams {
     /// while `0.0` (0%) means that essentially, no compression is applied.  
     #[id = "drywet"]
     pub dry_wet: FloatParam,
+    /// How quickly the level detector's reading is allowed to rise. This is separate from the attack time:
+    /// it shapes *the level* the compressor sees, rather than how fast the compressor responds to it.
+    /// `0.0` means the detector's raw reading is used as-is.
+    #[id = "detrise"]
+    pub detector_rise_time: FloatParam,
+    /// How quickly the level detector's reading is allowed to fall. Longer times make the compressor
+    /// "hold on" to sustained material.
+    #[id = "detfall"]
+    pub detector_fall_time: FloatParam,
+    /// Whether the detector weights below apply to left and right, or to mid and side.
+    #[id = "detchannels"]
+    pub detector_channels: EnumParam<DetectorChannels>,
+    /// How much the left (or mid) channel counts towards the detector's reading. Lets one side of an asymmetric
+    /// stereo source (piano, drum overheads) drive the compression without unlinking the channels.
+    #[id = "detweight1"]
+    pub detector_weight_a: FloatParam,
+    /// Same as `detector_weight_a`, for the right (or side) channel.
+    #[id = "detweight2"]
+    pub detector_weight_b: FloatParam,
+    /// How long the processing takes to fade in after the plugin starts or gets reset, in seconds. Keeps the first
+    /// buffers, while the detector is still warming up, from ending in an audible clamp. `0.0` turns it off.
+    #[id = "startupramp"]
+    pub startup_ramp: FloatParam,
+    /// How long the threshold, ratio and knee take to follow changes (and automation), in seconds.
+    #[id = "paramsmooth"]
+    pub param_smoothing_time: FloatParam,
+    /// Eco/normal/HQ processing, see `Quality`. Safe to switch while playing.
+    #[id = "quality"]
+    pub quality: EnumParam<Quality>,
+    /// Delays the audio by half the RMS window so the gain reduction lines up with the energy the RMS detector is
+    /// reacting to. Only does anything with RMS level detection, and adds that much latency.
+    #[id = "rmscomp"]
+    pub rms_compensation: BoolParam,
+    /// Whether the gain computer uses exact or fast decibel conversions. See `DbConversion`.
+    #[id = "dbconv"]
+    pub db_conversion: EnumParam<DbConversion>,
+    /// When enabled, the output is only let through while a MIDI note is held,
+    /// turning the plugin into a gate that can be played from a keyboard.
+    #[id = "notegate"]
+    pub note_gate: BoolParam,
+    /// How long the note gate takes to close after the last MIDI note is released.
+    #[id = "gaterelease"]
+    pub gate_release_time: FloatParam,
+    /// When enabled, the level detector listens to the sidechain input instead of the main input.
+    #[id = "sidechain"]
+    pub sidechain: BoolParam,
+    /// Whether the detector gets the sidechain in stereo, as a mono sum, or just one side of it.
+    #[id = "scchannels"]
+    pub sidechain_channels: EnumParam<SidechainChannels>,
+    /// Trims the sidechain before it reaches the detector. Unlike `input_gain`, this never touches the program material.
+    #[id = "scgain"]
+    pub sidechain_gain: FloatParam,
+    /// Flips the sidechain's polarity. Detection only looks at the level, so this won't change the gain reduction
+    /// by itself, it matters once the sidechain gets listened to or mixed with something else.
+    #[id = "scinvert"]
+    pub sidechain_invert: BoolParam,
+    /// Moves the sidechain in time relative to the audio, in seconds. Positive values delay a key that arrives
+    /// early, negative values delay the audio instead for a key that arrives late (which adds that much latency).
+    #[id = "scoffset"]
+    pub sidechain_offset: FloatParam,
+    /// Locks the gain reduction at its current value until this is turned off again.
+    #[id = "freezegr"]
+    pub freeze_gain_reduction: BoolParam,
+    /// The current gain reduction in dB (so `0.0` or less), written by the plugin itself while `gr_output_write` is
+    /// enabled and the host is playing. Recording this as automation turns the compression into volume automation.
+    /// Setting it by hand doesn't do anything.
+    #[id = "groutput"]
+    pub gr_output: FloatParam,
+    /// Whether the plugin keeps writing `gr_output`. Off by default, so it doesn't fill up the host's automation lanes.
+    #[id = "groutputwrite"]
+    pub gr_output_write: BoolParam,
+    /// The latency currently reported to the host, in milliseconds. Written by the plugin itself whenever it changes,
+    /// so hosts can show it next to the other parameters. Setting it by hand doesn't do anything.
+    #[id = "latency"]
+    pub latency_output: FloatParam,
+    /// Holds the gate open no matter what notes are held, for punching sections in manually.
+    #[id = "forceopen"]
+    pub force_open: BoolParam,
+    /// Holds the gate closed, even if the note gate is off. Takes priority over `force_open`.
+    #[id = "forceclosed"]
+    pub force_closed: BoolParam,
+    /// The first LFO's speed, in Hz. See `modulation::Modulation` for how the LFOs and the envelope follower get
+    /// applied.
+    #[id = "lfo1rate"]
+    pub lfo1_rate: FloatParam,
+    #[id = "lfo1shape"]
+    pub lfo1_shape: EnumParam<LfoShape>,
+    #[id = "lfo1target"]
+    pub lfo1_target: EnumParam<ModTarget>,
+    /// How far the LFO moves its target, as a fraction of the target's modulation range. Negative values flip it.
+    #[id = "lfo1depth"]
+    pub lfo1_depth: FloatParam,
+    #[id = "lfo2rate"]
+    pub lfo2_rate: FloatParam,
+    #[id = "lfo2shape"]
+    pub lfo2_shape: EnumParam<LfoShape>,
+    #[id = "lfo2target"]
+    pub lfo2_target: EnumParam<ModTarget>,
+    #[id = "lfo2depth"]
+    pub lfo2_depth: FloatParam,
+    /// How quickly the envelope follower falls back down after the input gets quieter, in seconds.
+    #[id = "envrelease"]
+    pub env_release: FloatParam,
+    #[id = "envtarget"]
+    pub env_target: EnumParam<ModTarget>,
+    /// How far the envelope follower moves its target. It only goes from 0 up, so negative depths move the target down.
+    #[id = "envdepth"]
+    pub env_depth: FloatParam,
 }
 
 impl GainParams {
     pub fn new() -> Self {
+        Self::with_defaults(&UserDefaults::load())
+    }
+
+    /// Starts every parameter out at `defaults` instead of the user's saved defaults, see `offline`.
+    pub fn with_defaults(defaults: &UserDefaults) -> Self {
         Self {
+            editor_state: editor::default_state(),
+            theme: RwLock::new(Theme::default()),
+            osc_output: RwLock::new(OscOutputSettings::default()),
+            osc_input: RwLock::new(OscInputSettings::default()),
+            cc_mappings: RwLock::new(Vec::new()),
+            loudness_report: RwLock::new(None),
+            session_stats: RwLock::new(SessionStats::default()),
+            loaded_preset: RwLock::new(None),
+            meter_layout: RwLock::new(MeterLayout::default()),
+            gr_display_smoothing: RwLock::new(DEFAULT_GR_DISPLAY_SMOOTHING),
+            block_size: RwLock::new(DEFAULT_BLOCK_SIZE),
+            state_version: RwLock::new(CURRENT_STATE_VERSION),
+            scenes: RwLock::new(Default::default()),
+            locked_params: RwLock::new(HashSet::new()),
             // Persisted fields can be initialized like any other fields, and they'll keep their
             // values when restoring the plugin's state.
-            meter_type: EnumParam::new("Level Detection", LevelDetection::Rms),
+            link_group: IntParam::new(
+                "Link Group",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: LINK_GROUP_COUNT as i32,
+                },
+            )
+            .with_value_to_string(Arc::new(|group| match group {
+                0 => "Off".to_string(),
+                group => format!("Group {group}"),
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                let string = string.trim().to_lowercase();
+                if string == "off" {
+                    Some(0)
+                } else {
+                    string.trim_start_matches("group").trim().parse().ok()
+                }
+            })),
+            link_mode: EnumParam::new(
+                "Link Mode",
+                defaults.enumeration("linkmode", LinkMode::Detector),
+            ),
+            scene: IntParam::new(
+                "Scene",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: SCENE_COUNT as i32,
+                },
+            )
+            .with_value_to_string(Arc::new(|scene| match scene {
+                0 => "Off".to_string(),
+                scene => format!("Scene {scene}"),
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                let string = string.trim().to_lowercase();
+                if string == "off" {
+                    Some(0)
+                } else {
+                    string.trim_start_matches("scene").trim().parse().ok()
+                }
+            })),
+            mode: EnumParam::new(
+                "Mode",
+                defaults.enumeration("mode", GainComputerMode::Compress),
+            ),
+            max_boost: FloatParam::new(
+                "Max Boost",
+                defaults.float("maxboost", DEFAULT_MAX_BOOST),
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(v2s_f32_rounded(1))
+            .with_string_to_value(s2v_db()),
+            tone_depth: FloatParam::new(
+                "Dynamic Tone",
+                defaults.float("tonedepth", 0.0),
+                FloatRange::Linear {
+                    min: -6.0,
+                    max: 6.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(v2s_f32_rounded(1))
+            .with_string_to_value(s2v_db()),
+            meter_type: EnumParam::new(
+                "Level Detection",
+                defaults.enumeration("lvldetection", LevelDetection::Rms),
+            ),
+            peak_blend: FloatParam::new(
+                "Peak Blend",
+                defaults.float("peakblend", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(v2s_rounded_multiplied(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            peak_decay: FloatParam::new(
+                "Peak Hold/Decay",
+                defaults.float("peakdecay", DEFAULT_PEAK_DECAY),
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 2.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
             // THRESHOLD
             threshold: FloatParam::new(
                 "Threshold",
-                DEFAULT_THRESHOLD,
+                defaults.float("threshold", DEFAULT_THRESHOLD),
                 FloatRange::Skewed {
                     min: -100.0,
                     max: 5.0,
@@ -70,33 +438,34 @@ impl GainParams {
                 },
             )
             // our threshold is already in dB land, so we don't need any conversion/formatting
-            // TODO: play with smoothing style/timing
-            .with_smoother(SmoothingStyle::Linear(10.0))
+            // smoothing happens in the plugin, see `param_smoothing_time`
             .with_unit(" dB")
             // TODO:
             // create a custom formatter for -inf dB
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
-            // TODO:
-            // do we need string_to_value..?
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(s2v_db()),
 
             // RATIO
             ratio: FloatParam::new(
                 "Ratio",
-                DEFAULT_RATIO, // default compression ratio of 4:1 dB
+                defaults.float("ratio", DEFAULT_RATIO), // default compression ratio of 4:1
                 FloatRange::Skewed {
-                    min: 1.0,
+                    min: MIN_RATIO,
                     max: 100.0,
                     factor: FloatRange::skew_factor(-1.8),
                 },
             )
-            .with_smoother(SmoothingStyle::Linear(10.0))
-            // TODO: customize formatter
-            .with_value_to_string(formatters::v2s_compression_ratio(2))
-            .with_unit(" dB"),
+            .with_value_to_string(v2s_ratio(2))
+            .with_string_to_value(s2v_ratio()),
+            stepped_ratio: BoolParam::new("Stepped Ratio", defaults.bool("ratiostepped", false)),
+            ratio_step: EnumParam::new(
+                "Ratio Step",
+                defaults.enumeration("ratiostep", RatioStep::Four),
+            ),
             // ATTACK TIME
             attack_time: FloatParam::new(
                 "Attack Time",
-                DEFAULT_ATTACK_TIME,
+                defaults.float("attack", DEFAULT_ATTACK_TIME),
                 FloatRange::Skewed {
                     min: 0.0, // 0 seconds atk time, meaning the compressor takes effect instantly
                     max: 1.0,
@@ -104,11 +473,12 @@ impl GainParams {
                 },
             )
             .with_smoother(SmoothingStyle::Linear(10.0))
-            .with_value_to_string(v2s_time_formatter()),
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
             // RELEASE
             release_time: FloatParam::new(
                 "Release Time",
-                DEFAULT_RELEASE_TIME,
+                defaults.float("release", DEFAULT_RELEASE_TIME),
                 FloatRange::Skewed {
                     min: 0.0,
                     max: 5.0,
@@ -116,25 +486,59 @@ impl GainParams {
                 },
             )
             .with_smoother(SmoothingStyle::Linear(10.0))
-            .with_value_to_string(v2s_time_formatter()),
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            slow_release_time: FloatParam::new(
+                "Slow Release",
+                defaults.float("slowrelease", DEFAULT_SLOW_RELEASE_TIME),
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 5.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            release_blend: FloatParam::new(
+                "Release Blend",
+                defaults.float("releaseblend", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(v2s_rounded_multiplied(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            lf_hold: BoolParam::new("LF Hold", defaults.bool("lfhold", false)),
+            transient_bleed: FloatParam::new(
+                "Transient Bleed",
+                defaults.float("bleed", 0.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(v2s_rounded_multiplied(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
             // KNEE WIDTH
             knee_width: FloatParam::new(
                 "Knee Width",
-                DEFAULT_KNEE,
+                defaults.float("knee", DEFAULT_KNEE),
                 FloatRange::Linear {
                     min: 0.0,
                     max: 20.0,
                 },
             )
-            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_unit(" dB")
-            .with_value_to_string(v2s_f32_rounded(1)),
+            .with_value_to_string(v2s_f32_rounded(1))
+            .with_string_to_value(s2v_db()),
+            knee_shape: EnumParam::new(
+                "Knee Shape",
+                defaults.enumeration("kneeshape", KneeShape::Quadratic),
+            ),
             // INPUT GAIN
             // basically, the exact same as this. LOL
             // https://github.com/robbert-vdh/nih-plug/blob/ffe9b61fcb0441c9d33f4413f5ebe7394637b21f/plugins/examples/gain/src/lib.rs#L67
             input_gain: FloatParam::new(
                 "Input Gain",
-                util::db_to_gain(0.0),
+                defaults.float("ingain", util::db_to_gain(0.0)),
                 FloatRange::Skewed {
                     min: util::db_to_gain(-30.0),
                     max: util::db_to_gain(30.0),
@@ -152,10 +556,11 @@ impl GainParams {
             // `.with_step_size(0.1)` function to get internal rounding.
             .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
             .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            input_pad: EnumParam::new("Input Pad", defaults.enumeration("inpad", InputPad::Off)),
             // OUTPUT GAIN
             output_gain: FloatParam::new(
                 "Output Gain",
-                util::db_to_gain(0.0),
+                defaults.float("outgain", util::db_to_gain(0.0)),
                 FloatRange::Skewed {
                     min: util::db_to_gain(-30.0),
                     max: util::db_to_gain(30.0),
@@ -167,11 +572,231 @@ impl GainParams {
             .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
             .with_string_to_value(formatters::s2v_f32_gain_to_db()),
             // DRY/WET
-            dry_wet: FloatParam::new("Dry/Wet", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 }) // 1.0 default for full compressor effect
-                .with_smoother(SmoothingStyle::Linear(10.0))
-                .with_value_to_string(v2s_rounded_multiplied(1)),
+            dry_wet: FloatParam::new(
+                "Dry/Wet",
+                defaults.float("drywet", 1.0),
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ) // 1.0 default for full compressor effect
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(v2s_rounded_multiplied(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            // DETECTOR RISE/FALL
+            detector_rise_time: FloatParam::new(
+                "Detector Rise",
+                defaults.float("detrise", 0.0),
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            detector_fall_time: FloatParam::new(
+                "Detector Fall",
+                defaults.float("detfall", 0.0),
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 2.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            detector_channels: EnumParam::new(
+                "Detector Channels",
+                defaults.enumeration("detchannels", DetectorChannels::LeftRight),
+            ),
+            detector_weight_a: weight_param(
+                "Detector L/M Weight",
+                defaults.float("detweight1", 1.0),
+            ),
+            detector_weight_b: weight_param(
+                "Detector R/S Weight",
+                defaults.float("detweight2", 1.0),
+            ),
+            startup_ramp: FloatParam::new(
+                "Startup Ramp",
+                defaults.float("startupramp", DEFAULT_STARTUP_RAMP),
+                FloatRange::Linear { min: 0.0, max: 0.2 },
+            )
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            param_smoothing_time: FloatParam::new(
+                "Parameter Smoothing",
+                defaults.float("paramsmooth", DEFAULT_PARAM_SMOOTHING_TIME),
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 0.5,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            quality: EnumParam::new("Quality", defaults.enumeration("quality", Quality::Normal)),
+            rms_compensation: BoolParam::new(
+                "RMS Latency Compensation",
+                defaults.bool("rmscomp", false),
+            ),
+            db_conversion: EnumParam::new(
+                "dB Conversion",
+                defaults.enumeration("dbconv", DbConversion::Auto),
+            ),
+            // NOTE GATE
+            note_gate: BoolParam::new("Note Gate", defaults.bool("notegate", false)),
+            gate_release_time: FloatParam::new(
+                "Gate Release",
+                defaults.float("gaterelease", DEFAULT_GATE_RELEASE_TIME),
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 2.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            // SIDECHAIN
+            sidechain: BoolParam::new("External Sidechain", defaults.bool("sidechain", false)),
+            sidechain_channels: EnumParam::new(
+                "Sidechain Channels",
+                defaults.enumeration("scchannels", SidechainChannels::Stereo),
+            ),
+            sidechain_gain: FloatParam::new(
+                "Sidechain Trim",
+                defaults.float("scgain", util::db_to_gain(0.0)),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 24.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            sidechain_invert: BoolParam::new(
+                "Sidechain Polarity Invert",
+                defaults.bool("scinvert", false),
+            ),
+            sidechain_offset: FloatParam::new(
+                "Sidechain Offset",
+                defaults.float("scoffset", 0.0),
+                FloatRange::Linear {
+                    min: -MAX_SIDECHAIN_OFFSET,
+                    max: MAX_SIDECHAIN_OFFSET,
+                },
+            )
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            freeze_gain_reduction: BoolParam::new("Freeze GR", defaults.bool("freezegr", false)),
+            // GR OUTPUT
+            gr_output: FloatParam::new(
+                "GR Output",
+                0.0,
+                FloatRange::Linear {
+                    min: -MAX_GR_OUTPUT_DB,
+                    max: 0.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(v2s_f32_rounded(1))
+            .with_string_to_value(s2v_db()),
+            gr_output_write: BoolParam::new(
+                "Write GR Output",
+                defaults.bool("groutputwrite", false),
+            ),
+            latency_output: FloatParam::new(
+                "Latency",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: MAX_LATENCY_OUTPUT_MS,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_rounded(2))
+            .non_automatable(),
+            // FORCE OPEN/CLOSED
+            force_open: BoolParam::new("Force Open", defaults.bool("forceopen", false)),
+            force_closed: BoolParam::new("Force Closed", defaults.bool("forceclosed", false)),
+            // MODULATION
+            lfo1_rate: lfo_rate_param("LFO 1 Rate", defaults.float("lfo1rate", 1.0)),
+            lfo1_shape: EnumParam::new(
+                "LFO 1 Shape",
+                defaults.enumeration("lfo1shape", LfoShape::Sine),
+            ),
+            lfo1_target: EnumParam::new(
+                "LFO 1 Target",
+                defaults.enumeration("lfo1target", ModTarget::Off),
+            ),
+            lfo1_depth: depth_param("LFO 1 Depth", defaults.float("lfo1depth", 0.0)),
+            lfo2_rate: lfo_rate_param("LFO 2 Rate", defaults.float("lfo2rate", 0.25)),
+            lfo2_shape: EnumParam::new(
+                "LFO 2 Shape",
+                defaults.enumeration("lfo2shape", LfoShape::Sine),
+            ),
+            lfo2_target: EnumParam::new(
+                "LFO 2 Target",
+                defaults.enumeration("lfo2target", ModTarget::Off),
+            ),
+            lfo2_depth: depth_param("LFO 2 Depth", defaults.float("lfo2depth", 0.0)),
+            env_release: FloatParam::new(
+                "Envelope Release",
+                defaults.float("envrelease", 0.2),
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 2.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_value_to_string(v2s_time_formatter())
+            .with_string_to_value(s2v_time_formatter()),
+            env_target: EnumParam::new(
+                "Envelope Target",
+                defaults.enumeration("envtarget", ModTarget::Off),
+            ),
+            env_depth: depth_param("Envelope Depth", defaults.float("envdepth", 0.0)),
         }
     }
+
+    /// Whether the parameter with this ID has been locked in the editor, see `locked_params`.
+    pub fn is_locked(&self, param_id: &str) -> bool {
+        self.locked_params.read().unwrap().contains(param_id)
+    }
+}
+
+fn lfo_rate_param(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Skewed {
+            min: 0.01,
+            max: 20.0,
+            factor: FloatRange::skew_factor(-2.0),
+        },
+    )
+    .with_unit(" Hz")
+    .with_value_to_string(v2s_f32_rounded(2))
+}
+
+fn depth_param(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Linear {
+            min: -1.0,
+            max: 1.0,
+        },
+    )
+    .with_value_to_string(v2s_rounded_multiplied(0))
+    .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+fn weight_param(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(name, default, FloatRange::Linear { min: 0.0, max: 1.0 })
+        .with_smoother(SmoothingStyle::Linear(10.0))
+        .with_value_to_string(v2s_rounded_multiplied(0))
+        .with_string_to_value(formatters::s2v_f32_percentage())
 }
 
 // very slightly modified NIH-plug formatter
@@ -203,3 +828,57 @@ pub fn v2s_time_formatter() -> Arc<dyn Fn(f32) -> String + Send + Sync> {
         format!("{output:.2} {unit}")
     })
 }
+
+/// Parses a plain decibel value, with or without the `dB` suffix.
+pub fn s2v_db() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(|string| {
+        let string = string.trim();
+        let string = string
+            .strip_suffix("dB")
+            .or_else(|| string.strip_suffix("db"))
+            .unwrap_or(string);
+        string.trim().parse().ok()
+    })
+}
+
+/// Like nih-plug's `v2s_compression_ratio()`, except ratios below 1:1 are shown the other way around,
+/// so `0.5` becomes "1:2" instead of "0.50:1".
+pub fn v2s_ratio(digits: usize) -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(move |value| {
+        if value >= 1.0 {
+            format!("{value:.digits$}:1")
+        } else {
+            format!("1:{:.digits$}", value.recip())
+        }
+    })
+}
+
+/// The inverse of `v2s_ratio()`. Accepts both "4:1" and "1:2" style ratios, and bare numbers.
+pub fn s2v_ratio() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(|string| {
+        let string = string.trim();
+        match string.split_once(':') {
+            Some((left, right)) => {
+                let left: f32 = left.trim().parse().ok()?;
+                let right: f32 = right.trim().parse().ok()?;
+                (right != 0.0).then(|| left / right)
+            }
+            None => string.parse().ok(),
+        }
+    })
+}
+
+/// The inverse of `v2s_time_formatter()`. Accepts both `ms` and `s` suffixes,
+/// and a bare number is treated as milliseconds since that's what's displayed most of the time.
+pub fn s2v_time_formatter() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(|string| {
+        let string = string.trim().to_lowercase();
+        if let Some(ms) = string.strip_suffix("ms") {
+            ms.trim().parse::<f32>().ok().map(|ms| ms / 1000.0)
+        } else if let Some(seconds) = string.strip_suffix('s') {
+            seconds.trim().parse().ok()
+        } else {
+            string.parse::<f32>().ok().map(|ms| ms / 1000.0)
+        }
+    })
+}
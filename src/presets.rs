@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use nih_plug::prelude::*;
+
+use crate::{
+    param_writer::{ParamBatch, ParamWriter},
+    params::GainParams,
+};
+
+// TODO:
+// A/B morph position as an automatable parameter. there's no A/B or morph engine yet. when there is, the morph should
+// apply the interpolated values as modulation on top of the parameters (so automating it doesn't fight with the
+// host's own automation or flood the undo history) instead of writing them through the `ParamWriter` like presets do,
+// and skip locked parameters (`GainParams::locked_params`)
+
+// TODO:
+// a scriptable API for generating preset banks (e.g. a threshold series) from outside the plugin. that needs the
+// extracted DSP/preset crate, which doesn't exist yet: this is a cdylib with everything private. once it does, move
+// `Preset` over there with owned names and values and serde, so the schema is just the parameter IDs and plain values
+// below, and have the factory banks built by the same code instead of being written out by hand
+
+/// A named set of **plain** parameter values, keyed by parameter ID.
+/// Parameters that aren't listed keep whatever value they had.
+pub struct Preset {
+    pub name: &'static str,
+    pub values: &'static [(&'static str, f32)],
+}
+
+/// The built-in presets. MIDI program change `n` loads preset `n`, once the editor has been opened (see `ParamWriter`).
+pub const FACTORY_PRESETS: &[Preset] = &[
+    Preset {
+        name: "Init",
+        values: &[
+            ("lvldetection", 0.0),
+            ("threshold", -10.0),
+            ("ratio", 4.0),
+            ("attack", 0.001),
+            ("release", 0.05),
+            ("knee", 5.0),
+            ("ingain", 1.0),
+            ("outgain", 1.0),
+            ("drywet", 1.0),
+        ],
+    },
+    Preset {
+        name: "Gentle Glue",
+        values: &[
+            ("lvldetection", 0.0),
+            ("threshold", -18.0),
+            ("ratio", 2.0),
+            ("attack", 0.03),
+            ("release", 0.2),
+            ("knee", 10.0),
+            ("drywet", 1.0),
+        ],
+    },
+    Preset {
+        name: "Vocal Leveler",
+        values: &[
+            ("lvldetection", 0.0),
+            ("threshold", -20.0),
+            ("ratio", 3.0),
+            ("attack", 0.005),
+            ("release", 0.1),
+            ("knee", 6.0),
+            ("drywet", 1.0),
+        ],
+    },
+    Preset {
+        name: "Drum Smash",
+        values: &[
+            ("lvldetection", 1.0),
+            ("threshold", -30.0),
+            ("ratio", 10.0),
+            ("attack", 0.0005),
+            ("release", 0.08),
+            ("knee", 2.0),
+            ("drywet", 0.5),
+        ],
+    },
+    Preset {
+        name: "Brickwall-ish",
+        values: &[
+            ("lvldetection", 1.0),
+            ("threshold", -3.0),
+            ("ratio", 100.0),
+            ("attack", 0.0),
+            ("release", 0.05),
+            ("knee", 0.0),
+            ("drywet", 1.0),
+        ],
+    },
+];
+
+/// Quick starting points for the ballistics (attack, release, knee and detector) of common material.
+/// These leave the threshold and ratio alone, since those depend on the level of whatever is being compressed.
+pub const MATERIALS: &[Preset] = &[
+    Preset {
+        name: "Vocals",
+        values: &[
+            ("lvldetection", 0.0),
+            ("attack", 0.005),
+            ("release", 0.1),
+            ("knee", 6.0),
+            ("detrise", 0.0),
+            ("detfall", 0.05),
+        ],
+    },
+    Preset {
+        name: "Drums",
+        values: &[
+            ("lvldetection", 1.0),
+            // slow enough to let the transients through
+            ("attack", 0.01),
+            ("release", 0.06),
+            ("knee", 2.0),
+            ("detrise", 0.0),
+            ("detfall", 0.0),
+        ],
+    },
+    Preset {
+        name: "Bus",
+        values: &[
+            ("lvldetection", 0.0),
+            ("attack", 0.03),
+            ("release", 0.2),
+            ("knee", 8.0),
+            ("detrise", 0.005),
+            ("detfall", 0.1),
+        ],
+    },
+    Preset {
+        name: "Bass",
+        values: &[
+            ("lvldetection", 0.0),
+            // anything much faster than a cycle of the lowest notes starts distorting them
+            ("attack", 0.02),
+            ("release", 0.15),
+            ("knee", 4.0),
+            ("detrise", 0.005),
+            ("detfall", 0.08),
+        ],
+    },
+    Preset {
+        name: "Master",
+        values: &[
+            ("lvldetection", 0.0),
+            ("attack", 0.03),
+            ("release", 0.3),
+            ("knee", 10.0),
+            ("detrise", 0.01),
+            ("detfall", 0.2),
+        ],
+    },
+];
+
+/// Applies `preset` through the `ParamWriter`, so every change is seen by the host. Locked parameters are skipped.
+/// Returns `false` if the writer can't change parameters yet (the editor hasn't been opened).
+pub fn apply(
+    preset: &Preset,
+    params: &GainParams,
+    param_ptrs: &HashMap<String, ParamPtr>,
+    writer: &ParamWriter,
+) -> bool {
+    let mut batch = ParamBatch::default();
+    for (id, plain) in preset.values {
+        if params.is_locked(id) {
+            continue;
+        }
+        let Some(param) = param_ptrs.get(*id) else {
+            nih_debug_assert_failure!(
+                "Preset '{}' refers to unknown parameter '{id}'",
+                preset.name
+            );
+            continue;
+        };
+        batch.set_plain(*param, *plain);
+    }
+    writer.apply(batch)
+}
+
+/// Applies `preset` from the editor, through the editor's own `ParamSetter`.
+/// `param_ids` are the parameter pointers paired with their IDs, like the editor keeps them.
+pub fn apply_with_setter(
+    preset: &Preset,
+    params: &GainParams,
+    param_ids: &[(ParamPtr, String)],
+    setter: &ParamSetter,
+) {
+    let mut batch = ParamBatch::default();
+    for (id, plain) in preset.values {
+        if params.is_locked(id) {
+            continue;
+        }
+        let Some((param, _)) = param_ids.iter().find(|(_, other)| other == id) else {
+            nih_debug_assert_failure!(
+                "Preset '{}' refers to unknown parameter '{id}'",
+                preset.name
+            );
+            continue;
+        };
+        batch.set_plain(*param, *plain);
+    }
+    batch.apply_with_setter(setter);
+}
+
+/// Looks up a factory preset by name.
+pub fn find(name: &str) -> Option<&'static Preset> {
+    FACTORY_PRESETS.iter().find(|preset| preset.name == name)
+}
+
+/// A parameter whose current value doesn't match the preset's.
+pub struct Difference {
+    pub param: ParamPtr,
+    pub name: String,
+    /// The preset's value, formatted with the unit.
+    pub preset_value: String,
+    /// The current value, formatted with the unit.
+    pub current_value: String,
+    /// The preset's value, normalized. This is what reverting sets the parameter back to.
+    pub preset_normalized: f32,
+}
+
+/// Finds every parameter in `preset` that has been changed since.
+/// `param_ids` are the parameter pointers paired with their IDs, like the editor keeps them.
+pub fn differences(preset: &Preset, param_ids: &[(ParamPtr, String)]) -> Vec<Difference> {
+    preset
+        .values
+        .iter()
+        .filter_map(|(id, plain)| {
+            let (param, _) = param_ids.iter().find(|(_, other)| other == id)?;
+            // SAFETY: the pointer comes from our own `GainParams`, which lives as long as the plugin does
+            unsafe {
+                let preset_normalized = param.preview_normalized(*plain);
+                let current_normalized = param.unmodulated_normalized_value();
+                // comparing the normalized values so stepped and enum parameters don't trip over float rounding
+                if (preset_normalized - current_normalized).abs() < 1e-4 {
+                    return None;
+                }
+
+                Some(Difference {
+                    param: *param,
+                    name: param.name().to_string(),
+                    preset_value: param.normalized_value_to_string(preset_normalized, true),
+                    current_value: param.normalized_value_to_string(current_normalized, true),
+                    preset_normalized,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Sets a single parameter as its own gesture, through the editor's `ParamSetter`.
+pub fn set_with_setter(param: ParamPtr, normalized: f32, setter: &ParamSetter) {
+    let mut batch = ParamBatch::default();
+    batch.set_normalized(param, normalized);
+    batch.apply_with_setter(setter);
+}
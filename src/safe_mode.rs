@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SafeModeReason {
+    Panic,
+    /// The DSP put out NaN or infinity.
+    NonFinite,
+}
+
+impl SafeModeReason {
+    pub fn description(&self) -> &'static str {
+        match self {
+            SafeModeReason::Panic => "the processing crashed",
+            SafeModeReason::NonFinite => "the processing produced invalid (NaN or infinite) audio",
+        }
+    }
+}
+
+/// Whether the plugin has given up on processing and is passing the audio through untouched, shared between the audio
+/// thread and the editor. The audio thread engages it, the editor clears it again to try processing once more.
+///
+/// This only keeps the host alive when panics unwind. With `panic = "abort"`, or with nih-plug's `assert_process_allocs`
+/// (unwinding allocates), a panic still takes the host down with it.
+#[derive(Default)]
+pub struct SafeMode {
+    /// `0` while processing normally, otherwise one more than the `SafeModeReason`'s index.
+    state: AtomicU8,
+}
+
+impl SafeMode {
+    pub fn reason(&self) -> Option<SafeModeReason> {
+        match self.state.load(Ordering::Relaxed) {
+            0 => None,
+            1 => Some(SafeModeReason::Panic),
+            _ => Some(SafeModeReason::NonFinite),
+        }
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    pub fn engage(&self, reason: SafeModeReason) {
+        let state = match reason {
+            SafeModeReason::Panic => 1,
+            SafeModeReason::NonFinite => 2,
+        };
+        self.state.store(state, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.state.store(0, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    param_writer::{ParamBatch, ParamWriter},
+    params::{GainParams, NON_SETTING_PARAM_IDS},
+};
+
+/// How many scenes can be stored, see `GainParams::scene`.
+pub const SCENE_COUNT: usize = 4;
+
+/// A snapshot of every parameter's **normalized** value, keyed by parameter ID.
+/// Unlike presets these are captured from the current settings, and stored with the project.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub values: Vec<(String, f32)>,
+}
+
+impl Scene {
+    /// Captures the current value of every parameter, except for the scene selector and the outputs (see
+    /// `NON_SETTING_PARAM_IDS`).
+    pub fn capture(params: &GainParams) -> Self {
+        let values = params
+            .param_map()
+            .into_iter()
+            .filter(|(id, _, _)| !NON_SETTING_PARAM_IDS.contains(&id.as_str()))
+            // SAFETY: the pointer comes from our own `GainParams`, which lives as long as the plugin does
+            .map(|(id, param, _)| (id, unsafe { param.unmodulated_normalized_value() }))
+            .collect();
+
+        Self { values }
+    }
+
+    /// Applies the scene through the `ParamWriter`, skipping locked parameters.
+    /// Returns `false` if the writer can't change parameters yet (the editor hasn't been opened).
+    ///
+    /// When switching with `GainParams::scene`, this happens while the processed signal is faded out to the dry
+    /// signal (see `Gain::scene_fade`), so stepped and on/off parameters don't click.
+    pub fn apply(
+        &self,
+        params: &GainParams,
+        param_ptrs: &HashMap<String, ParamPtr>,
+        writer: &ParamWriter,
+    ) -> bool {
+        let mut batch = ParamBatch::default();
+        for (id, normalized) in &self.values {
+            if params.is_locked(id) {
+                continue;
+            }
+            // parameters that have been removed since the scene was stored are simply skipped
+            if let Some(param) = param_ptrs.get(id) {
+                batch.set_normalized(*param, *normalized);
+            }
+        }
+        writer.apply(batch)
+    }
+}
@@ -0,0 +1,92 @@
+use nih_plug::util;
+use serde::{Deserialize, Serialize};
+
+/// Gain reduction below this (in dB) doesn't count as compressing.
+const COMPRESSING_THRESHOLD_DB: f32 = 0.5;
+/// How often the audio thread hands what it gathered over to `GainParams::session_stats`, in seconds.
+const FLUSH_INTERVAL: f32 = 1.0;
+
+/// How hard the plugin has been working over the whole project, saved with the plugin state. Only audio that isn't
+/// silent counts, so an idle transport doesn't water the averages down.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SessionStats {
+    /// How much (non-silent) audio has gone through, in seconds.
+    pub processed_seconds: f64,
+    /// How much of that had at least `COMPRESSING_THRESHOLD_DB` of gain reduction, in seconds.
+    pub compressing_seconds: f64,
+    /// The gain reduction in dB, integrated over time. Divided by `processed_seconds` that's the average.
+    gain_reduction_db_seconds: f64,
+    /// The most gain reduction seen, in dB.
+    pub max_gain_reduction_db: f32,
+    /// The summed up squares of the input (after the input gain) and the output samples, for the level difference.
+    /// This is plain RMS, not LUFS, the K-weighting and gating wouldn't survive being added up across sessions anyway.
+    input_energy: f64,
+    output_energy: f64,
+}
+
+impl SessionStats {
+    /// The average gain reduction over everything processed so far, in dB.
+    pub fn average_gain_reduction_db(&self) -> Option<f32> {
+        (self.processed_seconds > 0.0)
+            .then(|| (self.gain_reduction_db_seconds / self.processed_seconds) as f32)
+    }
+
+    /// How much louder (or quieter, when negative) the output has been than the input, in dB.
+    pub fn level_delta_db(&self) -> Option<f32> {
+        (self.input_energy > 0.0 && self.output_energy > 0.0)
+            .then(|| (10.0 * (self.output_energy / self.input_energy).log10()) as f32)
+    }
+
+    fn merge(&mut self, other: &SessionStats) {
+        self.processed_seconds += other.processed_seconds;
+        self.compressing_seconds += other.compressing_seconds;
+        self.gain_reduction_db_seconds += other.gain_reduction_db_seconds;
+        self.max_gain_reduction_db = self.max_gain_reduction_db.max(other.max_gain_reduction_db);
+        self.input_energy += other.input_energy;
+        self.output_energy += other.output_energy;
+    }
+}
+
+/// The audio thread's side of the `SessionStats`. It only ever gathers what's new since the last flush, and adds that
+/// on top of the saved stats, so resetting them from the editor doesn't get overwritten a moment later.
+#[derive(Default)]
+pub struct StatsCollector {
+    pending: SessionStats,
+    since_flush: f32,
+}
+
+impl StatsCollector {
+    pub fn process_frame(&mut self, input: [f32; 2], output: [f32; 2]) {
+        self.pending.input_energy += (input[0] * input[0] + input[1] * input[1]) as f64;
+        self.pending.output_energy += (output[0] * output[0] + output[1] * output[1]) as f64;
+    }
+
+    /// Adds a block's worth of gain reduction. `gain` is the compressor's gain at the end of the block.
+    pub fn process_block(&mut self, samples: usize, gain: f32, sample_rate: f32) {
+        let seconds = samples as f32 / sample_rate;
+        let gain_reduction_db = (-util::gain_to_db(gain)).max(0.0);
+        self.pending.processed_seconds += seconds as f64;
+        if gain_reduction_db >= COMPRESSING_THRESHOLD_DB {
+            self.pending.compressing_seconds += seconds as f64;
+        }
+        self.pending.gain_reduction_db_seconds += (gain_reduction_db * seconds) as f64;
+        self.pending.max_gain_reduction_db =
+            self.pending.max_gain_reduction_db.max(gain_reduction_db);
+        self.since_flush += seconds;
+    }
+
+    /// Whether it's been long enough since the last `flush()`.
+    pub fn should_flush(&self) -> bool {
+        self.since_flush >= FLUSH_INTERVAL
+    }
+
+    /// Adds everything gathered so far to `stats`. Keeps it around if `stats` is `None`, so the audio thread can just
+    /// pass along whatever `try_write()` gave it.
+    pub fn flush(&mut self, stats: Option<&mut SessionStats>) {
+        if let Some(stats) = stats {
+            stats.merge(&self.pending);
+            self.pending = SessionStats::default();
+            self.since_flush = 0.0;
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use nih_plug::prelude::Enum;
+
+// TODO:
+// "auto-solo while adjusting": grabbing the sidechain filter's frequency knob temporarily turns on SC listen, and
+// turns it back off when the gesture ends. there's no sidechain filter or SC listen yet, add this along with them.
+// the gesture callbacks would come from wrapping the editor's `GuiContext`, like `editor::undo::RecordingContext`
+
+/// Which part of the sidechain the detector listens to.
+#[derive(PartialEq, Enum)]
+pub enum SidechainChannels {
+    Stereo,
+    #[name = "Mono Sum"]
+    MonoSum,
+    #[name = "Left Only"]
+    Left,
+    #[name = "Right Only"]
+    Right,
+}
+
+impl SidechainChannels {
+    /// Picks the channels the detector should see out of a sidechain frame.
+    /// The single-channel options get copied to both sides, so the (linked) detector still sees a stereo signal.
+    pub fn route(&self, [left, right]: [f32; 2]) -> [f32; 2] {
+        match self {
+            SidechainChannels::Stereo => [left, right],
+            SidechainChannels::MonoSum => {
+                let sum = (left + right) * 0.5;
+                [sum, sum]
+            }
+            SidechainChannels::Left => [left, left],
+            SidechainChannels::Right => [right, right],
+        }
+    }
+}
+
+/// Reads a single frame from the external sidechain input, or `None` if the host didn't give us one.
+///
+/// The sidechain's channel count doesn't have to match the main bus's:
+/// - A mono sidechain gets treated as dual mono, same as a mono main input. `SidechainChannels` makes no difference.
+/// - A stereo sidechain always gives the detector both channels, even on a mono bus. The detector is linked, so
+///   either side of the key can trigger gain reduction, unless `SidechainChannels` picks a side or sums them first.
+/// - Anything past the first two channels is ignored.
+pub fn frame(channels: &[&mut [f32]], index: usize) -> Option<[f32; 2]> {
+    let left = *channels.first()?.get(index)?;
+    let right = channels
+        .get(1)
+        .and_then(|channel| channel.get(index).copied())
+        .unwrap_or(left);
+
+    Some([left, right])
+}
@@ -0,0 +1,38 @@
+/// The coefficient for a one-pole filter that gets ~63% of the way to its target in `time` seconds.
+/// A time of zero (or less) means the filter jumps straight to its target.
+pub fn one_pole_coefficient(time: f32, sample_rate: f32) -> f32 {
+    if time <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-1.0 / (time * sample_rate)).exp()
+    }
+}
+
+/// Smooths a parameter with a one-pole filter. Unlike nih-plug's own smoothers, the smoothing time can change while
+/// it's running.
+#[derive(Default)]
+pub struct Smoother {
+    /// `None` until the first sample, or right after a reset, so the first value isn't smoothed in from zero.
+    value: Option<f32>,
+}
+
+impl Smoother {
+    /// Moves towards `target` by `samples` samples, which ends up in the same place as that many calls with a single
+    /// sample. `coefficient` is the per-sample `one_pole_coefficient()`.
+    pub fn next(&mut self, target: f32, coefficient: f32, samples: usize) -> f32 {
+        let value = match self.value {
+            Some(value) if samples == 1 => value + (target - value) * coefficient,
+            Some(value) => {
+                let coefficient = 1.0 - (1.0 - coefficient).powi(samples as i32);
+                value + (target - value) * coefficient
+            }
+            None => target,
+        };
+        self.value = Some(value);
+        value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
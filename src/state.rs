@@ -0,0 +1,42 @@
+use nih_plug::wrapper::state::PluginState;
+
+/// The version of the saved state this build writes. Bump this and add a migration to `MIGRATIONS` whenever a change
+/// to the parameters would make older projects load with the wrong values, e.g. changing a parameter's unit or range.
+pub const CURRENT_STATE_VERSION: u32 = 1;
+/// The key `GainParams::state_version` gets persisted under.
+const STATE_VERSION_KEY: &str = "state-version";
+
+/// Migration `n` turns a version `n` state into a version `n + 1` state. States from before versioning was added
+/// don't have a version at all, and count as version 0.
+///
+/// A migration that changes a parameter's stored value would look something like this:
+/// `if let Some(ParamValue::F32(attack)) = state.params.get_mut("attack") { *attack *= 1000.0 }`
+const MIGRATIONS: [fn(&mut PluginState); CURRENT_STATE_VERSION as usize] = [
+    // 0 -> 1: nothing changed, this is just where the version got introduced
+    |_| {},
+];
+
+/// Brings a state saved by an older version of the plugin up to date before it gets loaded. See `Plugin::filter_state`.
+///
+/// States from a *newer* version get loaded as is, since there's no way of knowing what changed.
+pub fn migrate(state: &mut PluginState) {
+    let version = state
+        .fields
+        .get(STATE_VERSION_KEY)
+        .and_then(|version| version.parse::<u32>().ok())
+        .unwrap_or(0);
+    if version > CURRENT_STATE_VERSION {
+        nih_plug::nih_log!(
+            "Loading a state from a newer version of the plugin (state version {version}), some settings may be off"
+        );
+        return;
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(state);
+    }
+    state.fields.insert(
+        STATE_VERSION_KEY.to_string(),
+        CURRENT_STATE_VERSION.to_string(),
+    );
+}
@@ -0,0 +1,129 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// How long it takes to fade the output out (and back in) around a switch.
+const FADE_TIME: f32 = 0.01;
+/// How long to stay silent at most while waiting for the switch to land, see `SwitchFade::landed()`. The new values get
+/// applied on the GUI thread, which normally takes a few milliseconds, but that's up to the host and it might not get
+/// around to it at all while rendering offline.
+const MAX_HOLD_TIME: f32 = 0.25;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Idle,
+    FadingOut,
+    /// Muted until the switch lands, with this many samples left before giving up on that.
+    Holding(u32),
+    FadingIn,
+}
+
+/// Fades the output out, lets something happen while it's silent, and fades back in.
+/// Used to switch presets from MIDI program changes without clicks. Scene switches use it on the processed signal
+/// alone, so the dry signal keeps playing through the switch.
+pub struct SwitchFade {
+    phase: Phase,
+    gain: f32,
+    /// What to switch to once the output is silent.
+    pending: Option<u8>,
+    /// Set once the last switch has been applied, see `landed()`.
+    landed: Arc<AtomicBool>,
+}
+
+impl Default for SwitchFade {
+    fn default() -> Self {
+        Self {
+            phase: Phase::Idle,
+            gain: 1.0,
+            pending: None,
+            landed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl SwitchFade {
+    /// Requests a switch to `target`. If a switch is already underway, the latest request wins.
+    pub fn start(&mut self, target: u8) {
+        self.pending = Some(target);
+        if matches!(self.phase, Phase::Idle | Phase::FadingIn) {
+            self.phase = Phase::FadingOut;
+        }
+    }
+
+    /// A flag for whoever applies the switch to set once the new values are in place. The output stays silent until
+    /// then (or until `MAX_HOLD_TIME` runs out), so nothing gets heard halfway through the switch.
+    pub fn landed(&self) -> Arc<AtomicBool> {
+        self.landed.clone()
+    }
+
+    pub fn reset(&mut self) {
+        // the flag is shared, so it stays
+        self.phase = Phase::Idle;
+        self.gain = 1.0;
+        self.pending = None;
+    }
+
+    /// Advances the fade by a single sample. Returns the gain to apply to that sample,
+    /// and the switch target at the exact moment it should be applied.
+    pub fn next(&mut self, sample_rate: f32) -> (f32, Option<u8>) {
+        let step = 1.0 / (FADE_TIME * sample_rate);
+        let hold_samples = (MAX_HOLD_TIME * sample_rate) as u32;
+        let mut switch = None;
+
+        match self.phase {
+            Phase::Idle => (),
+            Phase::FadingOut => {
+                self.gain = (self.gain - step).max(0.0);
+                if self.gain == 0.0 {
+                    switch = self.pending.take();
+                    self.landed.store(false, Ordering::Relaxed);
+                    self.phase = Phase::Holding(hold_samples);
+                }
+            }
+            // another request came in while we're already silent, so just switch again
+            Phase::Holding(_) if self.pending.is_some() => {
+                switch = self.pending.take();
+                self.landed.store(false, Ordering::Relaxed);
+                self.phase = Phase::Holding(hold_samples);
+            }
+            Phase::Holding(_) if self.landed.load(Ordering::Acquire) => {
+                self.phase = Phase::FadingIn
+            }
+            Phase::Holding(0) => self.phase = Phase::FadingIn,
+            Phase::Holding(samples_left) => self.phase = Phase::Holding(samples_left - 1),
+            Phase::FadingIn => {
+                self.gain = (self.gain + step).min(1.0);
+                if self.gain == 1.0 {
+                    self.phase = Phase::Idle;
+                }
+            }
+        }
+
+        (self.gain, switch)
+    }
+}
+
+/// Fades the processing in after the plugin starts (or gets reset), so the first few milliseconds, while the detector
+/// is still catching up with the input, don't end in a sudden clamp. Until then the output is mostly the dry signal.
+/// See `GainParams::startup_ramp`.
+#[derive(Default)]
+pub struct StartupRamp {
+    /// Seconds since the last reset.
+    elapsed: f32,
+}
+
+impl StartupRamp {
+    /// Advances the ramp by a single sample. Returns how much of the processed signal to use, `0.0..=1.0`.
+    pub fn next(&mut self, ramp_time: f32, sample_rate: f32) -> f32 {
+        if self.elapsed >= ramp_time {
+            return 1.0;
+        }
+        self.elapsed += 1.0 / sample_rate;
+        (self.elapsed / ramp_time).min(1.0)
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
@@ -0,0 +1,210 @@
+use nih_plug::prelude::*;
+
+use crate::params::{GainParams, NON_SETTING_PARAM_IDS};
+
+/// The non-commercial manufacturer ID, followed by two bytes identifying this plugin.
+const HEADER: [u8; 4] = [0xF0, 0x7D, b'f', b'n'];
+const END: u8 = 0xF7;
+const COMMAND_DUMP_REQUEST: u8 = 0x01;
+const COMMAND_DUMP: u8 = 0x02;
+
+/// How many parameters a single dump can hold. Comfortably more than we have.
+pub const MAX_DUMP_PARAMS: usize = 80;
+/// Each parameter's key gets stored as a 28 bit integer spread over four 7 bit data bytes.
+const BYTES_PER_KEY: usize = 4;
+const KEY_MASK: u32 = (1 << 28) - 1;
+/// Each value gets stored as a 21 bit integer spread over three 7 bit data bytes.
+const BYTES_PER_VALUE: usize = 3;
+const BYTES_PER_ENTRY: usize = BYTES_PER_KEY + BYTES_PER_VALUE;
+const VALUE_RESOLUTION: f32 = ((1 << 21) - 1) as f32;
+/// Header, command, parameter count, entries, end byte.
+const BUFFER_SIZE: usize = HEADER.len() + 2 + MAX_DUMP_PARAMS * BYTES_PER_ENTRY + 1;
+/// SysEx messages for backing up and restoring the plugin's settings without going through the host.
+///
+/// - `F0 7D 66 6E 01 F7` requests a dump, which the plugin answers with a `Dump` message.
+/// - `F0 7D 66 6E 02 <count> <entries...> F7` is a dump. Sending one to the plugin restores those settings, once the
+///   editor has been opened (see `ParamWriter`).
+///
+/// Every entry in a dump is a parameter's key (see `param_key()`) followed by its normalized value, so dumps keep
+/// working when parameters get added, removed or moved around. Parameters the dump doesn't have a value for are left
+/// alone when restoring it, and values for parameters that don't exist anymore are ignored. The non-parameter state
+/// (editor size, theme, OSC settings, MIDI mappings) is not included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateSysEx {
+    DumpRequest,
+    Dump {
+        entries: [(u32, f32); MAX_DUMP_PARAMS],
+        len: usize,
+    },
+}
+
+impl StateSysEx {
+    /// Builds a dump from the current (unmodulated) parameter values, see `dump_params()`.
+    pub fn dump(params: &[(u32, ParamPtr)]) -> Self {
+        let mut entries = [(0, 0.0); MAX_DUMP_PARAMS];
+        let len = params.len().min(MAX_DUMP_PARAMS);
+        for (entry, (key, param)) in entries.iter_mut().zip(params) {
+            // SAFETY: the pointers come from our own `GainParams`, which lives as long as the plugin does
+            *entry = (*key, unsafe { param.unmodulated_normalized_value() });
+        }
+
+        Self::Dump { entries, len }
+    }
+}
+
+/// A stable key for a parameter ID that fits in a dump: the ID's 32 bit FNV-1a hash, cut down to 28 bits. The test
+/// below makes sure no two parameters end up with the same key.
+pub fn param_key(id: &str) -> u32 {
+    let hash = id.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    hash & KEY_MASK
+}
+
+/// Every parameter that goes into a dump, with its key.
+pub fn dump_params(params: &GainParams) -> Vec<(u32, ParamPtr)> {
+    params
+        .param_map()
+        .into_iter()
+        .filter(|(id, _, _)| !NON_SETTING_PARAM_IDS.contains(&id.as_str()))
+        .map(|(id, ptr, _group)| (param_key(&id), ptr))
+        .collect()
+}
+
+impl SysExMessage for StateSysEx {
+    type Buffer = [u8; BUFFER_SIZE];
+
+    fn from_buffer(buffer: &[u8]) -> Option<Self> {
+        let body = buffer.strip_prefix(&HEADER)?.strip_suffix(&[END])?;
+        match body {
+            [COMMAND_DUMP_REQUEST] => Some(Self::DumpRequest),
+            [COMMAND_DUMP, len, data @ ..] => {
+                let len = *len as usize;
+                if len > MAX_DUMP_PARAMS || data.len() != len * BYTES_PER_ENTRY {
+                    return None;
+                }
+
+                let mut entries = [(0, 0.0); MAX_DUMP_PARAMS];
+                for (entry, bytes) in entries.iter_mut().zip(data.chunks_exact(BYTES_PER_ENTRY)) {
+                    let (key, value) = bytes.split_at(BYTES_PER_KEY);
+                    let raw_value = from_data_bytes(value);
+                    *entry = (from_data_bytes(key), raw_value as f32 / VALUE_RESOLUTION);
+                }
+
+                Some(Self::Dump { entries, len })
+            }
+            _ => None,
+        }
+    }
+
+    fn to_buffer(self) -> (Self::Buffer, usize) {
+        let mut buffer = [0u8; BUFFER_SIZE];
+        buffer[..HEADER.len()].copy_from_slice(&HEADER);
+        let mut length = HEADER.len();
+
+        match self {
+            Self::DumpRequest => {
+                buffer[length] = COMMAND_DUMP_REQUEST;
+                length += 1;
+            }
+            Self::Dump { entries, len } => {
+                buffer[length] = COMMAND_DUMP;
+                buffer[length + 1] = len as u8;
+                length += 2;
+
+                for (key, value) in &entries[..len] {
+                    to_data_bytes(*key, &mut buffer[length..length + BYTES_PER_KEY]);
+                    length += BYTES_PER_KEY;
+                    let raw_value = (value.clamp(0.0, 1.0) * VALUE_RESOLUTION).round() as u32;
+                    to_data_bytes(raw_value, &mut buffer[length..length + BYTES_PER_VALUE]);
+                    length += BYTES_PER_VALUE;
+                }
+            }
+        }
+
+        buffer[length] = END;
+        (buffer, length + 1)
+    }
+}
+
+/// Spreads `value` over `bytes`, seven bits per byte, most significant first.
+fn to_data_bytes(value: u32, bytes: &mut [u8]) {
+    for (index, byte) in bytes.iter_mut().rev().enumerate() {
+        *byte = ((value >> (7 * index)) & 0x7F) as u8;
+    }
+}
+
+fn from_data_bytes(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 7) | (*byte as u32 & 0x7F))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn param_keys_are_unique() {
+        let params = GainParams::new();
+        let ids: Vec<String> = params
+            .param_map()
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect();
+        let keys: HashSet<u32> = ids.iter().map(|id| param_key(id)).collect();
+        assert_eq!(keys.len(), ids.len());
+    }
+
+    #[test]
+    fn dump_skips_outputs_and_scene() {
+        let params = GainParams::new();
+        let dumped = dump_params(&params);
+        assert!(dumped.len() <= MAX_DUMP_PARAMS);
+        for id in NON_SETTING_PARAM_IDS {
+            assert!(dumped.iter().all(|(key, _)| *key != param_key(id)), "{id}");
+        }
+    }
+
+    #[test]
+    fn dump_round_trips() {
+        let mut entries = [(0, 0.0); MAX_DUMP_PARAMS];
+        entries[0] = (param_key("threshold"), 0.25);
+        entries[1] = (param_key("ratio"), 1.0);
+        entries[2] = (KEY_MASK, 0.0);
+        let dump = StateSysEx::Dump { entries, len: 3 };
+
+        let (buffer, length) = dump.to_buffer();
+        assert!(buffer[HEADER.len()..length - 1]
+            .iter()
+            .all(|byte| *byte < 0x80));
+        let Some(StateSysEx::Dump {
+            entries: decoded,
+            len,
+        }) = StateSysEx::from_buffer(&buffer[..length])
+        else {
+            panic!("the dump didn't parse");
+        };
+        assert_eq!(len, 3);
+        for ((key, value), (decoded_key, decoded_value)) in
+            entries[..len].iter().zip(&decoded[..len])
+        {
+            assert_eq!(key, decoded_key);
+            assert!((value - decoded_value).abs() <= VALUE_RESOLUTION.recip());
+        }
+    }
+
+    #[test]
+    fn truncated_dump_is_rejected() {
+        let (buffer, length) = StateSysEx::Dump {
+            entries: [(param_key("threshold"), 0.5); MAX_DUMP_PARAMS],
+            len: 2,
+        }
+        .to_buffer();
+        let mut truncated = buffer[..length - 2].to_vec();
+        truncated.push(END);
+        assert_eq!(StateSysEx::from_buffer(&truncated), None);
+    }
+}
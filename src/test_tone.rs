@@ -0,0 +1,151 @@
+use std::{
+    f32::consts::TAU,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use fundsp::hacker::{shared, Shared};
+use nih_plug::util;
+
+/// How long a burst lasts, and how long the gap after it lasts, in seconds.
+const BURST_ON: f32 = 0.25;
+const BURST_OFF: f32 = 0.75;
+/// How far the tone drops between bursts, in dB. It doesn't drop to silence, so the gaps show the release instead of
+/// pausing the meters (see `SilenceDetector`).
+const BURST_DROP_DB: f32 = 20.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ToneKind {
+    Sine,
+    /// The sine, alternating between its level and `BURST_DROP_DB` below that. Shows the attack and the release.
+    Burst,
+    PinkNoise,
+}
+
+impl ToneKind {
+    pub const ALL: [ToneKind; 3] = [ToneKind::Sine, ToneKind::Burst, ToneKind::PinkNoise];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToneKind::Sine => "Sine",
+            ToneKind::Burst => "Bursts",
+            ToneKind::PinkNoise => "Pink Noise",
+        }
+    }
+
+    fn from_index(index: u8) -> Self {
+        Self::ALL
+            .get(index as usize)
+            .copied()
+            .unwrap_or(ToneKind::Sine)
+    }
+}
+
+/// Settings for the built-in test tone, shared between the editor and the audio thread. Nothing here is saved, the
+/// tone always starts out off.
+pub struct TestTone {
+    pub enabled: AtomicBool,
+    kind: AtomicU8,
+    /// The tone's peak level, in dBFS.
+    pub level_db: Shared,
+    /// The sine's frequency, in Hz.
+    pub frequency: Shared,
+    /// Replaces the input when set, and gets added to it otherwise.
+    pub replace_input: AtomicBool,
+}
+
+impl Default for TestTone {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            kind: AtomicU8::new(0),
+            level_db: shared(-18.0),
+            frequency: shared(1000.0),
+            replace_input: AtomicBool::new(true),
+        }
+    }
+}
+
+impl TestTone {
+    pub fn kind(&self) -> ToneKind {
+        ToneKind::from_index(self.kind.load(Ordering::Relaxed))
+    }
+
+    pub fn set_kind(&self, kind: ToneKind) {
+        let index = ToneKind::ALL
+            .iter()
+            .position(|other| *other == kind)
+            .unwrap();
+        self.kind.store(index as u8, Ordering::Relaxed);
+    }
+}
+
+/// The audio thread's side of the test tone.
+#[derive(Default)]
+pub struct ToneGenerator {
+    /// 0..1
+    phase: f32,
+    /// Where in the on/off cycle the bursts are, in seconds.
+    burst_time: f32,
+    noise_seed: u32,
+    /// Paul Kellett's "economy" pink noise filter.
+    pink: [f32; 3],
+}
+
+impl ToneGenerator {
+    /// Applies the test tone to one frame of input, if it's enabled.
+    pub fn process(&mut self, frame: [f32; 2], tone: &TestTone, sample_rate: f32) -> [f32; 2] {
+        if !tone.enabled.load(Ordering::Relaxed) {
+            return frame;
+        }
+
+        let level = util::db_to_gain(tone.level_db.value());
+        let sample = match tone.kind() {
+            ToneKind::Sine => self.sine(tone.frequency.value(), sample_rate) * level,
+            ToneKind::Burst => {
+                self.burst_time = (self.burst_time + 1.0 / sample_rate) % (BURST_ON + BURST_OFF);
+                let level = if self.burst_time < BURST_ON {
+                    level
+                } else {
+                    level * util::db_to_gain(-BURST_DROP_DB)
+                };
+                self.sine(tone.frequency.value(), sample_rate) * level
+            }
+            ToneKind::PinkNoise => self.pink_noise() * level,
+        };
+
+        if tone.replace_input.load(Ordering::Relaxed) {
+            [sample; 2]
+        } else {
+            frame.map(|input| input + sample)
+        }
+    }
+
+    fn sine(&mut self, frequency: f32, sample_rate: f32) -> f32 {
+        let sample = (self.phase * TAU).sin();
+        self.phase = (self.phase + frequency / sample_rate).fract();
+        sample
+    }
+
+    /// Pink noise, scaled to stay roughly within -1..1.
+    fn pink_noise(&mut self) -> f32 {
+        // xorshift, plenty for a test signal. it gets stuck on zero, so that's where it starts over
+        if self.noise_seed == 0 {
+            self.noise_seed = 0x9e37_79b9;
+        }
+        self.noise_seed ^= self.noise_seed << 13;
+        self.noise_seed ^= self.noise_seed >> 17;
+        self.noise_seed ^= self.noise_seed << 5;
+        let white = self.noise_seed as f32 / u32::MAX as f32 * 2.0 - 1.0;
+
+        self.pink[0] = 0.99765 * self.pink[0] + white * 0.0990460;
+        self.pink[1] = 0.96300 * self.pink[1] + white * 0.2965164;
+        self.pink[2] = 0.57000 * self.pink[2] + white * 1.0526913;
+        (self.pink[0] + self.pink[1] + self.pink[2] + white * 0.1848) * 0.25
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.burst_time = 0.0;
+        self.pink = [0.0; 3];
+    }
+}
@@ -0,0 +1,64 @@
+use nih_plug::util;
+
+use crate::{detector::filter_coefficient, smoothing::one_pole_coefficient};
+
+/// The frequency the tilt pivots around, in Hz. Everything above it goes up while everything below it goes down (or
+/// the other way around), by half the tilt each.
+const PIVOT: f32 = 800.0;
+/// How much gain reduction it takes to reach the full depth, in dB.
+const FULL_DEPTH_GR_DB: f32 = 12.0;
+/// How long the filter gains take to follow the gain reduction, in seconds. The gain reduction itself is already
+/// smoothed by the attack and release, this only takes the edge off the steps between blocks.
+const GAIN_SMOOTHING: f32 = 0.01;
+
+/// The "dynamic tone" stage: a gentle tilt EQ on the compressed signal that follows the gain reduction, mostly to
+/// bring back the top end heavy compression tends to dull. See `GainParams::tone_depth`.
+pub struct TiltEq {
+    coefficient: f32,
+    smoothing: f32,
+    /// The low pass' state, per channel.
+    state: [f32; 2],
+    low_gain: f32,
+    high_gain: f32,
+}
+
+impl TiltEq {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            coefficient: filter_coefficient(PIVOT, sample_rate),
+            smoothing: one_pole_coefficient(GAIN_SMOOTHING, sample_rate),
+            state: [0.0; 2],
+            low_gain: 1.0,
+            high_gain: 1.0,
+        }
+    }
+
+    /// `gain` is the compressor's current gain, `depth_db` the tilt at `FULL_DEPTH_GR_DB` of gain reduction. Positive
+    /// depths make the signal brighter. Only gain reduction tilts anything, an upward expander's boost is ignored.
+    pub fn tilt_db(gain: f32, depth_db: f32) -> f32 {
+        let gain_reduction_db = (-util::gain_to_db(gain)).max(0.0);
+        depth_db * (gain_reduction_db / FULL_DEPTH_GR_DB).min(1.0)
+    }
+
+    pub fn process(&mut self, frame: [f32; 2], tilt_db: f32) -> [f32; 2] {
+        let low_target = util::db_to_gain(-tilt_db / 2.0);
+        let high_target = low_target.recip();
+        self.low_gain += (low_target - self.low_gain) * self.smoothing;
+        self.high_gain += (high_target - self.high_gain) * self.smoothing;
+
+        [0, 1].map(|channel| {
+            // TPT one-pole low pass, the highs are whatever it leaves out
+            let v = (frame[channel] - self.state[channel]) * self.coefficient;
+            let low = v + self.state[channel];
+            self.state[channel] = low + v;
+            let high = frame[channel] - low;
+            low * self.low_gain + high * self.high_gain
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.state = [0.0; 2];
+        self.low_gain = 1.0;
+        self.high_gain = 1.0;
+    }
+}
@@ -0,0 +1,106 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use nih_plug::prelude::*;
+
+use crate::params::NON_SETTING_PARAM_IDS;
+
+/// Settings saved from the editor as the starting point for new instances, keyed by parameter ID.
+///
+/// These get baked into the parameters' defaults when the plugin is created (see `GainParams::new()`), so a new
+/// instance starts out with them and resetting a parameter goes back to them too. Restoring a project overrides them
+/// like it would the factory defaults.
+///
+/// The values are **plain** values, stored one `id value` pair per line in the user's config directory, so they're
+/// shared by every instance in every host. Enums and bools are stored as their index.
+#[derive(Default)]
+pub struct UserDefaults {
+    values: HashMap<String, f32>,
+}
+
+impl UserDefaults {
+    /// Loads the user defaults, or returns empty ones (which fall back to the factory defaults) if there aren't any.
+    pub fn load() -> Self {
+        let Some(contents) = path().and_then(|path| fs::read_to_string(path).ok()) else {
+            return Self::default();
+        };
+        let values = contents
+            .lines()
+            .filter_map(|line| {
+                let (id, value) = line.split_once(' ')?;
+                Some((id.to_string(), value.trim().parse().ok()?))
+            })
+            // older versions also saved the outputs
+            .filter(|(id, _)| !NON_SETTING_PARAM_IDS.contains(&id.as_str()))
+            .collect();
+
+        Self { values }
+    }
+
+    /// Saves the current value of every parameter as the user defaults, except for the scene selector and the outputs
+    /// (see `NON_SETTING_PARAM_IDS`).
+    pub fn save(params: &impl Params) -> io::Result<()> {
+        let path = path().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "couldn't find a config directory")
+        })?;
+        let contents: String = params
+            .param_map()
+            .into_iter()
+            .filter(|(id, _, _)| !NON_SETTING_PARAM_IDS.contains(&id.as_str()))
+            .map(|(id, param, _)| {
+                // SAFETY: the pointer comes from our own `GainParams`, which lives as long as the plugin does
+                let plain = unsafe { param.preview_plain(param.unmodulated_normalized_value()) };
+                format!("{id} {plain}\n")
+            })
+            .collect();
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, contents)
+    }
+
+    /// Goes back to the factory defaults for new instances.
+    pub fn clear() -> io::Result<()> {
+        match path() {
+            Some(path) if path.exists() => fs::remove_file(path),
+            _ => Ok(()),
+        }
+    }
+
+    /// Defaults that don't come from the config file, in the same format: plain values by parameter ID.
+    pub fn from_values(values: HashMap<String, f32>) -> Self {
+        Self { values }
+    }
+
+    pub fn float(&self, id: &str, default: f32) -> f32 {
+        self.values.get(id).copied().unwrap_or(default)
+    }
+
+    pub fn bool(&self, id: &str, default: bool) -> bool {
+        self.values.get(id).map_or(default, |value| *value >= 0.5)
+    }
+
+    pub fn enumeration<T: Enum>(&self, id: &str, default: T) -> T {
+        // clamped in case a variant got removed since the defaults were saved
+        let last = T::variants().len() - 1;
+        self.values.get(id).map_or(default, |value| {
+            T::from_index((value.round() as usize).min(last))
+        })
+    }
+}
+
+/// Where the user defaults are stored. There's no config directory crate in here, so this follows each platform's
+/// convention by hand.
+fn path() -> Option<PathBuf> {
+    let directory = if cfg!(target_os = "windows") {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(std::env::var_os("HOME")?).join("Library/Application Support")
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?
+    };
+
+    Some(directory.join("funih").join("user-defaults.txt"))
+}